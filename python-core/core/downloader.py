@@ -1,3 +1,4 @@
+import io
 import os
 import yt_dlp
 import traceback
@@ -38,6 +39,18 @@ def generate_video_hash(file_path: str, sample_size: int = 4096) -> str:
     return hash_obj.hexdigest()
 
 
+def _with_cookies(opts: dict, cookies: str = None) -> dict:
+    """
+    Copy of yt-dlp options that reads cookies from memory.
+    yt-dlp accepts a text stream as cookiefile; each YoutubeDL instance
+    needs its own because it reads and rewrites the stream.
+    """
+    opts = opts.copy()
+    if cookies:
+        opts["cookiefile"] = io.StringIO(cookies)
+    return opts
+
+
 def download_video(
     url: str,
     output_dir: str = "generate",
@@ -52,28 +65,21 @@ def download_video(
     - Auto-detects and uses aria2c if available (multi-connection download).
     - Aggressive retry settings for HTTP and fragment errors.
     - Python-level retry wrapper for global resilience.
-    - Accepts cookie CONTENT (Netscape format) directly, not file paths.
+    - Accepts cookie CONTENT (Netscape format) directly, not file paths,
+      and never writes it to disk.
     """
     Path(output_dir).mkdir(parents=True, exist_ok=True)
 
     # Auto-select cookie based on URL domain
-    # Write cookie content to temp file if provided
-    cookie_path = None
-    temp_cookie_file = None
+    cookies = None
 
     if "youtube" in url.lower() or "youtu.be" in url.lower():
         if cookies_yt and cookies_yt.strip():
-            temp_cookie_file = os.path.join(output_dir, ".cookies_yt.txt")
-            with open(temp_cookie_file, "w", encoding="utf-8") as f:
-                f.write(cookies_yt)
-            cookie_path = temp_cookie_file
+            cookies = cookies_yt
             print("[Downloader] Using YouTube cookies (from config)")
     elif "bilibili" in url.lower():
         if cookies_bili and cookies_bili.strip():
-            temp_cookie_file = os.path.join(output_dir, ".cookies_bili.txt")
-            with open(temp_cookie_file, "w", encoding="utf-8") as f:
-                f.write(cookies_bili)
-            cookie_path = temp_cookie_file
+            cookies = cookies_bili
             print("[Downloader] Using Bilibili cookies (from config)")
 
     # --- Bulletproof Base Options ---
@@ -94,7 +100,8 @@ def download_video(
 
     # Auto-detect aria2c for multi-connection download
     # NOTE: Disable aria2 if progress_hook is provided, as aria2 doesn't support progress callbacks
-    if shutil.which("aria2c") and not progress_hook:
+    # aria2c would need the cookies written to a file, so skip it when they're set
+    if shutil.which("aria2c") and not progress_hook and not cookies:
         print("[Downloader] aria2c detected! Enabling multi-connection download.")
         base_opts["external_downloader"] = "aria2c"
         base_opts["external_downloader_args"] = {
@@ -108,9 +115,6 @@ def download_video(
     if progress_hook:
         base_opts["progress_hooks"] = [progress_hook]
 
-    if cookie_path:
-        base_opts["cookiefile"] = cookie_path
-
     # --- Python-Level Retry Wrapper ---
     MAX_GLOBAL_RETRIES = 3
     RETRY_COOLDOWN = 5  # seconds
@@ -121,7 +125,7 @@ def download_video(
         meta_opts = base_opts.copy()
         meta_opts["quiet"] = True  # Keep metadata extraction quiet
 
-        with yt_dlp.YoutubeDL(meta_opts) as ydl:
+        with yt_dlp.YoutubeDL(_with_cookies(meta_opts, cookies)) as ydl:
             info = ydl.extract_info(url, download=False)
 
             if not isinstance(info, dict):
@@ -173,10 +177,6 @@ def download_video(
                     print(f"[Downloader] Found matching local file: {video_path}")
                     print(f"[Downloader] Using local file instead of re-downloading")
                     
-                    # Clean up temp cookie file if exists
-                    if temp_cookie_file and os.path.exists(temp_cookie_file):
-                        os.remove(temp_cookie_file)
-                    
                     return {
                         "success": True,
                         "title": title,
@@ -205,7 +205,7 @@ def download_video(
             for attempt in range(MAX_GLOBAL_RETRIES):
                 try:
                     print(f"[Downloader] Attempt {attempt + 1}/{MAX_GLOBAL_RETRIES}...")
-                    with yt_dlp.YoutubeDL(download_opts) as ydl:
+                    with yt_dlp.YoutubeDL(_with_cookies(download_opts, cookies)) as ydl:
                         info = ydl.extract_info(url, download=True)
                         video_path = ydl.prepare_filename(info)
                     break  # Success!
@@ -244,7 +244,7 @@ def download_video(
             for attempt in range(MAX_GLOBAL_RETRIES):
                 try:
                     print(f"[Downloader] Attempt {attempt + 1}/{MAX_GLOBAL_RETRIES}...")
-                    with yt_dlp.YoutubeDL(download_opts) as ydl:
+                    with yt_dlp.YoutubeDL(_with_cookies(download_opts, cookies)) as ydl:
                         info = ydl.extract_info(url, download=True)
                         video_path_temp = ydl.prepare_filename(info)
                     break  # Success!
@@ -506,6 +506,57 @@ Now you have the abstracts of all sections of this article. Based on the followi
         "zh": "启用后AI将进行更深入的分析和推理",
         "en": "When enabled, AI will perform deeper analysis and reasoning",
     },
+    # --- Launcher features (desktop app only) ---
+    "secrets_stored_in": {"zh": "密钥与 Cookie 保存在：", "en": "Keys and cookies are stored in:"},
+    "secrets_backend_keychain": {"zh": "系统钥匙串", "en": "the system keychain"},
+    "secrets_backend_file": {"zh": "加密文件", "en": "an encrypted file"},
+    "settings_save_failed": {"zh": "设置未保存", "en": "Settings not saved"},
+    "lbl_library": {"zh": "资料库位置", "en": "Library Location"},
+    "library_desc": {
+        "zh": "设置、历史记录和生成的笔记都保存在这里。移动时应用会重启",
+        "en": "Settings, history and generated notes live here. The app restarts while it moves",
+    },
+    "btn_move_library": {"zh": "移动资料库", "en": "Move Library"},
+    "lbl_watch_folders": {"zh": "监视文件夹", "en": "Watch Folders"},
+    "watch_folders_desc": {
+        "zh": "放入这些文件夹的新录音会自动加入队列",
+        "en": "New recordings dropped into these folders are queued automatically",
+    },
+    "btn_add_watch_folder": {"zh": "添加文件夹", "en": "Add Folder"},
+    "no_watch_folders": {"zh": "尚未添加文件夹", "en": "No folders yet"},
+    "lbl_logs": {"zh": "日志与诊断", "en": "Logs & Diagnostics"},
+    "btn_open_log": {"zh": "打开日志", "en": "Open Log"},
+    "btn_export_diagnostics": {"zh": "导出诊断包", "en": "Export Diagnostics"},
+    "saved_to": {"zh": "已保存到", "en": "Saved to"},
+    "nav_queue": {"zh": "任务队列", "en": "Queue"},
+    "queue_inputs_hint": {
+        "zh": "每行一个视频链接或本地文件路径",
+        "en": "One video URL or local file path per line",
+    },
+    "btn_queue_add": {"zh": "加入队列", "en": "Add to Queue"},
+    "btn_queue_pause": {"zh": "暂停", "en": "Pause"},
+    "btn_queue_resume": {"zh": "继续", "en": "Resume"},
+    "btn_queue_retry_failed": {"zh": "重试失败任务", "en": "Retry Failed"},
+    "btn_queue_clear_finished": {"zh": "清除已结束", "en": "Clear Finished"},
+    "lbl_queue_concurrency": {"zh": "同时处理", "en": "At once"},
+    "queue_empty": {"zh": "队列为空", "en": "The queue is empty"},
+    "job_queued": {"zh": "等待中", "en": "Queued"},
+    "job_running": {"zh": "处理中", "en": "Running"},
+    "job_done": {"zh": "已完成", "en": "Done"},
+    "job_failed": {"zh": "失败", "en": "Failed"},
+    "job_cancelled": {"zh": "已取消", "en": "Cancelled"},
+    "search_placeholder": {"zh": "搜索笔记...", "en": "Search notes..."},
+    "search_no_results": {"zh": "没有找到结果", "en": "No results"},
+    "btn_export": {"zh": "导出", "en": "Export"},
+    "export_html": {"zh": "网页 (HTML)", "en": "Web page (HTML)"},
+    "export_pdf": {"zh": "PDF", "en": "PDF"},
+    "export_epub": {"zh": "电子书 (EPUB)", "en": "E-book (EPUB)"},
+    "export_srt": {"zh": "字幕 (SRT)", "en": "Subtitles (SRT)"},
+    "export_vtt": {"zh": "字幕 (WebVTT)", "en": "Subtitles (WebVTT)"},
+    "export_ass": {"zh": "字幕 (ASS)", "en": "Subtitles (ASS)"},
+    "export_vault": {"zh": "Obsidian / Logseq 库", "en": "Obsidian / Logseq vault"},
+    "export_anki": {"zh": "Anki 卡组", "en": "Anki deck"},
+    "export_failed": {"zh": "导出失败", "en": "Export failed"},
 }
 
 
@@ -1,165 +1,190 @@
 import json
 import os
 import shutil
+import sqlite3
 import uuid
+from contextlib import closing
 from datetime import datetime
 from typing import List, Dict, Optional
 
-# Add TimeoutError import
-from builtins import TimeoutError
-
 # Get the directory where this module is located (python-core/core/)
 BASE_DIR = os.path.dirname(os.path.dirname(os.path.abspath(__file__)))
-HISTORY_FILE = os.path.join(BASE_DIR, "user_history.json")
-
-# Cross-platform file locking
-try:
-    import fcntl  # Unix/Linux/Mac
-    HAS_FCNTL = True
-except ImportError:
-    HAS_FCNTL = False
-
-try:
-    import msvcrt  # Windows
-    HAS_MSVCRT = True
-except ImportError:
-    HAS_MSVCRT = False
+DATA_DIR = os.environ.get("OAN_DATA_DIR") or BASE_DIR
+
+# The launcher owns the history database and passes its path; running
+# standalone keeps it in the data dir. The old user_history.json is
+# imported by the launcher on first start.
+HISTORY_DB = os.environ.get("OAN_HISTORY_DB") or os.path.join(DATA_DIR, "history.db")
+
+
+# Per-session columns; anything else (status, progress, ...) goes in `extra`
+_SESSION_COLUMNS = (
+    "id",
+    "timestamp",
+    "title",
+    "video_url",
+    "summary",
+    "transcript",
+    "project_dir",
+)
+
+# Must match SCHEMA in src-tauri/src/history.rs
+_SCHEMA_VERSION = 1
+_SCHEMA = """
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    title TEXT NOT NULL,
+    video_url TEXT NOT NULL DEFAULT '',
+    summary TEXT NOT NULL DEFAULT '',
+    transcript TEXT NOT NULL DEFAULT '',
+    project_dir TEXT NOT NULL DEFAULT '',
+    config_snapshot TEXT NOT NULL DEFAULT '{}',
+    extra TEXT NOT NULL DEFAULT '{}'
+);
+CREATE INDEX IF NOT EXISTS sessions_position ON sessions (position);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+"""
+
+
+def _connect() -> sqlite3.Connection:
+    """Open the history database, creating the schema if needed."""
+    os.makedirs(os.path.dirname(HISTORY_DB) or ".", exist_ok=True)
+    conn = sqlite3.connect(HISTORY_DB, timeout=5.0)
+    conn.row_factory = sqlite3.Row
+    conn.execute("PRAGMA journal_mode=WAL")
+    version = conn.execute("PRAGMA user_version").fetchone()[0]
+    if version > _SCHEMA_VERSION:
+        conn.close()
+        raise RuntimeError(
+            f"History database schema v{version} is newer than supported v{_SCHEMA_VERSION}"
+        )
+    conn.executescript(_SCHEMA)
+    conn.execute(f"PRAGMA user_version = {_SCHEMA_VERSION}")
+    return conn
+
+
+def _row_to_session(row: sqlite3.Row) -> Dict:
+    session = {col: row[col] for col in _SESSION_COLUMNS}
+    try:
+        session["config_snapshot"] = json.loads(row["config_snapshot"])
+    except ValueError:
+        session["config_snapshot"] = {}
+    try:
+        session.update(json.loads(row["extra"]))
+    except ValueError:
+        pass
+    return session
 
 
-class FileLock:
-    """Cross-platform file lock implementation"""
-    
-    def __init__(self, file_path: str, timeout: float = 10.0):
-        self.file_path = file_path
-        self.timeout = timeout
-        self.lock_file_path = file_path + ".lock"
-        self.lock_file = None
-    
-    def __enter__(self):
-        self.acquire()
-        return self
-    
-    def __exit__(self, exc_type, exc_val, exc_tb):
-        self.release()
-    
-    def acquire(self):
-        """Acquire file lock with timeout"""
-        import time
-        
-        start_time = time.time()
-        while True:
-            try:
-                # Try to create and open lock file exclusively
-                self.lock_file = open(self.lock_file_path, 'w')
-                if HAS_FCNTL:
-                    # Unix/Linux/Mac - use flock
-                    fcntl.flock(self.lock_file.fileno(), fcntl.LOCK_EX | fcntl.LOCK_NB)
-                elif HAS_MSVCRT:
-                    # Windows - use locking
-                    # Write a byte to the file and lock it
-                    self.lock_file.write('L')
-                    self.lock_file.flush()
-                    msvcrt.locking(self.lock_file.fileno(), msvcrt.LK_NBLCK, 1)
-                else:
-                    # Fallback: Just create lock file and hope for the best
-                    # This is not atomic but better than nothing
-                    pass
-                break
-            except (IOError, OSError):
-                if self.lock_file:
-                    self.lock_file.close()
-                    self.lock_file = None
-                
-                if time.time() - start_time > self.timeout:
-                    raise TimeoutError(f"Could not acquire lock for {self.file_path} within {self.timeout} seconds")
-                
-                # Wait a bit before retrying
-                time.sleep(0.1)
-    
-    def release(self):
-        """Release file lock"""
-        if self.lock_file:
-            try:
-                if HAS_FCNTL:
-                    # Unix/Linux/Mac - unlock
-                    fcntl.flock(self.lock_file.fileno(), fcntl.LOCK_UN)
-                elif HAS_MSVCRT:
-                    # Windows - unlock
-                    msvcrt.locking(self.lock_file.fileno(), msvcrt.LK_UNLCK, 1)
-                else:
-                    # Fallback: nothing to do
-                    pass
-            except (IOError, OSError):
-                pass
-            finally:
-                self.lock_file.close()
-                self.lock_file = None
-                # Try to remove lock file
-                try:
-                    os.remove(self.lock_file_path)
-                except (IOError, OSError):
-                    pass
-
-
-def _backup_corrupt_history_file(error: Exception) -> Optional[str]:
-    """Backup the corrupt history file for manual inspection."""
-    try:
-        timestamp = datetime.now().strftime("%Y%m%d_%H%M%S")
-        backup_path = f"{HISTORY_FILE}.corrupt-{timestamp}"
-        shutil.copy2(HISTORY_FILE, backup_path)
-        print(f"[Storage] Backed up corrupt history to {backup_path}: {error}")
-        return backup_path
-    except Exception as backup_err:
-        print(f"[Storage] Failed to back up corrupt history: {backup_err}")
-        return None
+def _session_values(session: Dict, position: int) -> tuple:
+    extra = {
+        k: v
+        for k, v in session.items()
+        if k not in _SESSION_COLUMNS and k != "config_snapshot"
+    }
+    return (
+        session["id"],
+        position,
+        session.get("timestamp") or "",
+        session.get("title") or "",
+        session.get("video_url") or "",
+        session.get("summary") or "",
+        session.get("transcript") or "",
+        session.get("project_dir") or "",
+        json.dumps(session.get("config_snapshot") or {}, ensure_ascii=False),
+        json.dumps(extra, ensure_ascii=False, default=str),
+    )
+
+
+def _insert_session(conn: sqlite3.Connection, session: Dict, position: int):
+    conn.execute(
+        """INSERT OR REPLACE INTO sessions
+            (id, position, timestamp, title, video_url, summary, transcript,
+             project_dir, config_snapshot, extra)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)""",
+        _session_values(session, position),
+    )
+
+
+def _upsert_session(conn: sqlite3.Connection, session: Dict, position: int):
+    """Insert `session` at `position`, or update it where it already is."""
+    conn.execute(
+        """INSERT INTO sessions
+            (id, position, timestamp, title, video_url, summary, transcript,
+             project_dir, config_snapshot, extra)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT (id) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            title = excluded.title,
+            video_url = excluded.video_url,
+            summary = excluded.summary,
+            transcript = excluded.transcript,
+            project_dir = excluded.project_dir,
+            config_snapshot = excluded.config_snapshot,
+            extra = excluded.extra""",
+        _session_values(session, position),
+    )
+
+
+def _top_position(conn: sqlite3.Connection) -> int:
+    return conn.execute("SELECT COALESCE(MIN(position), 0) FROM sessions").fetchone()[0]
 
 
 def load_history() -> List[Dict]:
-    """Load all sessions from history file."""
-    if not os.path.exists(HISTORY_FILE):
-        return []
+    """Load all sessions, newest first."""
     try:
-        with FileLock(HISTORY_FILE):
-            with open(HISTORY_FILE, "r", encoding="utf-8") as f:
-                data = json.load(f)
-                # Ensure it's a list
-                if isinstance(data, dict) and "sessions" in data:
-                    return data["sessions"]
-                elif isinstance(data, list):
-                    return data
-                return []
-    except json.JSONDecodeError as e:
-        # Keep a copy of the broken file and reset to a clean state
-        _backup_corrupt_history_file(e)
-        try:
-            save_history([])
-        except Exception as reset_err:
-            print(f"[Storage] Error resetting history after corruption: {reset_err}")
-        print(f"[Storage] Error loading history: {e}")
-        return []
+        with closing(_connect()) as conn:
+            rows = conn.execute("SELECT * FROM sessions ORDER BY position").fetchall()
+            return [_row_to_session(r) for r in rows]
     except Exception as e:
         print(f"[Storage] Error loading history: {e}")
         return []
 
 
-def save_history(sessions: List[Dict]):
-    """Save sessions to history file."""
+def update_session(session_id: str, **fields) -> bool:
+    """Change some fields of one session, leaving the rest of its row as
+    stored. Returns False if there is no such session."""
     try:
-        with FileLock(HISTORY_FILE):
-            tmp_path = f"{HISTORY_FILE}.tmp"
-            try:
-                with open(tmp_path, "w", encoding="utf-8") as f:
-                    json.dump({"sessions": sessions}, f, indent=2, ensure_ascii=False)
-                # Atomic replace to avoid truncated files on crashes
-                os.replace(tmp_path, HISTORY_FILE)
-            finally:
-                if os.path.exists(tmp_path):
-                    try:
-                        os.remove(tmp_path)
-                    except (IOError, OSError):
-                        pass
+        with closing(_connect()) as conn, conn:
+            # Hold the write lock from the read on, so a concurrent change
+            # to the same row isn't written back over
+            conn.execute("BEGIN IMMEDIATE")
+            row = conn.execute(
+                "SELECT * FROM sessions WHERE id = ?", (session_id,)
+            ).fetchone()
+            if row is None:
+                return False
+            session = _row_to_session(row)
+            session.update(fields)
+            _upsert_session(conn, session, row["position"])
+            return True
     except Exception as e:
         print(f"[Storage] Error saving history: {e}")
+        return False
+
+
+def replace_session(old_id: str, session: Dict) -> bool:
+    """Put `session` in the place of session `old_id`, e.g. when a
+    processing placeholder becomes the finished session. Returns False if
+    `old_id` was gone, in which case `session` is added on top."""
+    try:
+        with closing(_connect()) as conn, conn:
+            conn.execute("BEGIN IMMEDIATE")
+            row = conn.execute(
+                "SELECT position FROM sessions WHERE id = ?", (old_id,)
+            ).fetchone()
+            position = row[0] if row else _top_position(conn) - 1
+            conn.execute("DELETE FROM sessions WHERE id = ?", (old_id,))
+            _insert_session(conn, session, position)
+            return row is not None
+    except Exception as e:
+        print(f"[Storage] Error saving history: {e}")
+        return False
 
 
 def create_session(
@@ -169,9 +194,11 @@ def create_session(
     transcript: str,
     project_dir: str,
     config: Dict = None,
+    video_hash: str = None,
 ) -> Dict:
-    """Create a new session object."""
-    return {
+    """Create a new session object. `video_hash` is the processed video's
+    `generate_video_hash`, which the launcher uses to skip duplicates."""
+    session = {
         "id": str(uuid.uuid4()),
         "timestamp": datetime.now().isoformat(),
         "title": title,
@@ -181,137 +208,144 @@ def create_session(
         "project_dir": project_dir,
         "config_snapshot": config or {},
     }
+    if video_hash:
+        session["video_hash"] = video_hash
+    return session
 
 
 def add_session(session: Dict):
     """Add a session to history (prepend) and save."""
-    sessions = load_history()
-    # Prepend to keep newest first
-    sessions.insert(0, session)
-    save_history(sessions)
+    try:
+        with closing(_connect()) as conn, conn:
+            # Prepend to keep newest first
+            _insert_session(conn, session, _top_position(conn) - 1)
+    except Exception as e:
+        print(f"[Storage] Error saving history: {e}")
 
 
 def get_session(session_id: str) -> Optional[Dict]:
     """Get specific session by ID."""
-    sessions = load_history()
-    for s in sessions:
-        if s["id"] == session_id:
-            return s
-    return None
+    try:
+        with closing(_connect()) as conn:
+            row = conn.execute(
+                "SELECT * FROM sessions WHERE id = ?", (session_id,)
+            ).fetchone()
+            return _row_to_session(row) if row else None
+    except Exception as e:
+        print(f"[Storage] Error loading history: {e}")
+        return None
+
+
+def _remove_project_dir(session: Dict):
+    project_dir = session.get("project_dir")
+    if project_dir and os.path.exists(project_dir):
+        try:
+            shutil.rmtree(project_dir)
+            print(f"[Storage] Deleted project folder: {project_dir}")
+        except Exception as e:
+            print(f"[Storage] Error deleting folder {project_dir}: {e}")
 
 
 def delete_session(session_id: str, delete_files: bool = True):
     """Delete a session by ID and optionally remove local project files."""
-    sessions = load_history()
+    session = get_session(session_id)
+    if session and delete_files:
+        _remove_project_dir(session)
 
-    # Find and delete local files if requested
-    if delete_files:
-        for s in sessions:
-            if s["id"] == session_id:
-                project_dir = s.get("project_dir")
-                if project_dir and os.path.exists(project_dir):
-                    try:
-                        shutil.rmtree(project_dir)
-                        print(f"[Storage] Deleted project folder: {project_dir}")
-                    except Exception as e:
-                        print(f"[Storage] Error deleting folder {project_dir}: {e}")
-                break
-
-    # Remove from history
-    sessions = [s for s in sessions if s["id"] != session_id]
-    save_history(sessions)
+    try:
+        with closing(_connect()) as conn, conn:
+            conn.execute("DELETE FROM sessions WHERE id = ?", (session_id,))
+    except Exception as e:
+        print(f"[Storage] Error saving history: {e}")
 
 
 def clear_all_history(delete_files: bool = True):
     """Clear all history and optionally delete all project files."""
     if delete_files:
-        sessions = load_history()
-        for s in sessions:
-            project_dir = s.get("project_dir")
-            if project_dir and os.path.exists(project_dir):
-                try:
-                    shutil.rmtree(project_dir)
-                    print(f"[Storage] Deleted project folder: {project_dir}")
-                except Exception as e:
-                    print(f"[Storage] Error deleting folder {project_dir}: {e}")
+        for s in load_history():
+            _remove_project_dir(s)
 
-    if os.path.exists(HISTORY_FILE):
-        os.remove(HISTORY_FILE)
+    try:
+        with closing(_connect()) as conn, conn:
+            conn.execute("DELETE FROM sessions")
+    except Exception as e:
+        print(f"[Storage] Error clearing history: {e}")
 
 
 def rename_session(session_id: str, new_title: str) -> bool:
     """Rename a session and its local folder."""
     from core.utils import sanitize_filename
 
-    sessions = load_history()
+    s = get_session(session_id)
+    if not s:
+        return False
 
-    for s in sessions:
-        if s["id"] == session_id:
-            old_title = s["title"]
-            old_dir = s.get("project_dir")
-
-            # Update title in session
-            s["title"] = new_title
-
-            # Rename local folder if it exists
-            if old_dir and os.path.exists(old_dir):
-                parent_dir = os.path.dirname(old_dir)
-                new_dir_name = sanitize_filename(new_title)
-                new_dir = os.path.join(parent_dir, new_dir_name)
-
-                # Avoid overwriting existing folders
-                if os.path.exists(new_dir) and new_dir != old_dir:
-                    counter = 1
-                    base_dir = new_dir
-                    
-                    # Find the next available number suffix
-                    while os.path.exists(new_dir):
-                        new_dir = f"{base_dir}_{counter}"
-                        counter += 1
-
-                try:
-                    os.rename(old_dir, new_dir)
-                    s["project_dir"] = new_dir
-
-                    # Update paths in summary/report if they reference old path
-                    if s.get("summary"):
-                        old_basename = os.path.basename(old_dir)
-                        new_basename = os.path.basename(new_dir)
-                        s["summary"] = s["summary"].replace(
-                            f"/generate/{old_basename}", f"/generate/{new_basename}"
-                        )
-
-                    print(f"[Storage] Renamed folder: {old_dir} -> {new_dir}")
-                except Exception as e:
-                    print(f"[Storage] Error renaming folder: {e}")
-                    return False
-
-            save_history(sessions)
-            print(f"[Storage] Renamed session: {old_title} -> {new_title}")
-            return True
+    old_title = s["title"]
+    old_dir = s.get("project_dir")
+
+    # Update title in session
+    s["title"] = new_title
+
+    # Rename local folder if it exists
+    if old_dir and os.path.exists(old_dir):
+        parent_dir = os.path.dirname(old_dir)
+        new_dir_name = sanitize_filename(new_title)
+        new_dir = os.path.join(parent_dir, new_dir_name)
 
-    return False
+        # Avoid overwriting existing folders
+        if os.path.exists(new_dir) and new_dir != old_dir:
+            counter = 1
+            base_dir = new_dir
+
+            # Find the next available number suffix
+            while os.path.exists(new_dir):
+                new_dir = f"{base_dir}_{counter}"
+                counter += 1
+
+        try:
+            os.rename(old_dir, new_dir)
+            s["project_dir"] = new_dir
+
+            # Update paths in summary/report if they reference old path
+            if s.get("summary"):
+                old_basename = os.path.basename(old_dir)
+                new_basename = os.path.basename(new_dir)
+                s["summary"] = s["summary"].replace(
+                    f"/generate/{old_basename}", f"/generate/{new_basename}"
+                )
+
+            print(f"[Storage] Renamed folder: {old_dir} -> {new_dir}")
+        except Exception as e:
+            print(f"[Storage] Error renaming folder: {e}")
+            return False
+
+    try:
+        with closing(_connect()) as conn, conn:
+            conn.execute(
+                "UPDATE sessions SET title = ?, project_dir = ?, summary = ? WHERE id = ?",
+                (s["title"], s.get("project_dir") or "", s.get("summary") or "", session_id),
+            )
+    except Exception as e:
+        print(f"[Storage] Error saving history: {e}")
+        return False
+    print(f"[Storage] Renamed session: {old_title} -> {new_title}")
+    return True
 
 
 def sync_history():
     """Remove history entries whose project folders no longer exist."""
-    sessions = load_history()
-    original_count = len(sessions)
-
-    # Filter out sessions with missing project directories
-    valid_sessions = []
-    for s in sessions:
+    removed_count = 0
+    for s in load_history():
         project_dir = s.get("project_dir")
         if project_dir and os.path.exists(project_dir):
-            valid_sessions.append(s)
-        else:
-            print(
-                f"[Storage] Removing orphan entry: {s.get('title', 'Unknown')} (folder missing)"
-            )
+            continue
+        print(
+            f"[Storage] Removing orphan entry: {s.get('title', 'Unknown')} (folder missing)"
+        )
+        delete_session(s["id"], delete_files=False)
+        removed_count += 1
 
-    removed_count = original_count - len(valid_sessions)
     if removed_count > 0:
-        save_history(valid_sessions)
         print(f"[Storage] Synced history: removed {removed_count} orphan entries")
 
     return removed_count
@@ -332,9 +366,8 @@ def load_chat_history(project_dir: str) -> List[Dict]:
     chat_file = get_chat_file_path(project_dir)
     if os.path.exists(chat_file):
         try:
-            with FileLock(chat_file):
-                with open(chat_file, "r", encoding="utf-8") as f:
-                    return json.load(f)
+            with open(chat_file, "r", encoding="utf-8") as f:
+                return json.load(f)
         except Exception as e:
             print(f"[Storage] Error loading chat history: {e}")
     return []
@@ -343,10 +376,12 @@ def load_chat_history(project_dir: str) -> List[Dict]:
 def save_chat_history(project_dir: str, messages: List[Dict]):
     """Save chat history for a specific session."""
     chat_file = get_chat_file_path(project_dir)
+    # Written aside and swapped in, so a reader never sees half a file
+    tmp_file = chat_file + ".tmp"
     try:
-        with FileLock(chat_file):
-            with open(chat_file, "w", encoding="utf-8") as f:
-                json.dump(messages, f, ensure_ascii=False, indent=2)
+        with open(tmp_file, "w", encoding="utf-8") as f:
+            json.dump(messages, f, ensure_ascii=False, indent=2)
+        os.replace(tmp_file, chat_file)
     except Exception as e:
         print(f"[Storage] Error saving chat history: {e}")
 
@@ -364,7 +399,7 @@ def validate_and_cleanup_sessions():
     
     sessions = load_history()
     valid_sessions = []
-    cleaned_count = 0
+    removed_ids = []
     
     for session in sessions:
         session_id = session.get("id", "")
@@ -391,21 +426,22 @@ def validate_and_cleanup_sessions():
                             print(f"[Storage] Deleted stale project folder: {project_dir}")
                         except Exception as e:
                             print(f"[Storage] Error deleting stale folder {project_dir}: {e}")
-                    cleaned_count += 1
+                    removed_ids.append(session_id)
                 else:
                     # Keep recent temporary sessions
                     valid_sessions.append(session)
             except ValueError:
                 # If we can't parse the timestamp, treat it as invalid
-                cleaned_count += 1
+                removed_ids.append(session_id)
         else:
             # Invalid session ID format, remove it
-            cleaned_count += 1
+            removed_ids.append(session_id)
     
-    # Save cleaned sessions if we removed any
-    if cleaned_count > 0:
-        save_history(valid_sessions)
-        print(f"[Storage] Cleaned up {cleaned_count} invalid/stale sessions")
+    # Folders were handled above; only the rows go here
+    for session_id in removed_ids:
+        delete_session(session_id, delete_files=False)
+    if removed_ids:
+        print(f"[Storage] Cleaned up {len(removed_ids)} invalid/stale sessions")
     
     return valid_sessions
 
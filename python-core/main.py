@@ -4,6 +4,7 @@ import re
 import asyncio
 import time
 from nicegui import ui, run, app
+from fastapi.responses import JSONResponse
 import sys
 import logging
 
@@ -45,12 +46,17 @@ async def retry_async(func, max_retries=3, initial_delay=1, backoff_factor=2, ex
 # Get the directory where this script is located
 BASE_DIR = os.path.dirname(os.path.abspath(__file__))
 
+# User data (config, history, generate/) lives in the per-user library the
+# launcher passes in; running standalone keeps it next to the code
+DATA_DIR = os.environ.get("OAN_DATA_DIR") or BASE_DIR
+os.makedirs(DATA_DIR, exist_ok=True)
+
 # Capture original streams for redirection
 original_stdout = sys.stdout
 original_stderr = sys.stderr
 from openai import AsyncOpenAI
 
-from core.downloader import download_video
+from core.downloader import download_video, generate_video_hash
 from core.transcriber import TranscriberFactory
 from core.visual_processor import process_video_for_vision, extract_frame
 from core import model_manager
@@ -63,7 +69,8 @@ from core.utils import (
 from core.i18n import get_text
 from core.storage import (
     load_history,
-    save_history,
+    update_session,
+    replace_session,
     add_session,
     get_session,
     delete_session,
@@ -77,8 +84,10 @@ from core.storage import (
 )
 
 # --- Configuration & State ---
-CONFIG_FILE = os.path.join(BASE_DIR, "user_config.json")
+CONFIG_FILE = os.path.join(DATA_DIR, "user_config.json")
 DEFAULT_CONFIG = {
+    # Owned by the launcher's typed config (src-tauri/src/config.rs)
+    "schema_version": 1,
     "api_key": "",
     "base_url": "https://api.openai.com/v1",
     "model_name": "gpt-4o",
@@ -185,20 +194,73 @@ def check_first_launch_gpu_reminder():
             print(f"First launch check failed: {e}")
 
 
-GENERATE_DIR = os.path.join(BASE_DIR, "generate")
+GENERATE_DIR = os.path.join(DATA_DIR, "generate")
 if not os.path.exists(GENERATE_DIR):
     os.makedirs(GENERATE_DIR)
 app.add_static_files("/generate", GENERATE_DIR)
 
 
+# --- Secrets ---
+# Under the launcher the API key and cookies live in the OS keychain. They
+# arrive as JSON lines on stdin, stay in memory only, and edits go back to
+# the launcher on stdout instead of into user_config.json.
+SECRET_KEYS = ("api_key", "cookies_yt", "cookies_bili")
+SECRETS_FROM_LAUNCHER = os.environ.get("OAN_SECRETS_STDIN") == "1"
+SECRETS_UPDATE_PREFIX = "@@oan-secrets "
+launcher_secrets = {}
+
+
+def _read_secrets_message():
+    line = sys.stdin.readline()
+    if not line:
+        return None
+    try:
+        return json.loads(line).get("secrets", {})
+    except (ValueError, AttributeError) as e:
+        print(f"[Secrets] Ignoring malformed message from launcher: {e}")
+        return {}
+
+
+def _watch_launcher_secrets():
+    while True:
+        secrets = _read_secrets_message()
+        if secrets is None:
+            return
+        launcher_secrets.update(secrets)
+        state.config.update(secrets)
+
+
+if SECRETS_FROM_LAUNCHER:
+    import threading
+
+    # The launcher writes the first message right after spawning us
+    launcher_secrets.update(_read_secrets_message() or {})
+    threading.Thread(target=_watch_launcher_secrets, daemon=True).start()
+
+
+# Why user_config.json could not be read, if it couldn't. The file is left
+# as it is and never overwritten until it has been fixed.
+config_error = None
+
+
 def load_config():
+    global config_error
     cfg = DEFAULT_CONFIG.copy()
+    config_error = None
     if os.path.exists(CONFIG_FILE):
         try:
             with open(CONFIG_FILE, "r", encoding="utf-8") as f:
-                cfg = {**DEFAULT_CONFIG, **json.load(f)}
-        except Exception:
-            cfg = DEFAULT_CONFIG.copy()
+                stored = json.load(f)
+            if not isinstance(stored, dict):
+                raise ValueError("not a JSON object")
+            cfg = {**DEFAULT_CONFIG, **stored}
+        except Exception as e:
+            # Run on the defaults for now, but don't save them over the file
+            config_error = f"{CONFIG_FILE} is unreadable: {e}"
+            print(f"[Config] {config_error}")
+
+    if SECRETS_FROM_LAUNCHER:
+        cfg.update({key: launcher_secrets.get(key, "") for key in SECRET_KEYS})
 
     # If stored hardware mode is invalid for this machine, fall back to detected type
     if cfg.get("hardware_mode") not in hardware_info["valid_modes"]:
@@ -217,8 +279,29 @@ def load_config():
 
 
 def save_config(cfg):
-    with open(CONFIG_FILE, "w", encoding="utf-8") as f:
+    if config_error:
+        print(f"[Config] Not saving: {config_error}")
+        ui.notify(config_error, type="negative")
+        return
+
+    if SECRETS_FROM_LAUNCHER:
+        changed = {
+            key: cfg.get(key, "")
+            for key in SECRET_KEYS
+            if cfg.get(key, "") != launcher_secrets.get(key, "")
+        }
+        if changed:
+            launcher_secrets.update(changed)
+            # Straight to the launcher's pipe, bypassing the on-screen log
+            sys.__stdout__.write(SECRETS_UPDATE_PREFIX + json.dumps(changed) + "\n")
+            sys.__stdout__.flush()
+        cfg = {key: value for key, value in cfg.items() if key not in SECRET_KEYS}
+
+    # Write then rename so the launcher never reads a half-written file
+    tmp = CONFIG_FILE + ".tmp"
+    with open(tmp, "w", encoding="utf-8") as f:
         json.dump(cfg, f, indent=2, ensure_ascii=False)
+    os.replace(tmp, CONFIG_FILE)
 
 
 class State:
@@ -230,6 +313,420 @@ class State:
 
 state = State()
 
+# Version of the desktop bundle, injected by the Tauri launcher
+APP_VERSION = os.environ.get("OAN_APP_VERSION", "dev")
+
+# Per-launch token shared with the Tauri launcher. Popped so that child
+# processes (ffmpeg, aria2c, ...) don't inherit it. Unset in dev mode.
+AUTH_TOKEN = os.environ.pop("OAN_AUTH_TOKEN", None)
+AUTH_COOKIE = "oan_token"
+
+
+class LauncherAuthMiddleware:
+    """
+    Rejects HTTP and websocket requests that don't carry the launch token.
+    Accepts a Bearer header (Rust-side calls), the session cookie (webview),
+    or a one-time ?oan_token= query that is swapped for the cookie.
+    """
+
+    def __init__(self, asgi_app):
+        self.app = asgi_app
+
+    async def __call__(self, scope, receive, send):
+        if not AUTH_TOKEN or scope["type"] not in ("http", "websocket"):
+            await self.app(scope, receive, send)
+            return
+
+        import hmac
+        from http.cookies import SimpleCookie
+        from urllib.parse import parse_qs
+
+        def matches(value):
+            return bool(value) and hmac.compare_digest(value, AUTH_TOKEN)
+
+        headers = {k.lower(): v.decode("latin-1") for k, v in scope.get("headers", [])}
+
+        auth = headers.get(b"authorization", "")
+        if auth.startswith("Bearer ") and matches(auth[len("Bearer "):]):
+            await self.app(scope, receive, send)
+            return
+
+        cookies = SimpleCookie()
+        try:
+            cookies.load(headers.get(b"cookie", ""))
+        except Exception:
+            pass
+        if AUTH_COOKIE in cookies and matches(cookies[AUTH_COOKIE].value):
+            await self.app(scope, receive, send)
+            return
+
+        query = parse_qs(scope.get("query_string", b"").decode("latin-1"))
+        if scope["type"] == "http" and matches(query.get(AUTH_COOKIE, [""])[0]):
+            # Swap the query token for a cookie and drop it from the address bar
+            cookie = f"{AUTH_COOKIE}={AUTH_TOKEN}; Path=/; HttpOnly; SameSite=Strict"
+            await send({
+                "type": "http.response.start",
+                "status": 303,
+                "headers": [
+                    (b"location", scope.get("path", "/").encode("utf-8")),
+                    (b"set-cookie", cookie.encode("latin-1")),
+                    (b"content-length", b"0"),
+                ],
+            })
+            await send({"type": "http.response.body", "body": b""})
+            return
+
+        if scope["type"] == "websocket":
+            await send({"type": "websocket.close", "code": 1008})
+            return
+        await send({
+            "type": "http.response.start",
+            "status": 403,
+            "headers": [(b"content-type", b"text/plain")],
+        })
+        await send({"type": "http.response.body", "body": b"Forbidden"})
+
+
+app.add_middleware(LauncherAuthMiddleware)
+
+
+# --- Launcher commands ---
+# Under the launcher the page may call the Tauri commands listed in
+# src-tauri/permissions/sidecar-ui.toml. In dev mode there is no IPC.
+HAS_LAUNCHER = AUTH_TOKEN is not None
+
+
+class LauncherError(Exception):
+    """A launcher command failed; the message is its error."""
+
+
+def _launcher_error_text(error):
+    # Typed errors arrive as {"kind", "detail"}; config errors list fields
+    if isinstance(error, dict):
+        detail = error.get("detail")
+        if isinstance(detail, list):
+            return "; ".join(f"{e.get('field')}: {e.get('message')}" for e in detail)
+        return str(detail or error.get("kind"))
+    return str(error)
+
+
+async def invoke(command, args=None, timeout=600.0):
+    """Call a launcher command from the current page and return its result.
+    The timeout is long because some commands wait on a native dialog."""
+    reply = await ui.run_javascript(
+        f"""(async () => {{
+            try {{
+                return {{ok: await window.__TAURI__.core.invoke({json.dumps(command)}, {json.dumps(args or {})})}};
+            }} catch (e) {{
+                return {{error: e}};
+            }}
+        }})()""",
+        timeout=timeout,
+    )
+    if "error" in reply:
+        raise LauncherError(_launcher_error_text(reply["error"]))
+    return reply.get("ok")
+
+
+async def save_settings(cfg):
+    """Save from the settings UI. Under the launcher this goes through
+    set_secret and set_config, which report invalid fields instead of
+    writing them. Returns whether it was saved."""
+    # With config_error set, cfg holds defaults that must not be saved
+    if not HAS_LAUNCHER or config_error:
+        save_config(cfg)
+        return config_error is None
+    try:
+        for key in SECRET_KEYS:
+            value = cfg.get(key, "")
+            if value != launcher_secrets.get(key, ""):
+                await invoke("set_secret", {"key": key, "value": value})
+                launcher_secrets[key] = value
+        patch = {
+            key: value
+            for key, value in cfg.items()
+            if key not in SECRET_KEYS and key != "schema_version"
+        }
+        await invoke("set_config", {"patch": patch})
+    except LauncherError as e:
+        print(f"[Config] Not saved: {e}")
+        ui.notify(
+            f"{get_text('settings_save_failed', cfg.get('ui_language', 'zh'))}: {e}",
+            type="negative",
+            multi_line=True,
+        )
+        return False
+    return True
+
+
+# --- Launcher API ---
+@app.get("/api/health")
+def api_health():
+    """Health report polled by the Tauri launcher."""
+    hardware_mode = state.config.get("hardware_mode", hardware_info["type"])
+    asr_model = state.config.get("asr_model", "small")
+    statuses = model_manager.get_model_statuses(hardware_mode)
+    # Downloaded, not loaded: the model is only loaded when a job needs it
+    model_installed = any(s["key"] == asr_model and s["installed"] for s in statuses)
+    return {
+        "status": "ok",
+        "version": APP_VERSION,
+        "asr_backend": hardware_mode,
+        "asr_model": asr_model,
+        "model_installed": model_installed,
+        "hardware": hardware_info,
+        # Under a one-file build the launcher only knows the bootloader's PID
+        "pid": os.getpid(),
+    }
+
+
+@app.post("/api/config/reload")
+def api_config_reload():
+    """Re-read user_config.json after the launcher changed it."""
+    state.config = load_config()
+    return {"status": "ok"}
+
+
+@app.post("/api/shutdown")
+async def api_shutdown():
+    """Graceful stop requested by the Tauri launcher on exit."""
+    print("[Launcher] Shutdown requested.")
+    # Let the response go out before the server stops
+    asyncio.get_running_loop().call_later(0.2, app.shutdown)
+    return {"status": "stopping"}
+
+
+# --- Headless jobs (driven by the launcher's job queue) ---
+# job_id -> {"state", "stage", "progress", "session_id", "error", "task"}
+headless_jobs = {}
+
+
+def _job_status(job_id):
+    job = headless_jobs[job_id]
+    return {key: value for key, value in job.items() if key != "task"}
+
+
+async def _embed_frames(text, video_path, assets_dir, task_id):
+    """Insert a frame after every [mm:ss] timestamp, like the live view does."""
+    for ts in dict.fromkeys(re.findall(r"\[(\d{1,2}:\d{2})\]", text)):
+        seconds = timestamp_str_to_seconds(ts)
+        img_filename = f"frame_{seconds}.jpg"
+        img_fs_path = os.path.join(assets_dir, img_filename)
+        if not os.path.exists(img_fs_path):
+            await run.io_bound(extract_frame, video_path, seconds, img_fs_path)
+        if os.path.exists(img_fs_path) and f"![{ts}]" not in text:
+            img_web_path = f"/generate/{task_id}/assets/{img_filename}"
+            text = text.replace(f"[{ts}]", f"[{ts}]\n\n![{ts}]({img_web_path})")
+    return text
+
+
+async def run_headless_job(job_id, url=None, file_path=None, custom_prompt="", complexity=3):
+    """Download -> transcribe -> summarize without a browser attached."""
+    import uuid
+    from datetime import datetime
+
+    job = headless_jobs[job_id]
+    config = state.config
+
+    def set_stage(stage, progress=0.0):
+        job["stage"] = stage
+        job["progress"] = progress
+
+    task_id = f"{datetime.now().strftime('%Y%m%d_%H%M%S')}_{str(uuid.uuid4())[:8]}"
+    task_dir = os.path.join(GENERATE_DIR, task_id)
+    raw_dir = os.path.join(task_dir, "raw")
+    assets_dir = os.path.join(task_dir, "assets")
+    os.makedirs(raw_dir, exist_ok=True)
+    os.makedirs(assets_dir, exist_ok=True)
+    add_session({
+        "id": task_id,
+        "title": "Processing...",
+        "video_url": url or file_path,
+        "timestamp": datetime.now().strftime("%Y%m%d_%H%M%S"),
+        "status": "processing",
+        "project_dir": task_dir,
+    })
+    job["session_id"] = task_id
+
+    try:
+        if not config["api_key"]:
+            raise RuntimeError("API key missing")
+
+        # 1. Download
+        set_stage("download")
+        if file_path:
+            dl_res = {"success": True, "title": os.path.basename(file_path), "video_path": file_path}
+        else:
+            def dl_hook(d):
+                if d["status"] == "downloading":
+                    total = d.get("total_bytes") or d.get("total_bytes_estimate") or 1
+                    job["progress"] = min(d.get("downloaded_bytes", 0) / total, 1.0)
+
+            dl_res = await run.io_bound(
+                download_video,
+                clean_bilibili_url(url),
+                raw_dir,
+                config.get("cookies_yt", ""),
+                config.get("cookies_bili", ""),
+                True,
+                dl_hook,
+            )
+            if not dl_res["success"]:
+                raise RuntimeError(f"Download failed: {dl_res.get('error')}")
+        # Lets the launcher's watch folders recognise this video later
+        dl_res["video_hash"] = await run.io_bound(generate_video_hash, dl_res["video_path"])
+        update_session(task_id, progress="Download: Completed")
+
+        # 2. Transcribe
+        set_stage("transcribe")
+        segments = await async_transcribe(dl_res["video_path"], config["hardware_mode"])
+        transcript_text = " ".join([s["text"] for s in segments])
+        update_session(task_id, progress="Transcription: Completed")
+
+        # 3. Vision
+        set_stage("summarize")
+        vision_frames = []
+        if config["enable_vision"]:
+            try:
+                vision_frames = await async_vision(
+                    dl_res["video_path"], config["vision_interval"], assets_dir
+                )
+            except Exception as e:
+                print(f"Vision extraction failed: {str(e)}")
+
+        # 4. Summarize
+        abstract_content = ""
+        contents_content = ""
+        if config.get("enable_chunk_summary", False):
+            chunks = split_transcript_into_chunks(segments, target_duration_minutes=15)
+            all_abstracts = []
+            report_parts = []
+            for i, chunk in enumerate(chunks, 1):
+                job["progress"] = (i - 1) / len(chunks)
+                chunk_vision_frames = [
+                    frame for frame in vision_frames
+                    if chunk["start_time"] <= frame["timestamp"] <= chunk["end_time"]
+                ]
+                prev_abstracts_str = "\n\n".join(all_abstracts)
+                chunk_content = await generate_segmented_content_async(
+                    i, len(chunks), chunk["text"], chunk_vision_frames, config, prev_abstracts_str
+                )
+                report_parts.append(f"\n\n{'=' * 60}\n第 {i} 部分\n{'=' * 60}\n\n{chunk_content}")
+                abstract = await generate_abstract_async(
+                    i, len(chunks), chunk_content, prev_abstracts_str, config
+                )
+                all_abstracts.append(abstract)
+                abstract_content += f"\n{'-' * 40}\n{abstract}\n"
+                update_session(task_id, progress=f"AI Analysis: Chunk {i}/{len(chunks)} complete")
+            contents_content = await generate_final_contents_async("\n\n".join(all_abstracts), config)
+            report = f"{contents_content}\n\n---\n\n{''.join(report_parts)}"
+            abstract_content = abstract_content.strip()
+        else:
+            report = ""
+            errors = []
+            async for chunk_type, chunk_text in generate_summary_stream_async(
+                dl_res["title"], transcript_text, segments, vision_frames, config,
+                custom_prompt, complexity,
+            ):
+                if chunk_type == "content":
+                    report += chunk_text
+                elif chunk_type == "error":
+                    errors.append(chunk_text)
+            if not report:
+                raise RuntimeError(errors[0] if errors else "The model returned no summary")
+            report = await _embed_frames(report, dl_res["video_path"], assets_dir, task_id)
+        job["progress"] = 1.0
+
+        # 5. Finalize, then swap the temporary record for the real one
+        final_task_dir, final_report = finalize_task(
+            task_id, dl_res["title"], report, abstract_content, contents_content, segments
+        )
+        session = create_session(
+            dl_res["title"], url or file_path, final_report, transcript_text, final_task_dir, config,
+            video_hash=dl_res["video_hash"],
+        )
+        replace_session(task_id, session)
+        job["session_id"] = session["id"]
+        job["state"] = "done"
+    except asyncio.CancelledError:
+        print(f"[Jobs] {job_id} cancelled")
+        delete_session(task_id, delete_files=True)
+        job["session_id"] = None
+        job["state"] = "cancelled"
+    except Exception as e:
+        import traceback
+        print(traceback.format_exc())
+        update_session(task_id, status="error", progress=f"Error: {str(e)}")
+        job["state"] = "error"
+        job["error"] = str(e)
+
+
+@app.post("/api/jobs")
+async def api_jobs_start(payload: dict):
+    """Start a job from the launcher's queue; poll it with GET /api/jobs/{id}."""
+    job_id = str(payload.get("job_id") or "")
+    url = payload.get("url")
+    file_path = payload.get("file")
+    if not job_id or not (url or file_path):
+        return JSONResponse({"error": "job_id and url or file are required"}, status_code=400)
+    if job_id in headless_jobs and headless_jobs[job_id]["state"] == "running":
+        return _job_status(job_id)
+    headless_jobs[job_id] = {
+        "state": "running",
+        "stage": "starting",
+        "progress": 0.0,
+        "session_id": None,
+        "error": None,
+    }
+    headless_jobs[job_id]["task"] = asyncio.create_task(
+        run_headless_job(
+            job_id,
+            url=url,
+            file_path=file_path,
+            custom_prompt=payload.get("custom_prompt", ""),
+            complexity=payload.get("complexity", 3),
+        )
+    )
+    return _job_status(job_id)
+
+
+@app.get("/api/jobs/{job_id}")
+def api_jobs_status(job_id: str):
+    if job_id not in headless_jobs:
+        return JSONResponse({"error": "unknown job"}, status_code=404)
+    return _job_status(job_id)
+
+
+@app.post("/api/jobs/{job_id}/cancel")
+def api_jobs_cancel(job_id: str):
+    job = headless_jobs.get(job_id)
+    if job is None:
+        return JSONResponse({"error": "unknown job"}, status_code=404)
+    if job["state"] == "running":
+        job["task"].cancel()
+    return _job_status(job_id)
+
+
+# --- Flashcards (for the launcher's Anki export) ---
+@app.post("/api/flashcards")
+async def api_flashcards(payload: dict):
+    """Question/answer pairs for studying a report, each tied to one of its [mm:ss] markers."""
+    report = str(payload.get("report") or "")
+    if not report.strip():
+        return JSONResponse({"error": "report is required"}, status_code=400)
+    try:
+        count = max(1, min(int(payload.get("count") or 20), 100))
+    except (TypeError, ValueError):
+        return JSONResponse({"error": "count must be a number"}, status_code=400)
+    if not state.config["api_key"]:
+        return JSONResponse({"error": "API Key missing."}, status_code=400)
+    try:
+        cards = await generate_flashcards_async(report, count, state.config)
+    except Exception as e:
+        print(f"[Flashcards] Error generating flashcards: {e}")
+        return JSONResponse({"error": str(e)}, status_code=502)
+    return {"cards": cards}
+
 
 class WebLogger:
     def __init__(self, original_stream, ui_log_element):
@@ -326,9 +823,9 @@ class WebLogger:
 
 
 # --- Atomic Finalization Helper ---
-def finalize_task(task_id: str, raw_title: str, report_content: str, abstract_content: str = "", contents_content: str = "") -> tuple:
+def finalize_task(task_id: str, raw_title: str, report_content: str, abstract_content: str = "", contents_content: str = "", segments: list = None) -> tuple:
     """
-    Atomic finalization: Save report.md, abstract.md, contents.md, update paths, then rename folder.
+    Atomic finalization: Save report.md, abstract.md, contents.md, transcript.json, update paths, then rename folder.
     Returns (final_folder_path, updated_report_content).
     """
     from core.utils import sanitize_filename
@@ -351,6 +848,7 @@ def finalize_task(task_id: str, raw_title: str, report_content: str, abstract_co
     report_path = os.path.join(old_folder, "report.md")
     abstract_path = os.path.join(old_folder, "abstract.md")
     contents_path = os.path.join(old_folder, "contents.md")
+    transcript_path = os.path.join(old_folder, "transcript.json")
 
     if not os.path.exists(old_folder):
         print(f"[Finalize] Error: Folder {old_folder} not found.")
@@ -389,7 +887,20 @@ def finalize_task(task_id: str, raw_title: str, report_content: str, abstract_co
         except Exception as e:
             print(f"[Finalize] Could not save contents: {e}")
 
-    # 5. Rename folder (the atomic move)
+    # 5. Save timestamped transcript segments (used by search and exports)
+    if segments:
+        try:
+            with open(transcript_path, "w", encoding="utf-8") as f:
+                json.dump(
+                    [{"start": s["start"], "end": s["end"], "text": s["text"]} for s in segments],
+                    f,
+                    ensure_ascii=False,
+                )
+            print(f"[Finalize] Saved transcript.json to {transcript_path}")
+        except Exception as e:
+            print(f"[Finalize] Could not save transcript: {e}")
+
+    # 6. Rename folder (the atomic move)
     try:
         os.rename(old_folder, new_folder)
         print(f"[Finalize] Renamed folder: {old_folder} -> {new_folder}")
@@ -1295,6 +1806,73 @@ Output only the contents.md content, nothing else.
         return f"Error generating contents: {str(e)}"
 
 
+def _parse_flashcards(text):
+    """Cards from the model's reply, which may wrap the JSON in a code fence."""
+    start = min((i for i in (text.find("["), text.find("{")) if i >= 0), default=-1)
+    if start < 0:
+        raise ValueError("The model did not return any flashcards")
+    data, _ = json.JSONDecoder().raw_decode(text[start:])
+    if isinstance(data, dict):
+        data = data.get("cards", [])
+    cards = []
+    for item in data if isinstance(data, list) else []:
+        if not isinstance(item, dict):
+            continue
+        question = str(item.get("question") or "").strip()
+        answer = str(item.get("answer") or "").strip()
+        if not question or not answer:
+            continue
+        match = re.search(r"(?:\d+:)?\d{1,2}:\d{2}", str(item.get("timestamp") or ""))
+        cards.append({
+            "question": question,
+            "answer": answer,
+            "timestamp": match.group(0) if match else None,
+        })
+    if not cards:
+        raise ValueError("The model did not return any flashcards")
+    return cards
+
+
+async def generate_flashcards_async(report, count, config):
+    """
+    Generate question/answer flashcards from a finished report.
+    """
+    client = AsyncOpenAI(api_key=config["api_key"], base_url=config["base_url"])
+
+    # Frames only cost tokens; the [mm:ss] markers stay
+    report = re.sub(r"!\[[^\]]*\]\([^)]*\)", "", report)
+
+    system_prompt = f"""You write flashcards that help a student review a lecture.
+
+Write up to {count} cards from the notes the user sends. Each card asks about one fact, definition, cause or step that matters, and its answer is short enough to recall from memory (one to three sentences). Don't ask about the notes themselves or about the speaker.
+
+Write the cards in the same language as the notes.
+
+Every card comes from a section of the notes that has a [mm:ss] timestamp. Set "timestamp" to the nearest timestamp before the material the card is about, as "mm:ss", or null if there is none.
+
+Output only JSON, nothing else:
+{{"cards": [{{"question": "...", "answer": "...", "timestamp": "mm:ss"}}]}}
+"""
+
+    print(f"[Flashcards] Generating up to {count} flashcards...")
+
+    async def api_call():
+        return await client.chat.completions.create(
+            model=config["model_name"],
+            messages=[
+                {"role": "system", "content": system_prompt},
+                {"role": "user", "content": report},
+            ],
+            stream=False,
+        )
+
+    response = await retry_async(api_call, max_retries=3, initial_delay=1, backoff_factor=2)
+
+    cards = _parse_flashcards(response.choices[0].message.content or "")[:count]
+    print(f"[Flashcards] Generated {len(cards)} flashcards")
+    return cards
+
+
 # --- UI Construction ---
 
 
@@ -1306,6 +1884,14 @@ def index():
         'background-color: #FFFBFE; font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", "Noto Sans", Helvetica, Arial, sans-serif, "Apple Color Emoji", "Segoe UI Emoji";'
     )
 
+    if config_error:
+        ui.timer(1.0, lambda: ui.notify(
+            f"{config_error}. Settings will not be saved until the file is fixed.",
+            type="negative",
+            timeout=0,
+            close_button=True,
+        ), once=True)
+
     # Check if CUDA mode is selected but PyTorch CUDA is not installed
     if state.config.get("hardware_mode") == "cuda":
         torch_installed, cuda_version = check_torch_cuda_installed()
@@ -1497,6 +2083,26 @@ def index():
                     ).bind_value(state.config, "api_key").classes("w-full").props(
                         'outlined dense item-aligned input-class="text-sm"'
                     )
+                    if HAS_LAUNCHER:
+                        secrets_label = ui.label().classes("text-xs text-gray-500 px-1")
+
+                        async def show_secret_backend():
+                            try:
+                                status = await invoke("get_secret_status")
+                            except LauncherError as e:
+                                print(f"[Secrets] Status unavailable: {e}")
+                                return
+                            lang = state.config["ui_language"]
+                            backend = (
+                                "secrets_backend_keychain"
+                                if status.get("backend") == "keychain"
+                                else "secrets_backend_file"
+                            )
+                            secrets_label.text = (
+                                f"{get_text('secrets_stored_in', lang)} {get_text(backend, lang)}"
+                            )
+
+                        ui.timer(0.1, show_secret_backend, once=True)
                     ui.input(
                         get_text("lbl_base_url", state.config["ui_language"])
                     ).bind_value(state.config, "base_url").classes("w-full").props(
@@ -2013,30 +2619,297 @@ def index():
                     ui.timer(0.5, check_and_update_torch_status, once=True)
 
                 # 系统设置
-                with ui.tab_panel(tab_sys).classes("p-1"):
+                with ui.tab_panel(tab_sys).classes("p-1 flex flex-col gap-3"):
+
+                    async def change_language():
+                        if await save_settings(state.config):
+                            ui.run_javascript("location.reload()")
+
                     ui.select(
                         {"zh": "中文", "en": "English"},
                         label=get_text("lbl_ui_lang", state.config["ui_language"]),
                     ).bind_value(state.config, "ui_language").on_value_change(
-                        lambda: (
-                            save_config(state.config),
-                            ui.run_javascript("location.reload()"),
-                        )
+                        change_language
                     ).classes("w-full").props("outlined dense")
 
+                    if HAS_LAUNCHER:
+                        lang = state.config["ui_language"]
+
+                        # --- Library location ---
+                        ui.separator().classes("opacity-30 my-1")
+                        ui.label(get_text("lbl_library", lang)).classes(
+                            "text-sm font-bold text-gray-700"
+                        )
+                        ui.label(get_text("library_desc", lang)).classes(
+                            "text-xs text-gray-500"
+                        )
+                        library_label = ui.label().classes(
+                            "text-xs font-mono w-full truncate bg-white rounded-lg px-2 py-1 shadow-sm"
+                        )
+
+                        async def refresh_library_dir():
+                            try:
+                                library_label.text = await invoke("get_library_dir")
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+                            library_label.tooltip(library_label.text)
+
+                        async def move_library():
+                            # On success the engine restarts and this page
+                            # reloads in the new library; failures after the
+                            # restart began are shown by the launcher
+                            try:
+                                await invoke("move_library", timeout=3600.0)
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+
+                        ui.button(
+                            get_text("btn_move_library", lang),
+                            icon="drive_file_move",
+                            on_click=move_library,
+                        ).props("flat dense color=primary size=sm")
+                        ui.timer(0.1, refresh_library_dir, once=True)
+
+                        # --- Watch folders ---
+                        ui.separator().classes("opacity-30 my-1")
+                        ui.label(get_text("lbl_watch_folders", lang)).classes(
+                            "text-sm font-bold text-gray-700"
+                        )
+                        ui.label(get_text("watch_folders_desc", lang)).classes(
+                            "text-xs text-gray-500"
+                        )
+                        watch_list = ui.column().classes("w-full gap-1")
+
+                        async def refresh_watch_folders():
+                            try:
+                                folders = await invoke("list_watch_folders")
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+                                return
+                            watch_list.clear()
+                            with watch_list:
+                                if not folders:
+                                    ui.label(get_text("no_watch_folders", lang)).classes(
+                                        "text-xs text-grey-5 italic"
+                                    )
+                                for folder in folders:
+                                    with ui.row().classes(
+                                        "w-full items-center no-wrap bg-white rounded-lg px-2 py-1 shadow-sm"
+                                    ):
+                                        ui.label(folder).classes(
+                                            "text-xs font-mono flex-grow truncate"
+                                        ).tooltip(folder)
+                                        ui.button(
+                                            icon="close",
+                                            on_click=lambda f=folder: remove_watch_folder(f),
+                                        ).props("flat round dense size=xs color=grey-6")
+
+                        async def add_watch_folder():
+                            try:
+                                await invoke("add_watch_folder")
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+                            await refresh_watch_folders()
+
+                        async def remove_watch_folder(folder):
+                            try:
+                                await invoke("remove_watch_folder", {"path": folder})
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+                            await refresh_watch_folders()
+
+                        ui.button(
+                            get_text("btn_add_watch_folder", lang),
+                            icon="create_new_folder",
+                            on_click=add_watch_folder,
+                        ).props("flat dense color=primary size=sm")
+                        ui.timer(0.1, refresh_watch_folders, once=True)
+
+                        # --- Logs ---
+                        ui.separator().classes("opacity-30 my-1")
+                        ui.label(get_text("lbl_logs", lang)).classes(
+                            "text-sm font-bold text-gray-700"
+                        )
+
+                        async def open_log():
+                            try:
+                                await invoke("open_log")
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+
+                        async def export_diagnostics():
+                            try:
+                                path = await invoke("export_diagnostics")
+                            except LauncherError as e:
+                                ui.notify(str(e), type="negative")
+                                return
+                            ui.notify(f"{get_text('saved_to', lang)} {path}", type="positive")
+
+                        with ui.row().classes("gap-2"):
+                            ui.button(
+                                get_text("btn_open_log", lang),
+                                icon="article",
+                                on_click=open_log,
+                            ).props("flat dense color=primary size=sm")
+                            ui.button(
+                                get_text("btn_export_diagnostics", lang),
+                                icon="bug_report",
+                                on_click=export_diagnostics,
+                            ).props("flat dense color=primary size=sm")
+
             # 底部按钮
             with ui.row().classes("w-full justify-end mt-4 gap-2"):
                 ui.button(
                     get_text("close", state.config["ui_language"]),
                     on_click=dialog.close,
                 ).props("flat dense color=primary")
+
+                async def save_and_close():
+                    if await save_settings(state.config):
+                        dialog.close()
+
                 ui.button(
                     get_text("save", state.config["ui_language"]),
-                    on_click=lambda: (save_config(state.config), dialog.close()),
+                    on_click=save_and_close,
                 ).props("unelevated dense color=primary")
 
         dialog.open()
 
+    # --- Job Queue Dialog (launcher only) ---
+    async def open_queue():
+        lang = state.config["ui_language"]
+        with (
+            ui.dialog() as dialog,
+            ui.card().classes("w-full max-w-[640px] p-5 rounded-xl bg-white shadow-lg"),
+        ):
+            with ui.row().classes("w-full items-center justify-between mb-2"):
+                ui.label(get_text("nav_queue", lang)).classes(
+                    "text-lg font-bold text-gray-800"
+                )
+                ui.button(icon="close", on_click=dialog.close).props(
+                    "flat round dense color=grey size=sm"
+                )
+            inputs = (
+                ui.textarea(placeholder=get_text("queue_inputs_hint", lang))
+                .classes("w-full")
+                .props("outlined dense rows=3")
+            )
+            with ui.row().classes("w-full items-center gap-2"):
+                add_btn = ui.button(get_text("btn_queue_add", lang), icon="add").props(
+                    "unelevated dense color=primary"
+                )
+                pause_btn = ui.button().props("flat dense color=primary")
+                retry_btn = ui.button(
+                    get_text("btn_queue_retry_failed", lang), icon="replay"
+                ).props("flat dense color=primary")
+                clear_btn = ui.button(
+                    get_text("btn_queue_clear_finished", lang), icon="clear_all"
+                ).props("flat dense color=primary")
+                ui.space()
+                concurrency = ui.select(
+                    {n: str(n) for n in range(1, 5)},
+                    label=get_text("lbl_queue_concurrency", lang),
+                ).props("dense outlined options-dense").classes("w-24")
+            job_list = ui.column().classes(
+                "w-full gap-1 mt-2 max-h-[50vh] overflow-y-auto"
+            )
+
+        queue = {"paused": False}
+
+        async def call(command, args=None):
+            try:
+                return await invoke(command, args)
+            except LauncherError as e:
+                ui.notify(str(e), type="negative")
+                return None
+
+        def render(snapshot):
+            queue.update(snapshot)
+            pause_btn.set_text(
+                get_text("btn_queue_resume" if queue["paused"] else "btn_queue_pause", lang)
+            )
+            pause_btn.props(f"icon={'play_arrow' if queue['paused'] else 'pause'}")
+            concurrency.set_value(queue.get("concurrency", 1))
+            job_list.clear()
+            with job_list:
+                jobs = queue.get("jobs", [])
+                if not jobs:
+                    ui.label(get_text("queue_empty", lang)).classes(
+                        "text-grey-5 text-sm italic px-1"
+                    )
+                for index, job in enumerate(jobs):
+                    job_state = job["state"]
+                    with ui.row().classes(
+                        "w-full items-center no-wrap bg-[#F7F2FA] rounded-lg px-2 py-1 gap-2"
+                    ):
+                        with ui.column().classes("flex-grow gap-0 min-w-0"):
+                            ui.label(str(job["source"]["value"])).classes(
+                                "text-sm truncate w-full"
+                            ).tooltip(str(job["source"]["value"]))
+                            detail = get_text(f"job_{job_state}", lang)
+                            if job_state == "running" and job.get("stage"):
+                                detail += f" · {job['stage']} {int(job.get('progress', 0) * 100)}%"
+                            if job.get("error"):
+                                detail += f" · {job['error']}"
+                            ui.label(detail).classes("text-xs text-grey-7 truncate w-full")
+                        if job_state == "queued":
+                            ui.button(
+                                icon="arrow_upward",
+                                on_click=lambda j=job, i=index: call(
+                                    "move_job", {"id": j["id"], "index": max(i - 1, 0)}
+                                ),
+                            ).props("flat round dense size=sm")
+                            ui.button(
+                                icon="arrow_downward",
+                                on_click=lambda j=job, i=index: call(
+                                    "move_job", {"id": j["id"], "index": i + 1}
+                                ),
+                            ).props("flat round dense size=sm")
+                        if job_state in ("failed", "cancelled"):
+                            ui.button(
+                                icon="replay",
+                                on_click=lambda j=job: call("retry_jobs", {"ids": [j["id"]]}),
+                            ).props("flat round dense size=sm")
+                        if job_state in ("queued", "running"):
+                            ui.button(
+                                icon="close",
+                                on_click=lambda j=job: call("cancel_job", {"id": j["id"]}),
+                            ).props("flat round dense size=sm color=negative")
+                        elif job.get("session_id"):
+                            ui.button(
+                                icon="open_in_new",
+                                on_click=lambda j=job: (
+                                    dialog.close(),
+                                    load_session(j["session_id"]),
+                                ),
+                            ).props("flat round dense size=sm")
+
+        async def add_inputs():
+            lines = [l.strip() for l in (inputs.value or "").splitlines() if l.strip()]
+            if lines and await call("enqueue_jobs", {"inputs": lines}) is not None:
+                inputs.set_value("")
+
+        async def toggle_pause():
+            await call("resume_jobs" if queue["paused"] else "pause_jobs")
+
+        async def change_concurrency(e):
+            if e.value and e.value != queue.get("concurrency"):
+                await call("set_job_concurrency", {"concurrency": e.value})
+
+        add_btn.on_click(add_inputs)
+        pause_btn.on_click(toggle_pause)
+        retry_btn.on_click(lambda: call("retry_jobs"))
+        clear_btn.on_click(lambda: call("clear_finished_jobs"))
+        concurrency.on_value_change(change_concurrency)
+
+        snapshot = await call("list_jobs")
+        if snapshot is None:
+            return
+        render(snapshot)
+        queue_view["update"] = render
+        dialog.on_value_change(lambda e: e.value or queue_view.update(update=None))
+        dialog.open()
+
     # --- Left Sidebar (Floating/Rounded) ---
     with (
         ui.left_drawer(value=True)
@@ -2096,8 +2969,62 @@ def index():
                     "flat round dense size=xs color=grey-5"
                 ).tooltip(get_text("btn_clear_history", state.config["ui_language"]))
 
-            # Sync history on load (remove orphan entries)
-            sync_history()
+            # Full-text search through the launcher's index
+            if HAS_LAUNCHER:
+                search_input = (
+                    ui.input(
+                        placeholder=get_text(
+                            "search_placeholder", state.config["ui_language"]
+                        )
+                    )
+                    .props("dense outlined rounded clearable")
+                    .classes("w-full px-2 mb-1")
+                )
+                search_results = ui.column().classes("w-full gap-0 mb-2")
+
+                def format_seconds(seconds):
+                    seconds = int(seconds)
+                    hours, rest = divmod(seconds, 3600)
+                    if hours:
+                        return f"{hours}:{rest // 60:02d}:{rest % 60:02d}"
+                    return f"{rest // 60:02d}:{rest % 60:02d}"
+
+                async def search_notes():
+                    query = (search_input.value or "").strip()
+                    search_results.clear()
+                    if not query:
+                        return
+                    try:
+                        hits = await invoke(
+                            "search_notes", {"query": query, "limit": 20}
+                        )
+                    except LauncherError as e:
+                        ui.notify(str(e), type="negative")
+                        return
+                    with search_results:
+                        if not hits:
+                            ui.label(
+                                get_text(
+                                    "search_no_results", state.config["ui_language"]
+                                )
+                            ).classes("text-grey-5 text-xs italic px-3")
+                        for hit in hits:
+                            start = hit.get("start")
+                            stamp = f"[{format_seconds(start)}] " if start is not None else ""
+                            ui.button(
+                                f"{stamp}{hit['snippet']}",
+                                on_click=lambda e, sid=hit["session_id"]: load_session(sid),
+                            ).props("flat dense align=left no-caps").classes(
+                                "w-full px-2 text-left text-xs text-grey-8 rounded-lg hover:bg-[#E8DEF8]"
+                            )
+
+                search_input.on("keydown.enter", search_notes)
+                search_input.on("clear", lambda: search_results.clear())
+
+            # Sync history on load (remove orphan entries). The launcher's
+            # watcher does this instead and only flags them as missing.
+            if not HAS_LAUNCHER:
+                sync_history()
             
             # Validate and cleanup sessions (remove invalid/stale sessions)
             validate_and_cleanup_sessions()
@@ -2177,6 +3104,9 @@ def index():
                                 status_icon = "✅ "
                             elif status == "error":
                                 status_icon = "❌ "
+                            elif status == "missing":
+                                # The project folder is gone; deleting is up to the user
+                                status_icon = "⚠️ "
                             
                             # Display progress if available
                             progress_text = s.get("progress", "")
@@ -2231,8 +3161,40 @@ def index():
 
             history_list()
 
+            # The Tauri launcher watches generate/ and announces folder changes
+            ui.on("history_changed", lambda: history_list.refresh())
+            # Files dropped on the window that aren't audio or video
+            ui.on(
+                "media_rejected",
+                lambda e: [
+                    ui.notify(f.get("reason", ""), type="warning")
+                    for f in (e.args or [])
+                ],
+            )
+            # The queue dialog, while open, follows the launcher's job list
+            queue_view = {"update": None}
+            ui.on(
+                "jobs_changed",
+                lambda e: queue_view["update"] and queue_view["update"](e.args),
+            )
+            ui.add_body_html("""
+            <script>
+                window.__TAURI__?.event?.listen("history-changed", () => emitEvent("history_changed"));
+                window.__TAURI__?.event?.listen("media-rejected", (event) => emitEvent("media_rejected", event.payload));
+                window.__TAURI__?.event?.listen("jobs-changed", (event) => emitEvent("jobs_changed", event.payload));
+            </script>
+            """)
+
         # Bottom Actions
         with ui.column().classes("col-auto q-pa-sm w-full border-t border-gray-100"):
+            if HAS_LAUNCHER:
+                ui.button(
+                    get_text("nav_queue", state.config["ui_language"]),
+                    icon="playlist_play",
+                    on_click=lambda: open_queue(),
+                ).props(
+                    "flat rounded block align=left text-color-[#1C1B1F] w-full"
+                ).classes("rounded-full hover:bg-[#E8DEF8]")
             ui.button(
                 get_text("nav_settings_title", state.config["ui_language"]),
                 icon="settings",
@@ -2301,12 +3263,7 @@ def index():
                 state.current_session = None
                 main_content.refresh()
             
-            # Optimistic UI update: Remove from UI first
-            sessions = load_history()
-            filtered_sessions = [s for s in sessions if s["id"] != sess_id]
-            save_history(filtered_sessions)
-            
-            # Then delete files in background (this can be slower)
+            # Removes the entry and its files; the files can take a while
             await run.io_bound(delete_session, sess_id)
             
             # Reload the page to avoid DOM conflicts
@@ -2375,6 +3332,44 @@ def index():
         
         return modified_content
 
+    def render_export_menu(session_id):
+        """Export buttons backed by the launcher's exporters. Each one asks
+        where to save with the native dialog."""
+        lang = state.config["ui_language"]
+
+        async def export(command, args):
+            try:
+                path = await invoke(command, args)
+            except LauncherError as e:
+                ui.notify(f"{get_text('export_failed', lang)}: {e}", type="negative")
+                return
+            if path:
+                ui.notify(f"{get_text('saved_to', lang)} {path}", type="positive")
+
+        single = {"sessionId": session_id}
+        exports = [
+            ("export_html", "html", "export_report", {**single, "format": "html"}),
+            ("export_pdf", "picture_as_pdf", "export_report", {**single, "format": "pdf"}),
+            ("export_epub", "menu_book", "export_epub", {"sessionIds": [session_id]}),
+            ("export_srt", "subtitles", "export_subtitles", {**single, "format": "srt"}),
+            ("export_vtt", "subtitles", "export_subtitles", {**single, "format": "vtt"}),
+            ("export_ass", "subtitles", "export_subtitles", {**single, "format": "ass"}),
+            ("export_vault", "hub", "export_to_vault", single),
+            ("export_anki", "style", "export_anki", single),
+        ]
+
+        with ui.button(get_text("btn_export", lang), icon="ios_share").props(
+            "flat dense no-caps color=primary"
+        ):
+            with ui.menu():
+                for key, icon, command, args in exports:
+                    with ui.menu_item(
+                        on_click=lambda c=command, a=args: export(c, a)
+                    ):
+                        with ui.row().classes("items-center gap-2 no-wrap"):
+                            ui.icon(icon, size="xs").classes("text-grey-7")
+                            ui.label(get_text(key, lang))
+
     def render_history_view(session):
         # Layout classes are now managed by main_content() to prevent state corruption
 
@@ -2425,6 +3420,9 @@ def index():
                         )
                         ui.label("•")
                         ui.label(session.get("timestamp", "")[:10])
+                        if HAS_LAUNCHER:
+                            ui.space()
+                            render_export_menu(session["id"])
 
                     # Transcript Expansion
                     with ui.expansion(
@@ -3131,16 +4129,11 @@ def index():
         
         # Progress display for history records
         def update_history_progress(session_id, progress_text):
-            sessions = load_history()
-            for s in sessions:
-                if s["id"] == session_id:
-                    s["progress"] = progress_text
-                    save_history(sessions)
-                    if 'history_list' in globals():
-                        history_list.refresh()
-                    elif 'history_list' in locals():
-                        locals()['history_list'].refresh()
-                    break
+            if update_session(session_id, progress=progress_text):
+                if 'history_list' in globals():
+                    history_list.refresh()
+                elif 'history_list' in locals():
+                    locals()['history_list'].refresh()
 
         # Result Card (Hidden initially)
         result_card = ui.card().classes(
@@ -3182,16 +4175,11 @@ def index():
                 
             last_progress_refresh = current_time
             
-            sessions = load_history()
-            for s in sessions:
-                if s["id"] == session_id:
-                    s["progress"] = f"{step}: {progress}"
-                    save_history(sessions)
-                    if 'history_list' in globals():
-                        history_list.refresh()
-                    elif 'history_list' in locals():
-                        locals()['history_list'].refresh()
-                    break
+            if update_session(session_id, progress=f"{step}: {progress}"):
+                if 'history_list' in globals():
+                    history_list.refresh()
+                elif 'history_list' in locals():
+                    locals()['history_list'].refresh()
 
         # Thread-safe UI updater helper
         # Capture the main loop in the closure
@@ -3666,6 +4654,11 @@ def index():
                         print(f"Client already disconnected during download: {e}")
                         return
 
+                # Lets the launcher's watch folders recognise this video later
+                dl_res["video_hash"] = await run.io_bound(
+                    generate_video_hash, dl_res["video_path"]
+                )
+
                 # 2. Transcribe
                 with step_ts:
                     ui.spinner().classes("q-ma-md")
@@ -3980,7 +4973,7 @@ def index():
                 print(f"[Finalize] Debug: final_content_for_save content preview: {final_content_for_save[:200]}")
                 
                 final_task_dir, final_report = finalize_task(
-                    task_id, dl_res["title"], final_content_for_save, abstract_content, contents_content
+                    task_id, dl_res["title"], final_content_for_save, abstract_content, contents_content, segments
                 )
 
                 # Update displayed content with corrected paths
@@ -4101,52 +5094,19 @@ def index():
                 })();
                 """)
                 # Update existing temporary session instead of creating new one
-                try:
-                    sessions = load_history()
-                    session_updated = False
-                    for i, s in enumerate(sessions):
-                        if s["id"] == task_id:
-                            # 确保我们保存的是包含最终总结的正确内容
-                            sessions[i] = create_session(
-                                dl_res["title"],
-                                url,
-                                final_report,  # 保存最终报告内容
-                                transcript_text,
-                                final_task_dir,
-                                state.config,
-                            )
-                            save_history(sessions)
-                            session_updated = True
-                            break
-                    
-                    # If we couldn't find and update the session, log this issue
-                    if not session_updated:
-                        print(f"[Warning] Could not find temporary session {task_id} to update")
-                        # Try to add as a new session as fallback
-                        new_session = create_session(
-                            dl_res["title"],
-                            url,
-                            final_report,  # 保存最终报告内容
-                            transcript_text,
-                            final_task_dir,
-                            state.config,
-                        )
-                        add_session(new_session)
-                except Exception as update_error:
-                    print(f"[Error] Failed to update session {task_id}: {update_error}")
-                    # Try to add as a new session as fallback
-                    try:
-                        new_session = create_session(
-                            dl_res["title"],
-                            url,
-                            final_report,  # 保存最终报告内容
-                            transcript_text,
-                            final_task_dir,
-                            state.config,
-                        )
-                        add_session(new_session)
-                    except Exception as fallback_error:
-                        print(f"[Error] Failed to create fallback session: {fallback_error}")
+                # 确保我们保存的是包含最终总结的正确内容
+                final_session = create_session(
+                    dl_res["title"],
+                    url,
+                    final_report,  # 保存最终报告内容
+                    transcript_text,
+                    final_task_dir,
+                    state.config,
+                    video_hash=dl_res.get("video_hash"),
+                )
+                if not replace_session(task_id, final_session):
+                    # replace_session added it as a new session instead
+                    print(f"[Warning] Could not find temporary session {task_id} to update")
                 history_list.refresh()
 
             except Exception as e:
@@ -4158,17 +5118,11 @@ def index():
                     print("Client already disconnected, cannot show notification.")
                 # Update history record with error status
                 try:
-                    sessions = load_history()
-                    for s in sessions:
-                        if s["id"] == task_id:
-                            s["status"] = "error"
-                            s["progress"] = f"Error: {str(e)}"
-                            save_history(sessions)
-                            if 'history_list' in globals():
-                                history_list.refresh()
-                            elif 'history_list' in locals():
-                                locals()['history_list'].refresh()
-                            break
+                    if update_session(task_id, status="error", progress=f"Error: {str(e)}"):
+                        if 'history_list' in globals():
+                            history_list.refresh()
+                        elif 'history_list' in locals():
+                            locals()['history_list'].refresh()
                 except Exception as update_e:
                     print(f"Failed to update history with error: {update_e}")
             finally:
@@ -4197,10 +5151,10 @@ def index():
 def start_parent_watchdog():
     """
     Monitors the parent process (Tauri). If it dies, we die.
+    sys.exit() would only end the watchdog thread, so it uses os._exit().
     """
     import threading
     import time
-    import sys
     import os
     import psutil
 
@@ -4212,17 +5166,17 @@ def start_parent_watchdog():
             parent = psutil.Process(ppid)
         except psutil.NoSuchProcess:
             print("[Watchdog] Parent not found at startup. Exiting.")
-            sys.exit(0)
+            os._exit(0)
 
         while True:
             try:
                 # Check if parent is running and valid
                 if not parent.is_running() or parent.status() == psutil.STATUS_ZOMBIE:
                     print(f"[Watchdog] Parent {ppid} died. Exiting.")
-                    sys.exit(0)
+                    os._exit(0)
             except (psutil.NoSuchProcess, psutil.AccessDenied):
                 print(f"[Watchdog] Lost access to Parent {ppid}. Exiting.")
-                sys.exit(0)
+                os._exit(0)
 
             time.sleep(1.0)
 
@@ -4240,10 +5194,25 @@ if __name__ in {"__main__", "__mp_main__"}:
     parser = argparse.ArgumentParser()
     parser.add_argument("--port", type=int, default=8964, help="Port to bind to")
     parser.add_argument(
-        "--secret", type=str, default="gemini_secret", help="Storage secret"
+        "--secret",
+        type=str,
+        default=os.environ.pop("OAN_STORAGE_SECRET", "gemini_secret"),
+        help="Storage secret (the launcher passes it via OAN_STORAGE_SECRET)",
     )
     args = parser.parse_args()
 
+    # Lead a process group of our own when the launcher asks, so it can kill
+    # this server and the tools it runs (ffmpeg, yt-dlp) together
+    if (
+        os.environ.get("OAN_PROCESS_GROUP") == "1"
+        and __name__ == "__main__"
+        and hasattr(os, "setpgid")
+    ):
+        try:
+            os.setpgid(0, 0)
+        except OSError as e:
+            print(f"[Launcher] Could not start a process group: {e}")
+
     # Start Watchdog
     start_parent_watchdog()
 
@@ -1,3 +1,48 @@
 fn main() {
-  tauri_build::build()
+  tauri_build::try_build(
+    tauri_build::Attributes::new().app_manifest(
+      // Listing the commands puts them under the ACL, so a window can only
+      // call the ones its capabilities allow
+      tauri_build::AppManifest::new().commands(&[
+        "get_backend_health",
+        "tail_log",
+        "open_log",
+        "export_diagnostics",
+        "list_sessions",
+        "get_session",
+        "add_session",
+        "delete_session",
+        "rename_session",
+        "sync_orphans",
+        "clear_history",
+        "list_jobs",
+        "enqueue_jobs",
+        "pause_jobs",
+        "resume_jobs",
+        "cancel_job",
+        "move_job",
+        "retry_jobs",
+        "clear_finished_jobs",
+        "set_job_concurrency",
+        "list_watch_folders",
+        "add_watch_folder",
+        "remove_watch_folder",
+        "search_notes",
+        "export_subtitles",
+        "export_to_vault",
+        "export_report",
+        "export_epub",
+        "export_anki",
+        "get_library_dir",
+        "move_library",
+        "get_config",
+        "set_config",
+        "get_secret_status",
+        "set_secret",
+        "get_startup_status",
+        "retry_startup",
+      ]),
+    ),
+  )
+  .expect("failed to run tauri-build");
 }
@@ -0,0 +1,48 @@
+// Query parameter the webview uses to hand its token to the sidecar once;
+// the sidecar answers with an HttpOnly cookie (see LauncherAuthMiddleware).
+const TOKEN_QUERY_PARAM: &str = "oan_token";
+
+const SECRET_BYTES: usize = 32;
+
+/// Secrets generated fresh on every launch and shared only with the sidecar.
+///
+/// They travel through the sidecar's environment rather than its argv, since
+/// command lines are visible to every local process.
+pub struct LaunchSecrets {
+    storage_secret: String,
+    token: String,
+}
+
+impl LaunchSecrets {
+    pub fn generate() -> Self {
+        Self {
+            storage_secret: random_hex(SECRET_BYTES),
+            token: random_hex(SECRET_BYTES),
+        }
+    }
+
+    pub fn sidecar_env(&self) -> [(&'static str, &str); 2] {
+        [
+            ("OAN_STORAGE_SECRET", &self.storage_secret),
+            ("OAN_AUTH_TOKEN", &self.token),
+        ]
+    }
+
+    /// Value for the `Authorization` header on Rust-side requests.
+    pub fn bearer(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+
+    /// First URL loaded by the main webview; the sidecar trades the token in
+    /// the query string for a session cookie and redirects to `/`.
+    pub fn login_url(&self, base_url: &str) -> String {
+        format!("{}/?{}={}", base_url, TOKEN_QUERY_PARAM, self.token)
+    }
+}
+
+/// `len` random bytes as lowercase hex.
+pub fn random_hex(len: usize) -> String {
+    let mut buf = vec![0u8; len];
+    getrandom::getrandom(&mut buf).expect("OS random number generator unavailable");
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
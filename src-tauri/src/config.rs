@@ -0,0 +1,513 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::auth::LaunchSecrets;
+use crate::library::Library;
+use crate::secrets::SECRET_KEYS;
+use crate::supervisor::{self, Supervisor};
+
+const CONFIG_FILE: &str = "user_config.json";
+const RELOAD_PATH: &str = "/api/config/reload";
+
+/// Bumped whenever a migration is added to `migrate`.
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HardwareMode {
+    Mlx,
+    Cuda,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VisionDetail {
+    Low,
+    High,
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UiLanguage {
+    Zh,
+    En,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    Light,
+    Dark,
+    Auto,
+}
+
+/// The sidecar's user_config.json. Defaults match `DEFAULT_CONFIG` in
+/// main.py; keys this struct doesn't know are kept in `extra`. The API key
+/// and cookies live in the `SecretStore` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub schema_version: u32,
+    pub base_url: String,
+    pub model_name: String,
+    pub asr_model: String,
+    pub language: String,
+    pub hardware_mode: HardwareMode,
+    pub enable_vision: bool,
+    /// Seconds between captured frames.
+    pub vision_interval: u32,
+    pub vision_detail: VisionDetail,
+    pub detail_level: String,
+    pub ui_language: UiLanguage,
+    pub theme_mode: ThemeMode,
+    pub use_china_mirror: bool,
+    pub enable_chunk_summary: bool,
+    pub deep_thinking: bool,
+    pub first_launch_completed: bool,
+    pub remind_gpu_install: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            schema_version: CONFIG_VERSION,
+            base_url: "https://api.openai.com/v1".to_string(),
+            model_name: "gpt-4o".to_string(),
+            asr_model: "small".to_string(),
+            language: "Simplified Chinese (Default)".to_string(),
+            // The sidecar corrects this to the detected hardware on load
+            hardware_mode: if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+                HardwareMode::Mlx
+            } else {
+                HardwareMode::Cpu
+            },
+            enable_vision: true,
+            vision_interval: 15,
+            vision_detail: VisionDetail::Low,
+            detail_level: "Standard".to_string(),
+            ui_language: UiLanguage::Zh,
+            theme_mode: ThemeMode::Light,
+            use_china_mirror: false,
+            enable_chunk_summary: false,
+            deep_thinking: false,
+            first_launch_completed: false,
+            remind_gpu_install: true,
+            extra: Map::new(),
+        }
+    }
+}
+
+/// A problem with one config key.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum ConfigError {
+    /// The file or the update has invalid fields; nothing was written.
+    Invalid(Vec<FieldError>),
+    Io(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Invalid(errors) => {
+                let fields: Vec<String> = errors
+                    .iter()
+                    .map(|e| format!("{}: {}", e.field, e.message))
+                    .collect();
+                write!(f, "invalid config ({})", fields.join("; "))
+            }
+            ConfigError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Semantic checks on top of what the types already enforce.
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let lower = self.base_url.to_ascii_lowercase();
+        if !lower.starts_with("http://") && !lower.starts_with("https://") {
+            errors.push(FieldError::new(
+                "base_url",
+                "must start with http:// or https://",
+            ));
+        }
+        if self.model_name.trim().is_empty() {
+            errors.push(FieldError::new("model_name", "must not be empty"));
+        }
+        if self.asr_model.trim().is_empty() {
+            errors.push(FieldError::new("asr_model", "must not be empty"));
+        }
+        // Same range as the settings slider
+        if !(5..=60).contains(&self.vision_interval) {
+            errors.push(FieldError::new(
+                "vision_interval",
+                "must be between 5 and 60 seconds",
+            ));
+        }
+        if self.schema_version > CONFIG_VERSION {
+            errors.push(FieldError::new(
+                "schema_version",
+                format!("newer than supported version {}", CONFIG_VERSION),
+            ));
+        }
+        errors
+    }
+
+    /// Build a config from raw JSON, keeping every field that parses and
+    /// falling back to the default for the ones that don't. All problems
+    /// are returned instead of discarding the whole file.
+    fn from_fields(fields: Map<String, Value>) -> (Self, Vec<FieldError>) {
+        let mut merged = match serde_json::to_value(Self::default()) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        };
+        let mut errors = Vec::new();
+        for (key, value) in fields {
+            let mut trial = merged.clone();
+            trial.insert(key.clone(), value);
+            match serde_json::from_value::<Self>(Value::Object(trial.clone())) {
+                Ok(_) => merged = trial,
+                Err(e) => errors.push(FieldError::new(&key, e.to_string())),
+            }
+        }
+        let config = serde_json::from_value(Value::Object(merged)).unwrap_or_default();
+        (config, errors)
+    }
+}
+
+// The switches in `AppConfig`; other strings are left alone even if they
+// happen to read "true"
+const BOOL_FIELDS: [&str; 6] = [
+    "enable_vision",
+    "use_china_mirror",
+    "enable_chunk_summary",
+    "deep_thinking",
+    "first_launch_completed",
+    "remind_gpu_install",
+];
+
+/// Upgrade a config written by an older version, in place.
+fn migrate(fields: &mut Map<String, Value>) {
+    let version = fields
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+
+    if version < 1 {
+        // v0 files come straight from main.py, where settings inputs could
+        // store numbers and switches as strings
+        if let Some(Value::String(s)) = fields.get("vision_interval") {
+            if let Ok(n) = s.trim().parse::<u32>() {
+                fields.insert("vision_interval".to_string(), n.into());
+            }
+        }
+        for key in BOOL_FIELDS {
+            if let Some(value) = fields.get_mut(key) {
+                match value.as_str() {
+                    Some("true" | "True") => *value = Value::Bool(true),
+                    Some("false" | "False") => *value = Value::Bool(false),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if version < u64::from(CONFIG_VERSION) {
+        fields.insert("schema_version".to_string(), CONFIG_VERSION.into());
+    }
+}
+
+/// Reads and writes user_config.json in the library. Writes are serialized
+/// and atomic.
+#[derive(Default)]
+pub struct ConfigStore {
+    lock: Mutex<()>,
+}
+
+/// The current config plus any problems found while loading it.
+#[derive(Debug, Serialize)]
+pub struct ConfigReport {
+    pub config: AppConfig,
+    pub errors: Vec<FieldError>,
+}
+
+impl ConfigStore {
+    pub fn load(&self, dir: &Path) -> Result<ConfigReport, ConfigError> {
+        let _guard = self.lock.lock().unwrap();
+        load_unlocked(&dir.join(CONFIG_FILE))
+    }
+
+    /// Merge `patch` into the stored fields and write them out. Nothing is
+    /// written if any field is invalid, including stored ones the patch
+    /// doesn't fix, so a bad value is never silently reset to its default.
+    pub fn update(&self, dir: &Path, patch: Map<String, Value>) -> Result<AppConfig, ConfigError> {
+        let _guard = self.lock.lock().unwrap();
+        let path = dir.join(CONFIG_FILE);
+        let mut fields = read_fields(&path)?;
+        let mut errors = Vec::new();
+        for (key, value) in patch {
+            if key == "schema_version" {
+                errors.push(FieldError::new(&key, "is managed by the launcher"));
+                continue;
+            }
+            if SECRET_KEYS.contains(&key.as_str()) {
+                errors.push(FieldError::new(&key, "is stored in the keychain"));
+                continue;
+            }
+            fields.insert(key, value);
+        }
+        let (config, parse_errors) = AppConfig::from_fields(fields);
+        errors.extend(parse_errors);
+        errors.extend(config.validate());
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        write_atomic(&path, &config).map_err(|e| ConfigError::Io(e.to_string()))?;
+        Ok(config)
+    }
+
+    /// Values of `keys` exactly as stored in the file, ignoring the schema.
+    pub fn raw_values(&self, dir: &Path, keys: &[&str]) -> Result<Map<String, Value>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let fields = read_raw(&dir.join(CONFIG_FILE))?;
+        Ok(fields
+            .into_iter()
+            .filter(|(key, _)| keys.contains(&key.as_str()))
+            .collect())
+    }
+
+    /// Drop `keys` from the file, leaving everything else untouched.
+    pub fn remove_keys(&self, dir: &Path, keys: &[&str]) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let path = dir.join(CONFIG_FILE);
+        let mut fields = read_raw(&path)?;
+        if keys.iter().filter_map(|key| fields.remove(*key)).count() == 0 {
+            return Ok(());
+        }
+        write_atomic(&path, &fields).map_err(|e| e.to_string())
+    }
+}
+
+fn read_raw(path: &Path) -> Result<Map<String, Value>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => match serde_json::from_str(&text) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err(format!("{} is not a JSON object", path.display())),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Map::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn load_unlocked(path: &Path) -> Result<ConfigReport, ConfigError> {
+    let (config, mut errors) = AppConfig::from_fields(read_fields(path)?);
+    errors.extend(config.validate());
+    for error in &errors {
+        warn!("{}: {}: {}", path.display(), error.field, error.message);
+    }
+    Ok(ConfigReport { config, errors })
+}
+
+/// The file's fields, migrated and without secrets; none if it is missing.
+fn read_fields(path: &Path) -> Result<Map<String, Value>, ConfigError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Map::new()),
+        Err(e) => return Err(ConfigError::Io(format!("{}: {}", path.display(), e))),
+    };
+    let mut fields = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(map)) => map,
+        Ok(_) => {
+            return Err(ConfigError::Invalid(vec![FieldError::new(
+                "",
+                "the file is not a JSON object",
+            )]))
+        }
+        Err(e) => {
+            return Err(ConfigError::Invalid(vec![FieldError::new(
+                "",
+                format!("the file is not valid JSON: {}", e),
+            )]))
+        }
+    };
+    // Imported into the SecretStore at startup; never part of the config
+    for key in SECRET_KEYS {
+        fields.remove(key);
+    }
+    migrate(&mut fields);
+    Ok(fields)
+}
+
+/// Write to a temp file in the same folder, then rename over the original,
+/// so readers never see a half-written file.
+fn write_atomic<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Tell the running sidecar to re-read its config.
+async fn notify_sidecar(app: &AppHandle) -> Result<(), reqwest::Error> {
+    let port = app.state::<Supervisor>().port();
+    let bearer = app.state::<LaunchSecrets>().bearer();
+    reqwest::Client::new()
+        .post(format!("{}{}", supervisor::backend_url(port), RELOAD_PATH))
+        .header(reqwest::header::AUTHORIZATION, bearer)
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await?
+        .error_for_status()
+        .map(|_| ())
+}
+
+/// The current config, with any load problems listed per field. A
+/// malformed file is reported, never replaced with defaults.
+#[tauri::command]
+pub fn get_config(
+    store: State<'_, ConfigStore>,
+    library: State<'_, Library>,
+) -> Result<ConfigReport, ConfigError> {
+    store.load(&library.dir())
+}
+
+/// Apply `patch` (any subset of the config keys) and save. Returns the new
+/// config, or every invalid field.
+#[tauri::command]
+pub async fn set_config(
+    app: AppHandle,
+    patch: Map<String, Value>,
+) -> Result<AppConfig, ConfigError> {
+    let dir = app.state::<Library>().dir();
+    let config = app.state::<ConfigStore>().update(&dir, patch)?;
+    info!("Config saved");
+    if let Err(e) = notify_sidecar(&app).await {
+        warn!("Failed to reload sidecar config: {}", e);
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use serde_json::json;
+
+    fn patch(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("patch must be an object"),
+        }
+    }
+
+    #[test]
+    fn update_refuses_to_reset_an_invalid_stored_field() {
+        let dir = scratch_dir("config", "invalid-stored");
+        let original = r#"{"schema_version": 1, "vision_interval": 500, "model_name": "m"}"#;
+        fs::write(dir.join(CONFIG_FILE), original).unwrap();
+
+        let store = ConfigStore::default();
+        match store.update(&dir, patch(json!({"model_name": "other"}))) {
+            Err(ConfigError::Invalid(errors)) => {
+                assert!(errors.iter().any(|e| e.field == "vision_interval"))
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert_eq!(fs::read_to_string(dir.join(CONFIG_FILE)).unwrap(), original);
+
+        // Fixing the bad field in the same patch is allowed
+        let config = store
+            .update(
+                &dir,
+                patch(json!({"model_name": "other", "vision_interval": 20})),
+            )
+            .unwrap();
+        assert_eq!(config.model_name, "other");
+        assert_eq!(config.vision_interval, 20);
+    }
+
+    #[test]
+    fn update_refuses_a_field_of_the_wrong_type() {
+        let dir = scratch_dir("config", "wrong-type");
+        let original = r#"{"schema_version": 1, "hardware_mode": "quantum"}"#;
+        fs::write(dir.join(CONFIG_FILE), original).unwrap();
+
+        let store = ConfigStore::default();
+        assert!(store
+            .update(&dir, patch(json!({"deep_thinking": true})))
+            .is_err());
+        assert_eq!(fs::read_to_string(dir.join(CONFIG_FILE)).unwrap(), original);
+    }
+
+    #[test]
+    fn update_keeps_unknown_keys_and_starts_from_defaults() {
+        let dir = scratch_dir("config", "unknown-keys");
+        let store = ConfigStore::default();
+        store
+            .update(&dir, patch(json!({"custom_flag": 3})))
+            .unwrap();
+        let config = store
+            .update(&dir, patch(json!({"deep_thinking": true})))
+            .unwrap();
+        assert!(config.deep_thinking);
+        assert_eq!(config.extra.get("custom_flag"), Some(&json!(3)));
+        assert_eq!(config.schema_version, CONFIG_VERSION);
+    }
+
+    #[test]
+    fn migrate_converts_only_known_switches() {
+        let mut fields = patch(json!({
+            "vision_interval": " 30 ",
+            "enable_vision": "True",
+            "deep_thinking": "false",
+            "detail_level": "true",
+            "custom_note": "False",
+        }));
+        migrate(&mut fields);
+        assert_eq!(
+            Value::Object(fields),
+            json!({
+                "schema_version": CONFIG_VERSION,
+                "vision_interval": 30,
+                "enable_vision": true,
+                "deep_thinking": false,
+                "detail_level": "true",
+                "custom_note": "False",
+            })
+        );
+    }
+}
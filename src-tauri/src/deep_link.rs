@@ -0,0 +1,197 @@
+use log::{info, warn};
+use tauri::{App, AppHandle, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::jobs;
+use crate::launch_inputs::{self, LaunchInput};
+
+/// Declared under `plugins.deep-link` in tauri.conf.json, which installers
+/// register with the OS; `listen` registers it for builds that aren't
+/// installed.
+pub const SCHEME: &str = "openautonote";
+
+// openautonote://summarize?url=<video page>
+const SUMMARIZE: &str = "summarize";
+const BVID_LEN: usize = 12;
+
+pub fn is_deep_link(arg: &str) -> bool {
+    arg.len() > SCHEME.len()
+        && arg[..SCHEME.len()].eq_ignore_ascii_case(SCHEME)
+        && arg[SCHEME.len()..].starts_with(':')
+}
+
+/// Route links from the deep-link plugin to `handle`, starting with the one
+/// the app was launched with. Links given to a second launch reach the
+/// plugin through the single-instance plugin.
+pub fn listen(app: &App) {
+    // Installers register the scheme; dev builds and AppImages must do it
+    // themselves. macOS only reads it from the bundle's Info.plist.
+    #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+    if let Err(e) = app.deep_link().register_all() {
+        warn!("Failed to register the {} scheme: {}", SCHEME, e);
+    }
+    let app_handle = app.handle().clone();
+    app.deep_link()
+        .on_open_url(move |event| handle(&app_handle, event.urls()));
+    match app.deep_link().get_current() {
+        Ok(Some(urls)) => handle(app.handle(), urls),
+        Ok(None) => {}
+        Err(e) => warn!("Failed to read the launch deep link: {}", e),
+    }
+}
+
+/// Check a deep link and return the video URL it asks for.
+pub fn parse(link: &str) -> Result<String, String> {
+    let link = Url::parse(link).map_err(|e| format!("Invalid link: {}", e))?;
+    if !link.scheme().eq_ignore_ascii_case(SCHEME) {
+        return Err(format!("Not an {} link", SCHEME));
+    }
+    // "openautonote://summarize?..." puts the action in the host;
+    // "openautonote:summarize?..." in the path
+    let action = link
+        .host_str()
+        .unwrap_or_else(|| link.path())
+        .trim_matches('/');
+    if !action.eq_ignore_ascii_case(SUMMARIZE) {
+        return Err(format!("Unknown action: {}", action));
+    }
+    let target = link
+        .query_pairs()
+        .find(|(key, _)| key == "url")
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or("The link has no url parameter")?;
+    let url = Url::parse(&target).map_err(|e| format!("Invalid url {}: {}", target, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("Not a web page: {}", target));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("Links with credentials are not accepted".to_string());
+    }
+    Ok(clean_bilibili_url(url.as_str()))
+}
+
+/// Reduce any Bilibili link carrying a BV id (video page, watch-later,
+/// share link) to the plain video URL. Same as `clean_bilibili_url` in
+/// python-core/core/utils.py.
+pub fn clean_bilibili_url(url: &str) -> String {
+    let bytes = url.as_bytes();
+    let bvid = (0..bytes.len().saturating_sub(BVID_LEN - 1)).find_map(|i| {
+        let candidate = &bytes[i..i + BVID_LEN];
+        (candidate.starts_with(b"BV") && candidate[2..].iter().all(u8::is_ascii_alphanumeric))
+            .then(|| &url[i..i + BVID_LEN])
+    });
+    match bvid {
+        Some(bvid) => format!("https://www.bilibili.com/video/{}", bvid),
+        None => url.to_string(),
+    }
+}
+
+/// Queue the videos named by `links` and bring the main window forward.
+/// Anything that isn't one of our links, such as a file macOS was asked to
+/// open, is left alone.
+pub fn handle(app: &AppHandle, links: Vec<Url>) {
+    let links: Vec<Url> = links
+        .into_iter()
+        .filter(|link| is_deep_link(link.as_str()))
+        .collect();
+    if links.is_empty() {
+        return;
+    }
+    let sources: Vec<LaunchInput> = links
+        .iter()
+        .filter_map(|link| match parse(link.as_str()) {
+            Ok(url) => {
+                info!("Deep link for {}", url);
+                Some(LaunchInput::Url(url))
+            }
+            Err(e) => {
+                warn!("Ignoring deep link: {}", e);
+                None
+            }
+        })
+        .collect();
+    jobs::enqueue(app, sources);
+    launch_inputs::focus_main_window(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_the_scheme_in_any_case() {
+        assert!(is_deep_link("openautonote://summarize?url=x"));
+        assert!(is_deep_link("OpenAutoNote:summarize"));
+        assert!(!is_deep_link("openautonote"));
+        assert!(!is_deep_link("openautonotes://summarize"));
+        assert!(!is_deep_link("https://example.com"));
+    }
+
+    #[test]
+    fn parses_both_link_forms() {
+        let expected = "https://www.youtube.com/watch?v=abc";
+        assert_eq!(
+            parse("openautonote://summarize?url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3Dabc"),
+            Ok(expected.to_string())
+        );
+        assert_eq!(
+            parse("openautonote:summarize?url=https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3Dabc"),
+            Ok(expected.to_string())
+        );
+        assert_eq!(
+            parse("OPENAUTONOTE://Summarize/?url=https://example.com/a"),
+            Ok("https://example.com/a".to_string())
+        );
+    }
+
+    #[test]
+    fn cleans_bilibili_targets() {
+        assert_eq!(
+            parse("openautonote://summarize?url=https%3A%2F%2Fb23.tv%2FBV1xx411c7mD%3Fshare%3D1"),
+            Ok("https://www.bilibili.com/video/BV1xx411c7mD".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_links() {
+        assert!(parse("not a link").is_err());
+        assert!(parse("https://example.com/?url=https://example.com").is_err());
+        assert!(parse("openautonote://delete?url=https://example.com").is_err());
+        assert!(parse("openautonote://summarize").is_err());
+        assert!(parse("openautonote://summarize?url=%20").is_err());
+        assert!(parse("openautonote://summarize?url=file:///etc/passwd").is_err());
+        assert!(parse("openautonote://summarize?url=javascript:alert(1)").is_err());
+        assert!(parse("openautonote://summarize?url=https://user:pw@example.com/").is_err());
+    }
+
+    #[test]
+    fn reduces_bilibili_urls_to_the_video_page() {
+        for url in [
+            "https://www.bilibili.com/video/BV1xx411c7mD?p=2&spm_id_from=333",
+            "https://www.bilibili.com/list/watchlater?bvid=BV1xx411c7mD&oid=1",
+            "https://m.bilibili.com/video/BV1xx411c7mD/",
+            "https://b23.tv/BV1xx411c7mD",
+        ] {
+            assert_eq!(
+                clean_bilibili_url(url),
+                "https://www.bilibili.com/video/BV1xx411c7mD",
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_other_urls_alone() {
+        for url in [
+            "https://www.youtube.com/watch?v=BVabc",
+            "https://example.com/BV1xx41",
+            "https://example.com/BV1xx411c7m-",
+            "",
+            "BV",
+        ] {
+            assert_eq!(clean_bilibili_url(url), url);
+        }
+    }
+}
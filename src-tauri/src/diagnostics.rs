@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::export::add_to_zip;
+use crate::health::HealthMonitor;
+use crate::history::HistoryStore;
+use crate::library::Library;
+use crate::logging;
+
+const CONFIG_FILE: &str = "user_config.json";
+const REDACTED: &str = "<redacted>";
+
+// Config keys that hold credentials; anything matching is never exported
+const SECRET_KEY_MARKERS: [&str; 5] = ["key", "token", "secret", "cookie", "password"];
+
+#[derive(Serialize)]
+struct SystemReport {
+    generated_at: u64,
+    app_version: String,
+    os: &'static str,
+    arch: &'static str,
+    family: &'static str,
+    sidecar: Value,
+}
+
+/// Shape of the history database without any of its content.
+#[derive(Default, Serialize)]
+struct HistoryStats {
+    db_bytes: u64,
+    session_count: usize,
+    sessions_missing_project_dir: usize,
+    status_counts: BTreeMap<String, usize>,
+    error: Option<String>,
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEY_MARKERS.iter().any(|m| key.contains(m))
+}
+
+/// Replace every non-empty credential value in the config with a marker,
+/// including those in nested objects and arrays.
+fn sanitize_config(mut config: Value) -> Value {
+    match &mut config {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let empty = matches!(value, Value::Null) || value.as_str() == Some("");
+                if is_secret_key(key) && !empty {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    *value = sanitize_config(value.take());
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                *item = sanitize_config(item.take());
+            }
+        }
+        _ => {}
+    }
+    config
+}
+
+fn history_stats(store: &HistoryStore) -> HistoryStats {
+    let mut stats = HistoryStats {
+        db_bytes: fs::metadata(store.path()).map(|m| m.len()).unwrap_or(0),
+        ..Default::default()
+    };
+    let sessions = match store.list() {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            stats.error = Some(e.to_string());
+            return stats;
+        }
+    };
+    stats.session_count = sessions.len();
+    for session in &sessions {
+        let status = session
+            .extra
+            .get("status")
+            .and_then(Value::as_str)
+            .unwrap_or("completed");
+        *stats.status_counts.entry(status.to_string()).or_default() += 1;
+        if !Path::new(&session.project_dir).is_dir() {
+            stats.sessions_missing_project_dir += 1;
+        }
+    }
+    stats
+}
+
+fn write_json<T: Serialize>(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    value: &T,
+) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    add_to_zip(zip, name, &json, SimpleFileOptions::default())
+}
+
+fn write_file(zip: &mut ZipWriter<File>, name: &str, path: &Path) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    add_to_zip(zip, name, &bytes, SimpleFileOptions::default())
+}
+
+fn write_archive(app: &AppHandle, path: &Path, system: &SystemReport) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    write_json(&mut zip, "system.json", system)?;
+
+    for path in logging::log_files(app) {
+        if let Some(name) = path.file_name() {
+            let name = format!("logs/{}", name.to_string_lossy());
+            if let Err(e) = write_file(&mut zip, &name, &path) {
+                warn!("Skipping log file in diagnostics: {}", e);
+            }
+        }
+    }
+
+    let config_path = app.state::<Library>().dir().join(CONFIG_FILE);
+    let config = fs::read_to_string(config_path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| serde_json::from_str::<Value>(&s).map_err(|e| e.to_string()));
+    match config {
+        Ok(config) => write_json(&mut zip, CONFIG_FILE, &sanitize_config(config))?,
+        Err(e) => write_json(&mut zip, CONFIG_FILE, &serde_json::json!({ "error": e }))?,
+    }
+    let stats = history_stats(&app.state::<HistoryStore>());
+    write_json(&mut zip, "history_stats.json", &stats)?;
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write a zip with logs, sanitized config, history stats and system info
+/// for bug reports. Returns the path of the archive.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: AppHandle,
+    destination: Option<String>,
+) -> Result<String, String> {
+    let monitor = app.state::<HealthMonitor>();
+    let health = match monitor.check().await {
+        Ok(health) => Ok(health),
+        Err(e) => monitor.latest().ok_or(e),
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = match destination {
+        Some(dir) => PathBuf::from(dir),
+        None => app
+            .path()
+            .download_dir()
+            .or_else(|_| app.path().app_data_dir())
+            .map_err(|e| e.to_string())?,
+    };
+    let out_path = dir.join(format!("openautonote-diagnostics-{}.zip", now));
+
+    let system = SystemReport {
+        generated_at: now,
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        family: std::env::consts::FAMILY,
+        sidecar: match &health {
+            Ok(health) => serde_json::to_value(health).unwrap_or(Value::Null),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        },
+    };
+
+    // Logs and the history can be large; keep them off the async runtime,
+    // and only put the archive in place once it is complete
+    let out = out_path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let mut tmp = out.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let result = write_archive(&app, &tmp, &system)
+            .and_then(|_| fs::rename(&tmp, &out).map_err(|e| e.to_string()));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    info!("Diagnostics written to {}", out_path.display());
+    Ok(out_path.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sanitize_config_redacts_nested_credentials() {
+        let config = json!({
+            "api_key": "sk-123",
+            "openai_token": "",
+            "cookie_file": null,
+            "model_name": "gpt-4o",
+            "providers": {
+                "openai": {"apiKey": "sk-456", "base_url": "https://api.openai.com"},
+                "proxy": {"Password": "hunter2", "port": 8080},
+            },
+            "accounts": [{"name": "me", "session_token": "abc"}],
+            "secrets": {"anything": "here"},
+        });
+        assert_eq!(
+            sanitize_config(config),
+            json!({
+                "api_key": REDACTED,
+                "openai_token": "",
+                "cookie_file": null,
+                "model_name": "gpt-4o",
+                "providers": {
+                    "openai": {"apiKey": REDACTED, "base_url": "https://api.openai.com"},
+                    "proxy": {"Password": REDACTED, "port": 8080},
+                },
+                "accounts": [{"name": "me", "session_token": REDACTED}],
+                "secrets": REDACTED,
+            })
+        );
+    }
+}
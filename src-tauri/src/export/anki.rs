@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::markdown::{
+    escape_html, format_timestamp, parse_timestamp_marker, timestamp_url, to_html,
+};
+use super::{add_to_zip, file_stem, find_session, read_report, resolve_destination, web_url};
+use crate::history::Session;
+use crate::jobs::sidecar_request;
+
+const FLASHCARDS_PATH: &str = "/api/flashcards";
+// The model writes the whole deck in one reply
+const FLASHCARDS_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_CARD_COUNT: usize = 20;
+const MAX_CARD_COUNT: usize = 100;
+/// A frame further than this from a card's timestamp shows something else.
+const MAX_FRAME_DISTANCE: u32 = 60;
+
+// Fixed, so every deck shares one note type in the user's collection
+const MODEL_ID: i64 = 1_718_000_000_000;
+const MODEL_NAME: &str = "OpenAutoNote Q&A";
+const FIELDS: [&str; 4] = ["Question", "Answer", "Frame", "Source"];
+const PARENT_DECK: &str = "OpenAutoNote";
+const TAG: &str = "openautonote";
+
+const QUESTION_TEMPLATE: &str = "{{Question}}";
+const ANSWER_TEMPLATE: &str = "{{FrontSide}}\n\n<hr id=answer>\n\n{{Answer}}\n\n\
+    {{#Frame}}<div class=\"frame\">{{Frame}}</div>{{/Frame}}\n\
+    <div class=\"source\">{{Source}}</div>";
+const CARD_STYLE: &str = ".card { font: 20px/1.5 -apple-system, \"Segoe UI\", \"PingFang SC\", \"Microsoft YaHei\", sans-serif;
+  text-align: left; color: #1c1b1f; background: #fffbfe; }
+.card p { margin: 0.4em 0; }
+.frame img { max-width: 100%; border-radius: 8px; margin-top: 12px; }
+.source { margin-top: 12px; font-size: 0.75em; color: #666; }
+.source a { color: #6750a4; }
+.nightMode .card, .night_mode .card { color: #e6e1e5; background: #1c1b1f; }
+";
+const LATEX_PRE: &str = "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\
+    \\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\
+    \\setlength{\\parindent}{0in}\n\\begin{document}\n";
+
+// Anki's collection schema 11, which every version can import
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null,
+    scm integer not null, ver integer not null, dty integer not null,
+    usn integer not null, ls integer not null, conf text not null,
+    models text not null, decks text not null, dconf text not null,
+    tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null,
+    mod integer not null, usn integer not null, tags text not null,
+    flds text not null, sfld integer not null, csum integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null,
+    ord integer not null, mod integer not null, usn integer not null,
+    type integer not null, queue integer not null, due integer not null,
+    ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null,
+    odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null,
+    ease integer not null, ivl integer not null, lastIvl integer not null,
+    factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (
+    usn integer not null, oid integer not null, type integer not null
+);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+/// A question and answer the sidecar wrote from a report.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Flashcard {
+    pub question: String,
+    pub answer: String,
+    /// `MM:SS` or `H:MM:SS` of the part of the video the card is about.
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+impl Flashcard {
+    fn seconds(&self) -> Option<u32> {
+        let timestamp = self.timestamp.as_deref()?.trim();
+        parse_timestamp_marker(&format!("[{}]", timestamp)).map(|(seconds, _)| seconds)
+    }
+}
+
+#[derive(Deserialize)]
+struct FlashcardsResponse {
+    cards: Vec<Flashcard>,
+}
+
+#[derive(Deserialize)]
+struct SidecarError {
+    error: String,
+}
+
+/// Ask the sidecar's model for up to `count` cards about `report`.
+async fn generate_cards(
+    app: &AppHandle,
+    report: &str,
+    count: usize,
+) -> Result<Vec<Flashcard>, String> {
+    let response = sidecar_request(app, reqwest::Method::POST, FLASHCARDS_PATH)
+        .timeout(FLASHCARDS_TIMEOUT)
+        .json(&json!({ "report": report, "count": count }))
+        .send()
+        .await
+        .map_err(|e| format!("Couldn't reach the engine: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(match response.json::<SidecarError>().await {
+            Ok(body) => body.error,
+            Err(_) => format!("HTTP {}", status),
+        });
+    }
+    let cards = response
+        .json::<FlashcardsResponse>()
+        .await
+        .map_err(|e| e.to_string())?
+        .cards;
+    if cards.is_empty() {
+        return Err("No flashcards were generated".to_string());
+    }
+    Ok(cards)
+}
+
+/// The session's frames by the second they show, from `frame_65.jpg` or
+/// `frame_0065.jpg`.
+fn frames(project_dir: &Path) -> Vec<(u32, PathBuf)> {
+    let Ok(entries) = fs::read_dir(project_dir.join("assets")) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let seconds = name
+                .strip_prefix("frame_")?
+                .strip_suffix(".jpg")?
+                .parse()
+                .ok()?;
+            Some((seconds, entry.path()))
+        })
+        .collect()
+}
+
+fn nearest_frame(frames: &[(u32, PathBuf)], seconds: u32) -> Option<&Path> {
+    frames
+        .iter()
+        .map(|(at, path)| (at.abs_diff(seconds), path))
+        .filter(|(distance, _)| *distance <= MAX_FRAME_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, path)| path.as_path())
+}
+
+// Positive ids that stay the same across exports of a session
+fn stable_id(key: &str) -> i64 {
+    let digest = Sha256::digest(key.as_bytes());
+    i64::from_be_bytes(digest[..8].try_into().unwrap()) & (i64::MAX >> 1)
+}
+
+// The same question from the same session is the same note, so importing a
+// deck again updates it instead of adding copies
+fn guid(session: &Session, question: &str) -> String {
+    let digest = Sha256::digest(format!("{}\u{1f}{}", session.id, question).as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Anki finds duplicates by the first 8 hex digits of the sort field's SHA-1
+fn checksum(text: &str) -> i64 {
+    let digest = Sha1::digest(text.as_bytes());
+    i64::from(u32::from_be_bytes(digest[..4].try_into().unwrap()))
+}
+
+/// A note ready for the collection.
+struct Note {
+    guid: String,
+    fields: [String; 4],
+    sort_field: String,
+}
+
+/// Media files in the package: the name cards use and where to read it.
+#[derive(Default)]
+struct Media {
+    files: Vec<(String, PathBuf)>,
+    names: HashMap<PathBuf, String>,
+}
+
+impl Media {
+    fn add(&mut self, session: &Session, source: &Path) -> Option<String> {
+        if let Some(name) = self.names.get(source) {
+            return Some(name.clone());
+        }
+        // Anki keeps every deck's media in one folder
+        let name = format!(
+            "oan-{}-{}",
+            session.id,
+            source.file_name()?.to_string_lossy()
+        );
+        self.files.push((name.clone(), source.to_path_buf()));
+        self.names.insert(source.to_path_buf(), name.clone());
+        Some(name)
+    }
+}
+
+fn build_notes(session: &Session, cards: &[Flashcard], media: &mut Media) -> Vec<Note> {
+    let frames = frames(Path::new(&session.project_dir));
+    let title = escape_html(&session.title);
+    cards
+        .iter()
+        .filter(|card| !card.question.trim().is_empty() && !card.answer.trim().is_empty())
+        .map(|card| {
+            let question = card.question.trim();
+            let seconds = card.seconds();
+            let frame = seconds
+                .and_then(|seconds| nearest_frame(&frames, seconds))
+                .and_then(|path| media.add(session, path))
+                .map(|name| format!("<img src=\"{}\">", escape_html(&name)))
+                .unwrap_or_default();
+            let source = match seconds {
+                Some(seconds) => {
+                    let marker = format!("[{}]", format_timestamp(seconds));
+                    match timestamp_url(&session.video_url, seconds) {
+                        Some(url) => {
+                            format!("<a href=\"{}\">{}</a> {}", escape_html(&url), marker, title)
+                        }
+                        None => format!("{} {}", marker, title),
+                    }
+                }
+                None => title.clone(),
+            };
+            Note {
+                guid: guid(session, question),
+                fields: [
+                    escape_html(question).replace('\n', "<br>"),
+                    to_html(card.answer.trim()).html,
+                    frame,
+                    source,
+                ],
+                sort_field: question.to_string(),
+            }
+        })
+        .collect()
+}
+
+// The name Anki shows under the OpenAutoNote deck; `::` would nest it further
+fn deck_name(session: &Session) -> String {
+    let title = session.title.trim().replace("::", ":");
+    let title = if title.is_empty() {
+        session.id.clone()
+    } else {
+        title
+    };
+    format!("{}::{}", PARENT_DECK, title)
+}
+
+fn deck(id: i64, name: &str, description: &str, now: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "desc": description,
+        "mod": now,
+        "usn": -1,
+        "dyn": 0,
+        "conf": 1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn model(deck_id: i64, now: i64) -> Value {
+    let fields: Vec<Value> = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })
+        })
+        .collect();
+    json!({
+        "id": MODEL_ID,
+        "name": MODEL_NAME,
+        "type": 0,
+        "mod": now,
+        "usn": -1,
+        "sortf": 0,
+        "did": deck_id,
+        "flds": fields,
+        "tmpls": [{
+            "name": "Card 1",
+            "ord": 0,
+            "qfmt": QUESTION_TEMPLATE,
+            "afmt": ANSWER_TEMPLATE,
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null,
+        }],
+        "css": CARD_STYLE,
+        "latexPre": LATEX_PRE,
+        "latexPost": "\\end{document}",
+        "req": [[0, "any", [0]]],
+        "tags": [],
+        "vers": [],
+    })
+}
+
+fn deck_options(now: i64) -> Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": now,
+        "usn": 0,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "delays": [1, 10],
+            "ints": [1, 4, 7],
+            "initialFactor": 2500,
+            "order": 1,
+            "perDay": 20,
+            "bury": true,
+            "separate": true,
+        },
+        "rev": {
+            "perDay": 200,
+            "ease4": 1.3,
+            "fuzz": 0.05,
+            "ivlFct": 1,
+            "maxIvl": 36500,
+            "minSpace": 1,
+            "bury": true,
+        },
+        "lapse": {
+            "delays": [10],
+            "mult": 0,
+            "minInt": 1,
+            "leechFails": 8,
+            "leechAction": 0,
+        },
+    })
+}
+
+/// Write an Anki collection holding `notes` as new cards in their own deck.
+fn write_collection(path: &Path, session: &Session, notes: &[Note]) -> rusqlite::Result<()> {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let now = now_ms / 1000;
+    let parent_id = stable_id(PARENT_DECK);
+    let deck_id = stable_id(&format!("{}\u{1f}{}", PARENT_DECK, session.id));
+    let description = web_url(&session.video_url)
+        .map(|url| format!("<a href=\"{0}\">{0}</a>", escape_html(url)))
+        .unwrap_or_default();
+
+    let conf = json!({
+        "activeDecks": [1],
+        "curDeck": 1,
+        "curModel": MODEL_ID.to_string(),
+        "nextPos": notes.len() + 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+        "newSpread": 0,
+        "newBury": true,
+        "dueCounts": true,
+        "estTimes": true,
+        "collapseTime": 1200,
+        "timeLim": 0,
+    });
+    let models = json!({ MODEL_ID.to_string(): model(deck_id, now) });
+    let decks = json!({
+        "1": deck(1, "Default", "", now),
+        parent_id.to_string(): deck(parent_id, PARENT_DECK, "", now),
+        deck_id.to_string(): deck(deck_id, &deck_name(session), &description, now),
+    });
+    let dconf = json!({ "1": deck_options(now) });
+
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            now,
+            now_ms,
+            conf.to_string(),
+            models.to_string(),
+            decks.to_string(),
+            dconf.to_string()
+        ],
+    )?;
+    for (i, note) in notes.iter().enumerate() {
+        // Millisecond ids like Anki's own; the importer renumbers clashes
+        let id = now_ms + i as i64;
+        tx.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                id,
+                note.guid,
+                MODEL_ID,
+                now,
+                format!(" {} ", TAG),
+                note.fields.join("\u{1f}"),
+                note.sort_field,
+                checksum(&note.sort_field)
+            ],
+        )?;
+        tx.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_id, now, i as i64 + 1],
+        )?;
+    }
+    tx.commit()
+}
+
+fn write_zip(path: &Path, collection: &Path, media: &Media) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+    let deflated = SimpleFileOptions::default();
+    let data = fs::read(collection).map_err(|e| e.to_string())?;
+    add_to_zip(&mut zip, "collection.anki2", &data, deflated)?;
+
+    // Media files are stored by number, with `media` mapping them to names
+    let mut names = serde_json::Map::new();
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, source) in &media.files {
+        match fs::read(source) {
+            Ok(data) => {
+                let number = names.len().to_string();
+                add_to_zip(&mut zip, &number, &data, stored)?;
+                names.insert(number, Value::String(name.clone()));
+            }
+            Err(e) => warn!("Failed to read {}: {}", source.display(), e),
+        }
+    }
+    add_to_zip(
+        &mut zip,
+        "media",
+        Value::Object(names).to_string().as_bytes(),
+        deflated,
+    )?;
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write `cards` from `session` to `path` as an Anki package. Returns the
+/// number of notes.
+pub fn write_apkg(session: &Session, cards: &[Flashcard], path: &Path) -> Result<usize, String> {
+    let mut media = Media::default();
+    let notes = build_notes(session, cards, &mut media);
+    if notes.is_empty() {
+        return Err("No flashcards were generated".to_string());
+    }
+
+    let with_suffix = |suffix: &str| {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        PathBuf::from(file)
+    };
+    let collection = with_suffix(".anki2.tmp");
+    let tmp = with_suffix(".tmp");
+    let _ = fs::remove_file(&collection);
+    let result = write_collection(&collection, session, &notes)
+        .map_err(|e| e.to_string())
+        .and_then(|_| write_zip(&tmp, &collection, &media))
+        .and_then(|_| fs::rename(&tmp, path).map_err(|e| e.to_string()));
+    let _ = fs::remove_file(&collection);
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+    info!(
+        "Exported {} flashcards with {} frames to {}",
+        notes.len(),
+        media.files.len(),
+        path.display()
+    );
+    Ok(notes.len())
+}
+
+/// Turn a session's report into an Anki deck: the engine writes questions
+/// and answers, and each card gets the nearest frame and a link back to its
+/// timestamp. Returns where it was saved, or `None` if the user cancelled.
+#[tauri::command]
+pub async fn export_anki(
+    app: AppHandle,
+    session_id: String,
+    count: Option<usize>,
+    destination: Option<String>,
+) -> Result<Option<String>, String> {
+    let count = count.unwrap_or(DEFAULT_CARD_COUNT);
+    if !(1..=MAX_CARD_COUNT).contains(&count) {
+        return Err(format!("count must be between 1 and {}", MAX_CARD_COUNT));
+    }
+    let session = find_session(&app, &session_id)?;
+    let report = read_report(&session);
+    if report.trim().is_empty() {
+        return Err("This session has no summary yet".to_string());
+    }
+
+    // Ask first: writing the cards takes a while
+    let file_name = format!("{}.apkg", file_stem(&session.title));
+    let Some(mut path) =
+        resolve_destination(&app, destination, file_name, "Anki deck", &["apkg"]).await?
+    else {
+        return Ok(None);
+    };
+    if path.extension().is_none() {
+        path.set_extension("apkg");
+    }
+    let cards = generate_cards(&app, &report, count).await?;
+    let out = path.clone();
+    tauri::async_runtime::spawn_blocking(move || write_apkg(&session, &cards, &out))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn session(project_dir: &Path) -> Session {
+        serde_json::from_value(json!({
+            "id": "abc",
+            "timestamp": "20240501_093000",
+            "title": "Graphs :: Part 1",
+            "video_url": "https://www.youtube.com/watch?v=xyz",
+            "project_dir": project_dir,
+        }))
+        .unwrap()
+    }
+
+    fn card(question: &str, answer: &str, timestamp: Option<&str>) -> Flashcard {
+        Flashcard {
+            question: question.to_string(),
+            answer: answer.to_string(),
+            timestamp: timestamp.map(str::to_string),
+        }
+    }
+
+    fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn package_holds_notes_cards_decks_and_media() {
+        let dir = scratch_dir("anki", "package");
+        let project = dir.join("project");
+        fs::create_dir_all(project.join("assets")).unwrap();
+        fs::write(project.join("assets/frame_0065.jpg"), b"frame").unwrap();
+        fs::write(project.join("assets/frame_600.jpg"), b"far").unwrap();
+        let talk = session(&project);
+        let cards = [
+            card("What is a graph?", "Nodes and **edges**.", Some("01:10")),
+            card("Who named it?", "Euler.", Some("1:20")),
+            card("  ", "Dropped: no question", None),
+            card("Why study it?", "It models networks.", None),
+        ];
+        let apkg = dir.join("deck.apkg");
+        assert_eq!(write_apkg(&talk, &cards, &apkg), Ok(3));
+        assert!(!dir.join("deck.apkg.tmp").exists());
+        assert!(!dir.join("deck.apkg.anki2.tmp").exists());
+
+        let mut archive = ZipArchive::new(File::open(&apkg).unwrap()).unwrap();
+        // Both cards are near second 65, so the frame is stored once
+        let media: Value = serde_json::from_slice(&read_entry(&mut archive, "media")).unwrap();
+        assert_eq!(media, json!({ "0": "oan-abc-frame_0065.jpg" }));
+        assert_eq!(read_entry(&mut archive, "0"), b"frame");
+        assert!(archive.by_name("1").is_err());
+
+        let collection = dir.join("collection.anki2");
+        fs::write(&collection, read_entry(&mut archive, "collection.anki2")).unwrap();
+        let conn = Connection::open(&collection).unwrap();
+        let deck_id = stable_id(&format!("{}\u{1f}{}", PARENT_DECK, talk.id));
+        let decks: String = conn
+            .query_row("SELECT decks FROM col", [], |row| row.get(0))
+            .unwrap();
+        let decks: Value = serde_json::from_str(&decks).unwrap();
+        assert_eq!(
+            decks[deck_id.to_string()]["name"],
+            "OpenAutoNote::Graphs : Part 1"
+        );
+        assert_eq!(
+            decks[stable_id(PARENT_DECK).to_string()]["name"],
+            PARENT_DECK
+        );
+
+        let mut stmt = conn
+            .prepare("SELECT guid, mid, tags, flds, sfld, csum FROM notes ORDER BY id")
+            .unwrap();
+        let notes: Vec<(String, i64, String, String, String, i64)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(notes.len(), 3);
+        let (guid_0, mid, tags, fields, sort_field, csum) = &notes[0];
+        assert_eq!(*guid_0, guid(&talk, "What is a graph?"));
+        assert_eq!(*mid, MODEL_ID);
+        assert_eq!(tags, " openautonote ");
+        let fields: Vec<&str> = fields.split('\u{1f}').collect();
+        assert_eq!(fields[0], "What is a graph?");
+        assert!(fields[1].contains("<strong>edges</strong>"));
+        assert_eq!(fields[2], "<img src=\"oan-abc-frame_0065.jpg\">");
+        assert!(fields[3].starts_with("<a href=\"https://www.youtube.com/watch?v=xyz&amp;t=70"));
+        assert_eq!(sort_field, "What is a graph?");
+        assert_eq!(*csum, checksum("What is a graph?"));
+        // No timestamp: no frame, and the source is just the title
+        let fields: Vec<&str> = notes[2].3.split('\u{1f}').collect();
+        assert_eq!(fields[2], "");
+        assert_eq!(fields[3], "Graphs :: Part 1");
+
+        let cards: Vec<(i64, i64, i64)> = conn
+            .prepare("SELECT nid, did, due FROM cards ORDER BY due")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(cards.len(), 3);
+        assert!(cards.iter().all(|(_, did, _)| *did == deck_id));
+        assert_eq!(
+            cards.iter().map(|(_, _, due)| *due).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn empty_decks_write_nothing() {
+        let dir = scratch_dir("anki", "empty");
+        let talk = session(&dir);
+        let apkg = dir.join("deck.apkg");
+        let cards = [card("Question?", " ", None)];
+        assert!(write_apkg(&talk, &cards, &apkg).is_err());
+        assert!(!apkg.exists());
+    }
+
+    #[test]
+    fn guids_are_stable_per_session_and_question() {
+        let one = session(Path::new("/nowhere"));
+        let mut other = one.clone();
+        other.id = "def".to_string();
+        assert_eq!(guid(&one, "Q"), guid(&one, "Q"));
+        assert_ne!(guid(&one, "Q"), guid(&one, "R"));
+        assert_ne!(guid(&one, "Q"), guid(&other, "Q"));
+        assert!(stable_id("OpenAutoNote") > 0);
+    }
+}
@@ -0,0 +1,602 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{info, warn};
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::markdown::{
+    asset_path, chapter_level, encode_link, escape_html, link_timestamps, rewrite_images,
+    split_at_headings, split_parts, timestamp_url, to_html, Heading, Part,
+};
+use super::{add_to_zip, file_stem, find_session, read_report, resolve_destination, web_url};
+use crate::history::Session;
+use crate::timestamp;
+
+const STYLE: &str = "body { font-family: serif; line-height: 1.6; }
+h1, h2, h3 { font-family: sans-serif; line-height: 1.3; }
+img { max-width: 100%; height: auto; display: block; margin: 1em auto; }
+pre { white-space: pre-wrap; font-size: 0.85em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 0.2em 0.5em; }
+blockquote { margin-left: 1em; padding-left: 1em; border-left: 3px solid #ccc; }
+";
+
+struct Chapter {
+    file: String,
+    title: String,
+    headings: Vec<Heading>,
+}
+
+struct Image {
+    file: String,
+    data: Vec<u8>,
+}
+
+/// A book being assembled: chapters are rendered as they are added and
+/// written out, with their frames, by `finish`.
+struct Book {
+    title: String,
+    language: String,
+    // One entry per session: its title and chapters
+    sessions: Vec<(String, Vec<Chapter>)>,
+    documents: Vec<(String, String)>,
+    images: Vec<Image>,
+    copied: HashMap<PathBuf, String>,
+}
+
+fn media_type(file: &str) -> &'static str {
+    match Path::new(file).extension().and_then(|e| e.to_str()) {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+fn xhtml(title: &str, language: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         lang=\"{1}\" xml:lang=\"{1}\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{0}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />\n</head>\n<body>\n{2}</body>\n</html>\n",
+        escape_html(title),
+        escape_html(language),
+        body
+    )
+}
+
+/// Chapters of a report: the parts of a chunked summary, otherwise its
+/// top-level sections, otherwise the whole report.
+fn chapters(report: &str, title: &str) -> Vec<Part> {
+    let parts = split_parts(report);
+    let mut chapters = if parts.len() > 1 {
+        parts
+    } else {
+        match chapter_level(report) {
+            Some(level) => split_at_headings(report, level),
+            None => Vec::new(),
+        }
+    };
+    if chapters.is_empty() {
+        chapters.push(Part {
+            title: None,
+            body: report.trim().to_string(),
+        });
+    }
+    // Text before the first chapter is an overview, or the whole book
+    let untitled = if chapters.len() == 1 {
+        title
+    } else {
+        "Overview"
+    };
+    for chapter in &mut chapters {
+        chapter.title.get_or_insert_with(|| untitled.to_string());
+    }
+    chapters
+}
+
+impl Book {
+    fn new(title: String, language: String) -> Self {
+        Self {
+            title,
+            language,
+            sessions: Vec::new(),
+            documents: Vec::new(),
+            images: Vec::new(),
+            copied: HashMap::new(),
+        }
+    }
+
+    fn add_session(&mut self, session: &Session) {
+        let index = self.sessions.len() + 1;
+        let project_dir = Path::new(&session.project_dir);
+        let report = read_report(session);
+
+        let mut added = Vec::new();
+        for part in chapters(&report, &session.title) {
+            let title = part.title.unwrap_or_default();
+            let body = self.package_frames(&part.body, project_dir, index);
+            let body = link_timestamps(&body, |seconds| timestamp_url(&session.video_url, seconds));
+            let rendered = to_html(&body);
+            let file = format!("s{}-c{}.xhtml", index, added.len() + 1);
+            let html = format!(
+                "<section epub:type=\"chapter\">\n<h1>{}</h1>\n{}</section>\n",
+                escape_html(&title),
+                rendered.html
+            );
+            self.documents
+                .push((file.clone(), xhtml(&title, &self.language, &html)));
+            added.push(Chapter {
+                file,
+                title,
+                headings: rendered.headings,
+            });
+        }
+        self.sessions.push((session.title.clone(), added));
+    }
+
+    /// Point frame links at copies inside the book.
+    fn package_frames(&mut self, text: &str, project_dir: &Path, session: usize) -> String {
+        rewrite_images(text, |_, target| {
+            let source = asset_path(project_dir, target)?;
+            if let Some(file) = self.copied.get(&source) {
+                return Some(encode_link(file));
+            }
+            // Read now so a frame that can't be packaged keeps its original
+            // link instead of pointing at a file missing from the book
+            let data = match fs::read(&source) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", source.display(), e);
+                    return None;
+                }
+            };
+            // Every session has its own frame_N.jpg files
+            let name = source.file_name()?.to_string_lossy().into_owned();
+            let file = format!("images/s{}/{}", session, name);
+            self.images.push(Image {
+                file: file.clone(),
+                data,
+            });
+            self.copied.insert(source, file.clone());
+            Some(encode_link(&file))
+        })
+    }
+
+    fn nav(&self) -> String {
+        let mut out = String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+        let single = self.sessions.len() == 1;
+        for (title, chapters) in &self.sessions {
+            if !single {
+                let first = chapters.first().map_or("", |c| c.file.as_str());
+                let _ = write!(
+                    out,
+                    "<li><a href=\"{}\">{}</a>\n<ol>\n",
+                    first,
+                    escape_html(title)
+                );
+            }
+            for chapter in chapters {
+                let _ = write!(
+                    out,
+                    "<li><a href=\"{}\">{}</a>",
+                    chapter.file,
+                    escape_html(&chapter.title)
+                );
+                out.push_str(&heading_list(chapter));
+                out.push_str("</li>\n");
+            }
+            if !single {
+                out.push_str("</ol>\n</li>\n");
+            }
+        }
+        out.push_str("</ol>\n</nav>\n");
+        xhtml("Contents", &self.language, &out)
+    }
+
+    fn package(&self, id: &str, modified: &str, sources: &[String]) -> String {
+        let mut manifest = String::from(
+            "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n\
+             <item id=\"style\" href=\"style.css\" media-type=\"text/css\" />\n",
+        );
+        let mut spine = String::from("<itemref idref=\"nav\" />\n");
+        for (n, (file, _)) in self.documents.iter().enumerate() {
+            let _ = writeln!(
+                manifest,
+                "<item id=\"doc{}\" href=\"{}\" media-type=\"application/xhtml+xml\" />",
+                n + 1,
+                file
+            );
+            let _ = writeln!(spine, "<itemref idref=\"doc{}\" />", n + 1);
+        }
+        for (n, image) in self.images.iter().enumerate() {
+            let _ = writeln!(
+                manifest,
+                "<item id=\"img{}\" href=\"{}\" media-type=\"{}\" />",
+                n + 1,
+                escape_html(&encode_link(&image.file)),
+                media_type(&image.file)
+            );
+        }
+        let sources: String = sources
+            .iter()
+            .map(|s| format!("<dc:source>{}</dc:source>\n", escape_html(s)))
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+             <dc:title>{}</dc:title>\n\
+             <dc:language>{}</dc:language>\n\
+             <dc:creator>OpenAutoNote</dc:creator>\n\
+             {}<meta property=\"dcterms:modified\">{}</meta>\n\
+             </metadata>\n<manifest>\n{}</manifest>\n<spine>\n{}</spine>\n</package>\n",
+            escape_html(id),
+            escape_html(&self.title),
+            escape_html(&self.language),
+            sources,
+            modified,
+            manifest,
+            spine
+        )
+    }
+
+    fn finish(self, path: &Path, id: &str, sources: &[String]) -> Result<(), String> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let result = self.write_zip(&tmp, id, sources);
+        match result {
+            Ok(()) => fs::rename(&tmp, path).map_err(|e| e.to_string()),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+
+    fn write_zip(&self, path: &Path, id: &str, sources: &[String]) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut zip = ZipWriter::new(file);
+        let deflated = SimpleFileOptions::default();
+        // Readers identify the file by an uncompressed mimetype entry first
+        add_to_zip(
+            &mut zip,
+            "mimetype",
+            b"application/epub+zip",
+            SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        add_to_zip(
+            &mut zip,
+            "META-INF/container.xml",
+            b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+              <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+              <rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\" />\n\
+              </rootfiles>\n</container>\n",
+            deflated,
+        )?;
+        let modified = timestamp::iso8601(SystemTime::now());
+        add_to_zip(
+            &mut zip,
+            "OEBPS/content.opf",
+            self.package(id, &modified, sources).as_bytes(),
+            deflated,
+        )?;
+        add_to_zip(&mut zip, "OEBPS/nav.xhtml", self.nav().as_bytes(), deflated)?;
+        add_to_zip(&mut zip, "OEBPS/style.css", STYLE.as_bytes(), deflated)?;
+        for (file, html) in &self.documents {
+            add_to_zip(
+                &mut zip,
+                &format!("OEBPS/{}", file),
+                html.as_bytes(),
+                deflated,
+            )?;
+        }
+        // JPEGs don't shrink any further
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for image in &self.images {
+            add_to_zip(
+                &mut zip,
+                &format!("OEBPS/{}", image.file),
+                &image.data,
+                stored,
+            )?;
+        }
+        zip.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// A chapter's section headings, two levels deep, like the TOC the
+// summarizer writes
+fn heading_list(chapter: &Chapter) -> String {
+    let Some(top) = chapter.headings.iter().map(|h| h.level).min() else {
+        return String::new();
+    };
+    let mut out = String::from("\n<ol>\n");
+    let mut nested = false;
+    for (n, heading) in chapter
+        .headings
+        .iter()
+        .filter(|h| h.level <= top + 1)
+        .enumerate()
+    {
+        let link = format!(
+            "<a href=\"{}#{}\">{}</a>",
+            chapter.file,
+            escape_html(&heading.id),
+            heading.text
+        );
+        if heading.level > top && n > 0 {
+            if !nested {
+                // Nested under the previous entry, whose <li> is still open
+                out.push_str("\n<ol>\n");
+                nested = true;
+            }
+            let _ = writeln!(out, "<li>{}</li>", link);
+            continue;
+        }
+        if nested {
+            out.push_str("</ol>\n");
+            nested = false;
+        }
+        if n > 0 {
+            out.push_str("</li>\n");
+        }
+        out.push_str("<li>");
+        out.push_str(&link);
+    }
+    if nested {
+        out.push_str("</ol>\n");
+    }
+    out.push_str("</li>\n</ol>\n");
+    out
+}
+
+// Names the `language` setting is given in, by their BCP 47 tag. More
+// specific names come first
+const LANGUAGE_TAGS: [(&str, &str); 14] = [
+    ("traditional chinese", "zh-Hant"),
+    ("繁體", "zh-Hant"),
+    ("繁体", "zh-Hant"),
+    ("chinese", "zh-Hans"),
+    ("中文", "zh-Hans"),
+    ("english", "en"),
+    ("英文", "en"),
+    ("japanese", "ja"),
+    ("日本語", "ja"),
+    ("korean", "ko"),
+    ("french", "fr"),
+    ("german", "de"),
+    ("spanish", "es"),
+    ("russian", "ru"),
+];
+
+/// `dc:language` for the `language` setting, which is a name such as
+/// "Simplified Chinese (Default)" or already a tag such as "en". `und`
+/// (undetermined) if it is neither.
+fn language_tag(setting: &str) -> String {
+    let setting = setting.trim();
+    let is_tag = setting.split('-').enumerate().all(|(n, part)| {
+        let letters = part.chars().all(|c| c.is_ascii_alphanumeric());
+        letters
+            && if n == 0 {
+                (2..=3).contains(&part.len())
+            } else {
+                (2..=8).contains(&part.len())
+            }
+    });
+    if is_tag {
+        return setting.to_string();
+    }
+    let lower = setting.to_lowercase();
+    LANGUAGE_TAGS
+        .iter()
+        .find(|(name, _)| lower.contains(name))
+        .map_or("und", |(_, tag)| tag)
+        .to_string()
+}
+
+// The language the notes were written in, not the app's UI language
+fn language(sessions: &[Session]) -> String {
+    sessions
+        .iter()
+        .find_map(|s| s.config_snapshot.get("language")?.as_str())
+        .map_or_else(|| "und".to_string(), language_tag)
+}
+
+/// Write `sessions` as one EPUB 3 book, each session's chapters in order.
+pub fn write_epub(sessions: &[Session], title: &str, path: &Path) -> Result<(), String> {
+    let mut book = Book::new(title.to_string(), language(sessions));
+    for session in sessions {
+        book.add_session(session);
+    }
+    let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+    let id = format!("urn:openautonote:{}", ids.join("+"));
+    let sources: Vec<String> = sessions
+        .iter()
+        .filter_map(|s| web_url(&s.video_url))
+        .map(str::to_string)
+        .collect();
+    let images = book.images.len();
+    book.finish(path, &id, &sources)?;
+    info!(
+        "Exported {} sessions with {} frames to {}",
+        sessions.len(),
+        images,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Export one or more sessions, such as a term's lectures, as an EPUB.
+/// `title` defaults to the first session's. Returns where it was saved, or
+/// `None` if the user cancelled the save dialog.
+#[tauri::command]
+pub async fn export_epub(
+    app: AppHandle,
+    session_ids: Vec<String>,
+    title: Option<String>,
+    destination: Option<String>,
+) -> Result<Option<String>, String> {
+    if session_ids.is_empty() {
+        return Err("No sessions to export".to_string());
+    }
+    let sessions = session_ids
+        .iter()
+        .map(|id| find_session(&app, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    let title = title
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| sessions[0].title.clone());
+
+    let file_name = format!("{}.epub", file_stem(&title));
+    let Some(mut path) =
+        resolve_destination(&app, destination, file_name, "EPUB book", &["epub"]).await?
+    else {
+        return Ok(None);
+    };
+    if path.extension().is_none() {
+        path.set_extension("epub");
+    }
+    let out = path.clone();
+    tauri::async_runtime::spawn_blocking(move || write_epub(&sessions, &title, &out))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::REPORT_FILE;
+    use crate::test_util::scratch_dir;
+    use serde_json::{json, Value};
+
+    fn session(id: &str, project_dir: &Path, report: &str, config: Value) -> Session {
+        fs::create_dir_all(project_dir.join("assets")).unwrap();
+        fs::write(project_dir.join(REPORT_FILE), report).unwrap();
+        serde_json::from_value(json!({
+            "id": id,
+            "timestamp": "20240501_093000",
+            "title": id,
+            "video_url": "https://example.com/watch?v=1",
+            "project_dir": project_dir,
+            "config_snapshot": config,
+        }))
+        .unwrap()
+    }
+
+    fn heading(level: usize, id: &str) -> Heading {
+        Heading {
+            level,
+            id: id.to_string(),
+            text: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn heading_list_nests_one_level() {
+        let chapter = Chapter {
+            file: "c.xhtml".to_string(),
+            title: "Chapter".to_string(),
+            headings: vec![
+                heading(2, "a"),
+                heading(3, "a1"),
+                heading(4, "too-deep"),
+                heading(3, "a2"),
+                heading(2, "b"),
+            ],
+        };
+        assert_eq!(
+            heading_list(&chapter),
+            "\n<ol>\n<li><a href=\"c.xhtml#a\">a</a>\n<ol>\n\
+             <li><a href=\"c.xhtml#a1\">a1</a></li>\n\
+             <li><a href=\"c.xhtml#a2\">a2</a></li>\n\
+             </ol>\n</li>\n<li><a href=\"c.xhtml#b\">b</a></li>\n</ol>\n"
+        );
+        let empty = Chapter {
+            headings: Vec::new(),
+            ..chapter
+        };
+        assert_eq!(heading_list(&empty), "");
+    }
+
+    #[test]
+    fn language_comes_from_the_content_setting() {
+        assert_eq!(language_tag("Simplified Chinese (Default)"), "zh-Hans");
+        assert_eq!(language_tag("Traditional Chinese"), "zh-Hant");
+        assert_eq!(language_tag("English"), "en");
+        assert_eq!(language_tag("pt-BR"), "pt-BR");
+        assert_eq!(language_tag("Klingon"), "und");
+
+        let dir = scratch_dir("epub", "language");
+        let config = json!({"language": "English", "ui_language": "zh"});
+        let english = session("a", &dir.join("a"), "Text", config);
+        assert_eq!(language(&[english]), "en");
+        let unset = session("b", &dir.join("b"), "Text", json!({"ui_language": "zh"}));
+        assert_eq!(language(&[unset]), "und");
+    }
+
+    #[test]
+    fn package_lists_chapters_in_order_with_their_frames() {
+        let dir = scratch_dir("epub", "package");
+        let project = dir.join("Talk");
+        let report = "Intro\n\n## One\n\n![frame](/generate/Talk/assets/1.jpg)\n\n\
+                      ### Detail\n\n## Two\n\nMore ![again](/generate/Talk/assets/1.jpg)\n";
+        let talk = session("talk", &project, report, json!({"language": "English"}));
+        fs::write(project.join("assets/1.jpg"), b"jpeg").unwrap();
+
+        let mut book = Book::new("Course".to_string(), language(std::slice::from_ref(&talk)));
+        book.add_session(&talk);
+        let files: Vec<&str> = book.documents.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(files, ["s1-c1.xhtml", "s1-c2.xhtml", "s1-c3.xhtml"]);
+        // The frame is packaged once, however often it is shown
+        assert_eq!(book.images.len(), 1);
+        assert_eq!(book.images[0].file, "images/s1/1.jpg");
+        assert!(book.documents[1].1.contains("<img src=\"images/s1/1.jpg\""));
+
+        let opf = book.package("urn:test", "2024-05-01T09:30:00Z", &[]);
+        assert!(opf.contains("<dc:language>en</dc:language>"));
+        assert!(opf.contains("<item id=\"doc2\" href=\"s1-c2.xhtml\""));
+        assert!(
+            opf.contains("<item id=\"img1\" href=\"images/s1/1.jpg\" media-type=\"image/jpeg\" />")
+        );
+        assert!(opf.contains(
+            "<spine>\n<itemref idref=\"nav\" />\n<itemref idref=\"doc1\" />\n\
+             <itemref idref=\"doc2\" />\n<itemref idref=\"doc3\" />\n</spine>"
+        ));
+
+        let nav = book.nav();
+        assert!(nav.contains("<li><a href=\"s1-c1.xhtml\">Overview</a></li>"));
+        assert!(nav.contains(
+            "<li><a href=\"s1-c2.xhtml\">One</a>\n<ol>\n\
+             <li><a href=\"s1-c2.xhtml#detail\">Detail</a></li>\n</ol>\n</li>"
+        ));
+    }
+
+    #[test]
+    fn books_of_several_sessions_group_their_chapters() {
+        let dir = scratch_dir("epub", "sessions");
+        let first = session("first", &dir.join("first"), "Only text", json!({}));
+        let second = session("second", &dir.join("second"), "More text", json!({}));
+
+        let mut book = Book::new("Course".to_string(), "en".to_string());
+        book.add_session(&first);
+        book.add_session(&second);
+        let nav = book.nav();
+        assert!(nav.contains(
+            "<li><a href=\"s1-c1.xhtml\">first</a>\n<ol>\n\
+             <li><a href=\"s1-c1.xhtml\">first</a></li>\n</ol>\n</li>"
+        ));
+        assert!(nav.contains("<li><a href=\"s2-c1.xhtml\">second</a>\n<ol>"));
+    }
+}
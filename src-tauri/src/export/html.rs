@@ -0,0 +1,440 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use base64::Engine;
+use log::{info, warn};
+use serde::Deserialize;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::webview::PageLoadEvent;
+use tauri::{AppHandle, Manager, UriSchemeContext, WebviewUrl, WebviewWindowBuilder, Wry};
+
+use super::markdown::{
+    asset_path, atx_heading, escape_html, link_timestamps, list_marker, rewrite_images,
+    split_parts, timestamp_url, to_html, Heading,
+};
+use super::{
+    file_stem, find_session, read_report, resolve_destination, session_date, web_url, write_atomic,
+    CONTENTS_FILE,
+};
+use crate::history::Session;
+
+/// Window that shows a report and opens the print dialog for PDF export.
+pub const PRINT_WINDOW: &str = "report-print";
+/// Serves the report to the print window, which can't load it from disk on
+/// every platform.
+pub const PRINT_SCHEME: &str = "oan-report";
+
+const STYLE: &str = r#"
+:root { color-scheme: light; }
+body { font: 16px/1.7 -apple-system, "Segoe UI", "PingFang SC", "Microsoft YaHei", "Noto Sans CJK SC", sans-serif;
+  color: #1c1b1f; max-width: 860px; margin: 0 auto; padding: 32px 24px 64px; }
+header { border-bottom: 1px solid #e0e0e0; margin-bottom: 24px; }
+header h1 { margin: 0 0 4px; font-size: 2em; line-height: 1.3; }
+.meta { color: #666; font-size: 0.9em; margin: 0 0 16px; word-break: break-all; }
+nav.toc { background: #f6f3fa; border-radius: 12px; padding: 4px 20px; margin-bottom: 32px; }
+nav.toc > h2 { font-size: 1.1em; }
+a { color: #6750a4; }
+img { max-width: 100%; height: auto; border-radius: 8px; display: block; margin: 12px 0; }
+pre { background: #f4f4f4; padding: 12px; border-radius: 8px; overflow-x: auto; }
+code { font-family: ui-monospace, Menlo, Consolas, monospace; font-size: 0.9em; }
+table { border-collapse: collapse; margin: 16px 0; }
+th, td { border: 1px solid #ddd; padding: 6px 10px; }
+blockquote { border-left: 4px solid #d0bcff; margin: 16px 0; padding: 0 16px; color: #444; }
+@media print {
+  body { max-width: none; padding: 0; }
+  nav.toc { background: none; padding: 0; break-after: page; }
+  img, pre, table, blockquote { break-inside: avoid; }
+  h1, h2, h3 { break-after: avoid; }
+}
+"#;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Html,
+    /// Printed from a webview; the print dialog's "Save as PDF" writes it.
+    Pdf,
+}
+
+/// The report most recently sent to the print window.
+#[derive(Default)]
+pub struct PrintPreview(Mutex<Option<String>>);
+
+fn mime_type(path: &Path) -> &'static str {
+    match crate::media::extension(path).as_deref() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+/// Replace links to the session's frames with data URIs.
+fn inline_images(text: &str, project_dir: &Path) -> String {
+    rewrite_images(text, |_, target| {
+        let path = asset_path(project_dir, target)?;
+        match fs::read(&path) {
+            Ok(data) => Some(format!(
+                "data:{};base64,{}",
+                mime_type(&path),
+                base64::engine::general_purpose::STANDARD.encode(data)
+            )),
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                None
+            }
+        }
+    })
+}
+
+// Letters only, so numbering and timestamps don't get in the way of
+// matching a contents entry to its heading
+fn match_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric() && !c.is_ascii_digit())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn find_heading<'a>(headings: &'a [Heading], text: &str) -> Option<&'a Heading> {
+    let key = match_key(text);
+    if key.chars().count() < 2 {
+        return None;
+    }
+    headings
+        .iter()
+        .find(|h| match_key(&h.text) == key)
+        .or_else(|| {
+            headings.iter().find(|h| {
+                let heading = match_key(&h.text);
+                heading.chars().count() >= 2 && (heading.contains(&key) || key.contains(&heading))
+            })
+        })
+}
+
+/// contents.md with each entry that matches a report heading turned into
+/// a link to it.
+fn link_contents(contents: &str, headings: &[Heading]) -> String {
+    contents
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let (prefix_len, text) = if let Some((_, text)) = atx_heading(trimmed) {
+                (
+                    line.len() - trimmed.len() + trimmed.find(text).unwrap_or(0),
+                    text,
+                )
+            } else if let Some(marker) = list_marker(line) {
+                (marker.content, line.get(marker.content..).unwrap_or(""))
+            } else {
+                return line.to_string();
+            };
+            let text = text.trim();
+            if text.contains("](") {
+                return line.to_string();
+            }
+            match find_heading(headings, text) {
+                Some(heading) => format!("{}[{}](#{})", &line[..prefix_len], text, heading.id),
+                None => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Table of contents from the report's own headings
+fn headings_toc(headings: &[Heading]) -> String {
+    let top = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let mut out = String::new();
+    let mut depth = 0;
+    for heading in headings.iter().filter(|h| h.level <= top + 2) {
+        let level = heading.level - top + 1;
+        while depth < level {
+            out.push_str("<ul>\n");
+            depth += 1;
+        }
+        while depth > level {
+            out.push_str("</ul>\n");
+            depth -= 1;
+        }
+        let _ = writeln!(
+            out,
+            "<li><a href=\"#{}\">{}</a></li>",
+            heading.id, heading.text
+        );
+    }
+    out.push_str(&"</ul>\n".repeat(depth));
+    out
+}
+
+/// Render a session as one HTML file with its frames inlined, so it opens
+/// anywhere without the app or a network connection.
+pub fn render_report(session: &Session) -> String {
+    let project_dir = Path::new(&session.project_dir);
+    let mut report = read_report(session);
+    let contents = fs::read_to_string(project_dir.join(CONTENTS_FILE))
+        .ok()
+        .filter(|c| !c.trim().is_empty());
+
+    // Long reports start with a copy of contents.md, which becomes the
+    // table of contents instead
+    if let Some(contents) = &contents {
+        if let Some(rest) = report.trim_start().strip_prefix(contents.trim()) {
+            let rest = rest.trim_start();
+            report = rest.strip_prefix("---").unwrap_or(rest).to_string();
+        }
+    }
+
+    // Chunked summaries separate their parts with text banners
+    let report = split_parts(&report)
+        .into_iter()
+        .map(|part| match part.title {
+            Some(title) => format!("# {}\n\n{}", title, part.body),
+            None => part.body,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let report = inline_images(&report, project_dir);
+    let report = link_timestamps(&report, |seconds| {
+        timestamp_url(&session.video_url, seconds)
+    });
+    let body = to_html(&report);
+    let toc = match &contents {
+        Some(contents) => to_html(&link_contents(contents, &body.headings)).html,
+        None if body.headings.len() > 1 => headings_toc(&body.headings),
+        None => String::new(),
+    };
+
+    let title = escape_html(&session.title);
+    let mut meta = Vec::new();
+    if let Some(url) = web_url(&session.video_url) {
+        let url = escape_html(url);
+        meta.push(format!("<a href=\"{0}\">{0}</a>", url));
+    }
+    if let Some(date) = session_date(&session.timestamp) {
+        meta.push(date);
+    }
+
+    let mut page = String::new();
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\" />\n\
+         <meta name=\"generator\" content=\"OpenAutoNote\" />\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n\
+         <header>\n<h1>{}</h1>\n<p class=\"meta\">{}</p>\n</header>\n",
+        title,
+        STYLE,
+        title,
+        meta.join(" · ")
+    );
+    if !toc.is_empty() {
+        let _ = write!(
+            page,
+            "<nav class=\"toc\">\n<h2>Contents</h2>\n{}</nav>\n",
+            toc
+        );
+    }
+    let _ = write!(page, "<main>\n{}</main>\n</body>\n</html>\n", body.html);
+    page
+}
+
+/// Show `html` in the print window and open the print dialog once it has
+/// loaded.
+fn print(app: &AppHandle, title: &str, html: String) -> Result<(), String> {
+    *app.state::<PrintPreview>().0.lock().unwrap() = Some(html);
+    if let Some(window) = app.get_webview_window(PRINT_WINDOW) {
+        let _ = window.destroy();
+    }
+    // Windows and Android reach custom schemes over http
+    let url = if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/", PRINT_SCHEME)
+    } else {
+        format!("{}://localhost/", PRINT_SCHEME)
+    };
+    WebviewWindowBuilder::new(
+        app,
+        PRINT_WINDOW,
+        WebviewUrl::External(url.parse().map_err(|e| format!("{}", e))?),
+    )
+    .title(title)
+    .inner_size(900.0, 800.0)
+    .on_page_load(|window, payload| {
+        if payload.event() == PageLoadEvent::Finished {
+            if let Err(e) = window.print() {
+                warn!("Failed to open the print dialog: {}", e);
+            }
+        }
+    })
+    .build()
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Protocol handler for [`PRINT_SCHEME`]. Only the print window gets the
+/// report.
+pub fn serve_print(
+    ctx: UriSchemeContext<'_, Wry>,
+    _request: Request<Vec<u8>>,
+) -> Response<Cow<'static, [u8]>> {
+    let html = if ctx.webview_label() == PRINT_WINDOW {
+        ctx.app_handle()
+            .state::<PrintPreview>()
+            .0
+            .lock()
+            .unwrap()
+            .clone()
+    } else {
+        None
+    };
+    match html {
+        Some(html) => Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Cow::Owned(html.into_bytes())),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Cow::Borrowed(&[][..])),
+    }
+    .unwrap_or_default()
+}
+
+/// Export a session's report as a self-contained HTML file, or open it for
+/// printing to PDF. Returns where the HTML was saved; `None` for PDF, which
+/// the print dialog saves, or if the user cancelled.
+#[tauri::command]
+pub async fn export_report(
+    app: AppHandle,
+    session_id: String,
+    format: ReportFormat,
+    destination: Option<String>,
+) -> Result<Option<String>, String> {
+    let session = find_session(&app, &session_id)?;
+    let title = session.title.clone();
+    let html = tauri::async_runtime::spawn_blocking(move || render_report(&session))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match format {
+        ReportFormat::Pdf => {
+            print(&app, &title, html)?;
+            Ok(None)
+        }
+        ReportFormat::Html => {
+            let file_name = format!("{}.html", file_stem(&title));
+            let Some(mut path) =
+                resolve_destination(&app, destination, file_name, "Web page", &["html"]).await?
+            else {
+                return Ok(None);
+            };
+            if path.extension().is_none() {
+                path.set_extension("html");
+            }
+            write_atomic(&path, html.as_bytes())?;
+            info!("Exported report to {}", path.display());
+            Ok(Some(path.to_string_lossy().into_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::REPORT_FILE;
+    use crate::test_util::scratch_dir;
+    use serde_json::json;
+
+    fn session(project_dir: &Path) -> Session {
+        serde_json::from_value(json!({
+            "id": "abc",
+            "timestamp": "20240501_093000",
+            "title": "Graphs",
+            "video_url": "https://example.com/talk",
+            "project_dir": project_dir,
+        }))
+        .unwrap()
+    }
+
+    fn heading(level: usize, id: &str, text: &str) -> Heading {
+        Heading {
+            level,
+            id: id.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn contents_entries_link_to_matching_headings() {
+        let headings = [
+            heading(1, "1-introduction-0000", "1. Introduction [00:00]"),
+            heading(2, "shortest-paths", "Shortest paths"),
+        ];
+        let contents = "# Contents\n\n1. Introduction\n   - shortest paths (12:30)\n\
+                        - Appendix\n- [Linked](#x)\n- 12";
+        assert_eq!(
+            link_contents(contents, &headings),
+            "# Contents\n\n1. [Introduction](#1-introduction-0000)\n\
+             \x20  - [shortest paths (12:30)](#shortest-paths)\n\
+             - Appendix\n- [Linked](#x)\n- 12"
+        );
+    }
+
+    #[test]
+    fn headings_toc_nests_three_levels() {
+        let headings = [
+            heading(2, "a", "A"),
+            heading(3, "b", "B"),
+            heading(5, "deep", "Deep"),
+            heading(4, "c", "C"),
+            heading(2, "d", "D"),
+        ];
+        assert_eq!(
+            headings_toc(&headings),
+            "<ul>\n<li><a href=\"#a\">A</a></li>\n<ul>\n<li><a href=\"#b\">B</a></li>\n\
+             <ul>\n<li><a href=\"#c\">C</a></li>\n</ul>\n</ul>\n\
+             <li><a href=\"#d\">D</a></li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn report_copy_of_contents_becomes_the_linked_toc() {
+        let dir = scratch_dir("html", "contents");
+        let contents = "- Basics\n- Search";
+        fs::write(dir.join(CONTENTS_FILE), contents).unwrap();
+        fs::write(
+            dir.join(REPORT_FILE),
+            format!(
+                "{}\n\n---\n\n## Basics\n\nText.\n\n## Search\n\nMore.",
+                contents
+            ),
+        )
+        .unwrap();
+        let page = render_report(&session(&dir));
+        let (nav, main) = page.split_once("<main>").unwrap();
+        assert!(nav.contains("<nav class=\"toc\">"));
+        assert!(nav.contains("<a href=\"#basics\">Basics</a>"));
+        assert!(nav.contains("<a href=\"#search\">Search</a>"));
+        // The copy at the top of the report is dropped
+        assert!(!main.contains("<li>"));
+        assert!(!main.contains("<hr"));
+        assert!(main.contains("<h2 id=\"basics\">Basics</h2>"));
+    }
+
+    #[test]
+    fn report_without_contents_lists_its_headings() {
+        let dir = scratch_dir("html", "headings");
+        fs::write(dir.join(REPORT_FILE), "# One\n\nText.\n\n## Two\n\nMore.").unwrap();
+        let page = render_report(&session(&dir));
+        assert!(page.contains(
+            "<nav class=\"toc\">\n<h2>Contents</h2>\n<ul>\n<li><a href=\"#one\">One</a></li>\n\
+             <ul>\n<li><a href=\"#two\">Two</a></li>\n</ul>\n</ul>\n</nav>"
+        ));
+
+        // A single heading needs no contents
+        fs::write(dir.join(REPORT_FILE), "# One\n\nText.").unwrap();
+        assert!(!render_report(&session(&dir)).contains("<nav"));
+    }
+}
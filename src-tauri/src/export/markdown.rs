@@ -0,0 +1,694 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{html, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use tauri::Url;
+
+/// A `[MM:SS]` or `[MM:SS-MM:SS]` marker at the start of `text`, as
+/// written by the summarizer; past the first hour it writes `H:MM:SS`.
+/// Returns the start in seconds and the marker's length in bytes.
+pub fn parse_timestamp_marker(text: &str) -> Option<(u32, usize)> {
+    let inner = text.strip_prefix('[')?;
+    let close = inner.find(']')?;
+    let body = &inner[..close];
+    let start = body.split('-').next()?;
+    let clock = |s: &str| -> Option<u32> {
+        let (rest, seconds) = s.rsplit_once(':')?;
+        let (hours, minutes) = rest.split_once(':').unwrap_or(("0", rest));
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let valid = digits(hours)
+            && hours.len() <= 2
+            && digits(minutes)
+            && minutes.len() <= 2
+            && digits(seconds)
+            && seconds.len() == 2;
+        if !valid {
+            return None;
+        }
+        Some(
+            hours.parse::<u32>().ok()? * 3600
+                + minutes.parse::<u32>().ok()? * 60
+                + seconds.parse::<u32>().ok()?,
+        )
+    };
+    let seconds = clock(start)?;
+    if let Some((_, end)) = body.split_once('-') {
+        clock(end)?;
+    }
+    Some((seconds, close + 2))
+}
+
+/// `MM:SS`, or `H:MM:SS` from the first hour on, as `parse_timestamp_marker`
+/// reads it.
+pub fn format_timestamp(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// A link that opens the source video `seconds` in: YouTube and Bilibili
+/// take a `t` parameter, other pages and local files a media fragment.
+pub fn timestamp_url(video_url: &str, seconds: u32) -> Option<String> {
+    let mut url = match Url::parse(video_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => {
+            let path = Path::new(video_url);
+            if !path.is_absolute() {
+                return None;
+            }
+            let mut url = Url::from_file_path(path).ok()?;
+            url.set_fragment(Some(&format!("t={}", seconds)));
+            return Some(url.into());
+        }
+    };
+    let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+    let t = if host.ends_with("youtube.com") || host == "youtu.be" {
+        Some(format!("{}s", seconds))
+    } else if host.ends_with("bilibili.com") {
+        Some(seconds.to_string())
+    } else {
+        None
+    };
+    match t {
+        Some(t) => {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(key, _)| key != "t")
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(pairs)
+                .append_pair("t", &t);
+        }
+        None => url.set_fragment(Some(&format!("t={}", seconds))),
+    }
+    Some(url.into())
+}
+
+/// Turn bare timestamp markers into links made by `link`. Markers that are
+/// image alt text or already links are left alone.
+pub fn link_timestamps(text: &str, mut link: impl FnMut(u32) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        out.push_str(&rest[..open]);
+        let tail = &rest[open..];
+        let is_image = out.ends_with('!');
+        match parse_timestamp_marker(tail) {
+            Some((seconds, len)) if !is_image && !tail[len..].starts_with('(') => {
+                let marker = &tail[..len];
+                match link(seconds) {
+                    Some(url) => {
+                        out.push_str(marker);
+                        out.push('(');
+                        out.push_str(&url);
+                        out.push(')');
+                    }
+                    None => out.push_str(marker),
+                }
+                rest = &tail[len..];
+            }
+            _ => {
+                out.push('[');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Rewrite the target of every `![alt](target)` image. `rewrite` gets the
+/// alt text and target and returns the new target, or `None` to keep it.
+pub fn rewrite_images(text: &str, mut rewrite: impl FnMut(&str, &str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("![") {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let parsed = tail[2..].find("](").and_then(|alt_end| {
+            let alt = &tail[2..2 + alt_end];
+            let target_start = 2 + alt_end + 2;
+            let target_len = tail[target_start..].find(')')?;
+            (!alt.contains('\n')).then_some((alt, target_start, target_len))
+        });
+        match parsed {
+            Some((alt, target_start, target_len)) => {
+                let target = &tail[target_start..target_start + target_len];
+                let new_target = rewrite(alt, target.trim_matches(|c| c == '<' || c == '>'));
+                out.push_str("![");
+                out.push_str(alt);
+                out.push_str("](");
+                out.push_str(new_target.as_deref().unwrap_or(target));
+                out.push(')');
+                rest = &tail[target_start + target_len + 1..];
+            }
+            None => {
+                out.push_str("![");
+                rest = &tail[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The file behind an image target in a session's notes. Reports link
+/// frames as `/generate/<folder>/assets/frame_N.jpg` (served by the
+/// sidecar) or by absolute path; either way the file is looked up in the
+/// session's own assets folder, which survives folder renames.
+pub fn asset_path(project_dir: &Path, target: &str) -> Option<PathBuf> {
+    let target = target.split(['?', '#']).next()?;
+    let (_, name) = target.rsplit_once("assets/")?;
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return None;
+    }
+    let path = project_dir.join("assets").join(name);
+    path.is_file().then_some(path)
+}
+
+/// Percent-encode a relative path for use as a Markdown link target.
+pub fn encode_link(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'/' | b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// A heading in rendered Markdown, for building a table of contents.
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: usize,
+    pub id: String,
+    /// Escaped text without tags.
+    pub text: String,
+}
+
+pub struct Rendered {
+    pub html: String,
+    pub headings: Vec<Heading>,
+}
+
+/// Render the Markdown the summarizer writes (CommonMark plus tables and
+/// strikethrough). Raw HTML is escaped rather than passed through, links
+/// and images with unsafe targets are reduced to their text, and headings
+/// get unique ids. The output is also well-formed XHTML.
+pub fn to_html(markdown: &str) -> Rendered {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut events = Vec::new();
+    let mut headings = Vec::new();
+    let mut ids = HashSet::new();
+    // Whether each open link or image was kept
+    let mut kept = Vec::new();
+    // The heading being read, held back until its id is known
+    let mut heading: Option<(HeadingLevel, Vec<Event>)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        let event = match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(Tag::Link { ref dest_url, .. })
+            | Event::Start(Tag::Image { ref dest_url, .. }) => {
+                let safe = is_safe_url(dest_url);
+                kept.push(safe);
+                if !safe {
+                    continue;
+                }
+                event
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                if !kept.pop().unwrap_or(true) {
+                    continue;
+                }
+                event
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some((level, Vec::new()));
+                continue;
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, inner)) = heading.take() {
+                    let mut html = String::new();
+                    html::push_html(&mut html, inner.iter().cloned());
+                    let text = strip_tags(&html);
+                    let base = slug(&text);
+                    let mut id = base.clone();
+                    let mut n = 1;
+                    while !ids.insert(id.clone()) {
+                        n += 1;
+                        id = format!("{}-{}", base, n);
+                    }
+                    events.push(Event::Start(Tag::Heading {
+                        level,
+                        id: Some(id.clone().into()),
+                        classes: Vec::new(),
+                        attrs: Vec::new(),
+                    }));
+                    events.extend(inner);
+                    events.push(Event::End(TagEnd::Heading(level)));
+                    headings.push(Heading {
+                        level: level as usize,
+                        id,
+                        text,
+                    });
+                }
+                continue;
+            }
+            event => event,
+        };
+        match &mut heading {
+            Some((_, inner)) => inner.push(event),
+            None => events.push(event),
+        }
+    }
+    let mut html = String::new();
+    html::push_html(&mut html, events.into_iter());
+    Rendered { html, headings }
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Drop tags from rendered inline HTML.
+pub fn strip_tags(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+pub struct ListMarker {
+    /// Where the item's text starts.
+    pub content: usize,
+}
+
+pub fn list_marker(line: &str) -> Option<ListMarker> {
+    let indent = indent_of(line);
+    let rest = &line[indent..];
+    let marker_len = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || digits > 9 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+    let after = &rest[marker_len..];
+    if !after.is_empty() && !after.starts_with(' ') {
+        return None;
+    }
+    let spaces = indent_of(after);
+    // Five or more spaces would be an indented code block; treat as one
+    let spaces = if (1..=4).contains(&spaces) { spaces } else { 1 };
+    Some(ListMarker {
+        content: indent + marker_len + spaces.min(after.len()),
+    })
+}
+
+pub fn atx_heading(trimmed: &str) -> Option<(usize, &str)> {
+    let level = trimmed.bytes().take_while(|&b| b == b'#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+fn fence(trimmed: &str) -> Option<&str> {
+    ["```", "~~~"].into_iter().find(|f| trimmed.starts_with(f))
+}
+
+/// An anchor id for a heading. Keeps letters and digits in any script.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-') && !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// Links may only point at web pages, mail, files, in-page anchors, relative
+// paths and inlined images
+fn is_safe_url(url: &str) -> bool {
+    let lower = url.trim().to_ascii_lowercase();
+    let scheme = lower
+        .split_once(':')
+        .map(|(s, _)| s)
+        .filter(|s| !s.contains(['/', '?', '#']));
+    match scheme {
+        None => true,
+        Some("http" | "https" | "mailto" | "file") => true,
+        Some("data") => lower.starts_with("data:image/"),
+        Some(_) => false,
+    }
+}
+
+/// A titled section of a report.
+pub struct Part {
+    pub title: Option<String>,
+    pub body: String,
+}
+
+fn is_part_rule(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 10 && line.bytes().all(|b| b == b'=')
+}
+
+/// Split a report at the banners chunked summaries put between parts:
+/// a title between two lines of `=`. Text before the first banner is an
+/// untitled part.
+pub fn split_parts(report: &str) -> Vec<Part> {
+    let lines: Vec<&str> = report.lines().collect();
+    let mut parts = Vec::new();
+    let mut title = None;
+    let mut body: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let banner = i + 2 < lines.len()
+            && is_part_rule(lines[i])
+            && !lines[i + 1].trim().is_empty()
+            && is_part_rule(lines[i + 2]);
+        if banner {
+            push_part(&mut parts, title.take(), &body);
+            body.clear();
+            title = Some(lines[i + 1].trim().to_string());
+            i += 3;
+        } else {
+            body.push(lines[i]);
+            i += 1;
+        }
+    }
+    push_part(&mut parts, title, &body);
+    parts
+}
+
+fn push_part(parts: &mut Vec<Part>, title: Option<String>, body: &[&str]) {
+    let body = body.join("\n").trim().to_string();
+    if title.is_some() || !body.is_empty() {
+        parts.push(Part { title, body });
+    }
+}
+
+// (line index, level, text) of each heading outside code blocks
+fn heading_lines(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut headings = Vec::new();
+    let mut open_fence: Option<&str> = None;
+    for (n, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        match open_fence {
+            Some(marker) if trimmed.starts_with(marker) => open_fence = None,
+            Some(_) => {}
+            None => {
+                if let Some(marker) = fence(trimmed) {
+                    open_fence = Some(marker);
+                } else if let Some((level, heading)) = atx_heading(trimmed) {
+                    headings.push((n, level, heading));
+                }
+            }
+        }
+    }
+    headings
+}
+
+/// Split `text` before every heading of `level`, ignoring code blocks.
+pub fn split_at_headings(text: &str, level: usize) -> Vec<Part> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut parts = Vec::new();
+    let mut title = None;
+    let mut start = 0;
+    for (n, found, heading) in heading_lines(text) {
+        if found == level {
+            push_part(&mut parts, title.take(), &lines[start..n]);
+            title = Some(heading.to_string());
+            start = n + 1;
+        }
+    }
+    push_part(&mut parts, title, &lines[start..]);
+    parts
+}
+
+/// The highest heading level used more than once, if any: the level a
+/// report's chapters are written at.
+pub fn chapter_level(text: &str) -> Option<usize> {
+    let mut counts = [0usize; 7];
+    for (_, level, _) in heading_lines(text) {
+        counts[level] += 1;
+    }
+    (1..=6).find(|&level| counts[level] > 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_blocks() {
+        let html = to_html("# Title\n\nSome *text*.\n\n- one\n- two\n\n1. a\n2. b\n\n> quote\n\n```rust\nlet x = 1 < 2;\n```\n\n---\n").html;
+        assert_eq!(
+            html,
+            "<h1 id=\"title\">Title</h1>\n\
+             <p>Some <em>text</em>.</p>\n\
+             <ul>\n<li>one</li>\n<li>two</li>\n</ul>\n\
+             <ol>\n<li>a</li>\n<li>b</li>\n</ol>\n\
+             <blockquote>\n<p>quote</p>\n</blockquote>\n\
+             <pre><code class=\"language-rust\">let x = 1 &lt; 2;\n</code></pre>\n\
+             <hr />\n"
+        );
+    }
+
+    #[test]
+    fn renders_emphasis_and_code_spans() {
+        assert_eq!(
+            to_html("**bold** _it_ ***both*** ~~gone~~ `a*b*` snake_case_name").html,
+            "<p><strong>bold</strong> <em>it</em> <em><strong>both</strong></em> \
+             <del>gone</del> <code>a*b*</code> snake_case_name</p>\n"
+        );
+    }
+
+    #[test]
+    fn renders_tables_with_alignment() {
+        let html = to_html("| a | b | c |\n|:--|:-:|--:|\n| 1 | 2 | 3 |\n").html;
+        assert!(html.contains("<th style=\"text-align: left\">a</th>"));
+        assert!(html.contains("<th style=\"text-align: center\">b</th>"));
+        assert!(html.contains("<td style=\"text-align: right\">3</td>"));
+    }
+
+    #[test]
+    fn renders_nested_and_loose_lists() {
+        let html = to_html("- one\n  - inner\n- two\n\n- three\n").html;
+        assert!(html.contains("<ul>\n<li>inner</li>\n</ul>"));
+        assert!(html.contains("<li>\n<p>three</p>\n</li>"));
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        let html = to_html("<script>alert(1)</script>\n\nhi <b>there</b>").html;
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("&lt;b&gt;there&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn output_is_xhtml() {
+        let html = to_html("line  \nbreak\n\n![a](frame.jpg)\n\n***").html;
+        assert!(html.contains("<br />"));
+        assert!(html.contains("<img src=\"frame.jpg\" alt=\"a\" />"));
+        assert!(html.contains("<hr />"));
+    }
+
+    #[test]
+    fn keeps_safe_links_only() {
+        let html = to_html(
+            "[web](https://example.com/?a=1&b=2) [mail](mailto:a@b.c) [anchor](#x) \
+             [rel](notes/a.md) [js](javascript:alert(1)) [data](data:text/html,x) \
+             ![img](data:image/png;base64,AAAA) ![bad](vbscript:x) <https://auto.link>",
+        )
+        .html;
+        assert!(html.contains("<a href=\"https://example.com/?a=1&amp;b=2\">web</a>"));
+        assert!(html.contains("<a href=\"mailto:a@b.c\">mail</a>"));
+        assert!(html.contains("<a href=\"#x\">anchor</a>"));
+        assert!(html.contains("<a href=\"notes/a.md\">rel</a>"));
+        assert!(html.contains("<img src=\"data:image/png;base64,AAAA\" alt=\"img\" />"));
+        assert!(html.contains("<a href=\"https://auto.link\">https://auto.link</a>"));
+        assert!(!html.contains("javascript"));
+        assert!(!html.contains("data:text"));
+        assert!(!html.contains("vbscript"));
+        assert!(html.contains(" js "));
+        assert!(html.contains(" bad "));
+    }
+
+    #[test]
+    fn safe_urls() {
+        for url in [
+            "https://a.b",
+            "HTTP://a.b",
+            "mailto:x@y",
+            "file:///tmp/a.mp4",
+            "#t=5",
+            "assets/frame_1.jpg",
+            "a/b:c",
+            "data:image/jpeg;base64,xx",
+        ] {
+            assert!(is_safe_url(url), "{}", url);
+        }
+        for url in [
+            "javascript:x",
+            " JavaScript:x",
+            "data:text/html,x",
+            "vbscript:x",
+        ] {
+            assert!(!is_safe_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn heading_ids_are_unique_and_listed() {
+        let rendered = to_html("## Intro\n\n## Intro\n\n### *Détails* & more\n\n## 总结\n");
+        let ids: Vec<&str> = rendered.headings.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, ["intro", "intro-2", "détails-amp-more", "总结"]);
+        assert_eq!(rendered.headings[2].level, 3);
+        assert_eq!(rendered.headings[2].text, "Détails &amp; more");
+        assert!(rendered
+            .html
+            .contains("<h3 id=\"détails-amp-more\"><em>Détails</em> &amp; more</h3>"));
+    }
+
+    #[test]
+    fn parses_timestamp_markers() {
+        assert_eq!(parse_timestamp_marker("[01:05] text"), Some((65, 7)));
+        assert_eq!(parse_timestamp_marker("[1:05-2:00]"), Some((65, 11)));
+        assert_eq!(parse_timestamp_marker("[1:02:03]"), Some((3723, 9)));
+        assert_eq!(
+            parse_timestamp_marker("[01:02:03-1:05:00]"),
+            Some((3723, 18))
+        );
+        assert_eq!(parse_timestamp_marker("[123:05]"), None);
+        assert_eq!(parse_timestamp_marker("[1:2:03]"), Some((3723, 8)));
+        assert_eq!(parse_timestamp_marker("[1:02:3]"), None);
+        assert_eq!(parse_timestamp_marker("[1:02:03:04]"), None);
+        assert_eq!(parse_timestamp_marker("[01:5]"), None);
+        assert_eq!(parse_timestamp_marker("[01:05-x]"), None);
+        assert_eq!(parse_timestamp_marker("01:05"), None);
+    }
+
+    #[test]
+    fn formats_timestamps_as_they_are_parsed() {
+        assert_eq!(format_timestamp(65), "01:05");
+        assert_eq!(format_timestamp(3599), "59:59");
+        assert_eq!(format_timestamp(3723), "1:02:03");
+        for seconds in [0, 59, 600, 3600, 36_000 + 61] {
+            let marker = format!("[{}]", format_timestamp(seconds));
+            assert_eq!(
+                parse_timestamp_marker(&marker).map(|(s, _)| s),
+                Some(seconds)
+            );
+        }
+    }
+
+    #[test]
+    fn links_bare_timestamps_only() {
+        let text = "[00:10] a ![00:20](f.jpg) [00:30](x) [note] [00:40-00:50]";
+        let linked = link_timestamps(text, |s| Some(format!("v#t={}", s)));
+        assert_eq!(
+            linked,
+            "[00:10](v#t=10) a ![00:20](f.jpg) [00:30](x) [note] [00:40-00:50](v#t=40)"
+        );
+        assert_eq!(link_timestamps(text, |_| None), text);
+    }
+
+    #[test]
+    fn builds_timestamp_urls() {
+        assert_eq!(
+            timestamp_url("https://www.youtube.com/watch?v=abc&t=9", 65).as_deref(),
+            Some("https://www.youtube.com/watch?v=abc&t=65s")
+        );
+        assert_eq!(
+            timestamp_url("https://www.bilibili.com/video/BV1xx411c7mD", 65).as_deref(),
+            Some("https://www.bilibili.com/video/BV1xx411c7mD?t=65")
+        );
+        assert_eq!(
+            timestamp_url("https://example.com/v.mp4", 65).as_deref(),
+            Some("https://example.com/v.mp4#t=65")
+        );
+        assert_eq!(timestamp_url("relative/v.mp4", 65), None);
+    }
+
+    #[test]
+    fn rewrites_image_targets() {
+        let text = "a ![x](<one.jpg>) b ![y](two.jpg) ![broken\n](z)";
+        let out = rewrite_images(text, |alt, target| {
+            (alt == "x").then(|| format!("new/{}", target))
+        });
+        assert_eq!(out, "a ![x](new/one.jpg) b ![y](two.jpg) ![broken\n](z)");
+    }
+
+    #[test]
+    fn recognises_list_markers_and_headings() {
+        assert_eq!(list_marker("- item").map(|m| m.content), Some(2));
+        assert_eq!(list_marker("  12. item").map(|m| m.content), Some(6));
+        assert!(list_marker("-item").is_none());
+        assert!(list_marker("1234567890. item").is_none());
+        assert_eq!(atx_heading("## Title ##"), Some((2, "Title")));
+        assert_eq!(atx_heading("#Title"), None);
+        assert_eq!(atx_heading("####### deep"), None);
+    }
+
+    #[test]
+    fn splits_reports_into_parts() {
+        let report = "intro\n==========\nPart 1\n==========\nbody one\n==========\nPart 2\n==========\nbody two";
+        let parts = split_parts(report);
+        let titles: Vec<Option<&str>> = parts.iter().map(|p| p.title.as_deref()).collect();
+        assert_eq!(titles, [None, Some("Part 1"), Some("Part 2")]);
+        assert_eq!(parts[2].body, "body two");
+
+        let text = "# A\n\n## one\nx\n```\n## not a heading\n```\n## two\ny";
+        assert_eq!(chapter_level(text), Some(2));
+        let chapters = split_at_headings(text, 2);
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[1].title.as_deref(), Some("one"));
+        assert!(chapters[1].body.contains("## not a heading"));
+    }
+}
@@ -0,0 +1,166 @@
+pub mod anki;
+pub mod epub;
+pub mod html;
+pub mod markdown;
+pub mod subtitles;
+pub mod vault;
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::history::{HistoryStore, Session};
+
+// Files `finalize_task` writes in each session folder
+pub const REPORT_FILE: &str = "report.md";
+pub const ABSTRACT_FILE: &str = "abstract.md";
+pub const CONTENTS_FILE: &str = "contents.md";
+/// Timestamped segments, `[{start, end, text}]`.
+pub const TRANSCRIPT_FILE: &str = "transcript.json";
+
+/// One transcription segment, in seconds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// `None` when the file is missing or unreadable; sessions from before
+/// transcript.json only have the joined text.
+pub fn read_segments(path: &Path) -> Option<Vec<Segment>> {
+    let data = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(segments) => Some(segments),
+        Err(e) => {
+            warn!("Ignoring invalid {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn find_session(app: &AppHandle, id: &str) -> Result<Session, String> {
+    app.state::<HistoryStore>()
+        .get(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No session with id {}", id))
+}
+
+/// The session's report.md. Sessions still processing have none yet, but
+/// their summary is the same text.
+pub fn read_report(session: &Session) -> String {
+    fs::read_to_string(Path::new(&session.project_dir).join(REPORT_FILE))
+        .unwrap_or_else(|_| session.summary.clone())
+}
+
+/// `url` if it is a web page rather than the path of a local video.
+pub fn web_url(url: &str) -> Option<&str> {
+    Some(url).filter(|u| u.starts_with("http://") || u.starts_with("https://"))
+}
+
+/// Add a file holding `data` to `zip`.
+pub fn add_to_zip(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    data: &[u8],
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    zip.start_file(name, options).map_err(|e| e.to_string())?;
+    zip.write_all(data).map_err(|e| e.to_string())
+}
+
+/// `20240501_093000` as saved by the sidecar becomes `2024-05-01`.
+pub fn session_date(timestamp: &str) -> Option<String> {
+    let digits = timestamp.get(..8)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!(
+        "{}-{}-{}",
+        &digits[..4],
+        &digits[4..6],
+        &digits[6..]
+    ))
+}
+
+/// A file name for `title` that is safe on every platform.
+pub fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let stem = stem.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if stem.is_empty() {
+        "OpenAutoNote".to_string()
+    } else {
+        stem.chars().take(200).collect()
+    }
+}
+
+/// Ask where to save with the native dialog, starting in Downloads.
+/// `None` if the user cancelled.
+pub fn pick_save_path(
+    app: &AppHandle,
+    file_name: &str,
+    filter: &str,
+    extensions: &[&str],
+) -> Option<PathBuf> {
+    let mut dialog = app
+        .dialog()
+        .file()
+        .set_file_name(file_name)
+        .add_filter(filter, extensions);
+    if let Ok(dir) = app.path().download_dir() {
+        dialog = dialog.set_directory(dir);
+    }
+    dialog.blocking_save_file()?.into_path().ok()
+}
+
+/// Ask for a folder with the native dialog. `None` if the user cancelled.
+pub fn pick_folder(app: &AppHandle) -> Option<PathBuf> {
+    app.dialog().file().blocking_pick_folder()?.into_path().ok()
+}
+
+/// Where an export goes: `destination` if given, otherwise the save
+/// dialog's choice.
+pub async fn resolve_destination(
+    app: &AppHandle,
+    destination: Option<String>,
+    file_name: String,
+    filter: &'static str,
+    extensions: &'static [&'static str],
+) -> Result<Option<PathBuf>, String> {
+    if let Some(destination) = destination {
+        return Ok(Some(PathBuf::from(destination)));
+    }
+    // The dialog blocks until the user answers
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        pick_save_path(&app, &file_name, filter, extensions)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Write via a temporary file so a failed export never leaves half a file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, data).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
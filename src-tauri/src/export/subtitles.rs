@@ -0,0 +1,563 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use log::info;
+use serde::Deserialize;
+use tauri::AppHandle;
+
+use super::{
+    file_stem, find_session, read_segments, resolve_destination, write_atomic, Segment,
+    TRANSCRIPT_FILE,
+};
+
+// Short cues are only merged across pauses up to this long
+const MERGE_GAP: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            SubtitleFormat::Srt => &["srt"],
+            SubtitleFormat::Vtt => &["vtt"],
+            SubtitleFormat::Ass => &["ass"],
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        self.extensions()[0]
+    }
+
+    fn filter_name(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "SubRip subtitles",
+            SubtitleFormat::Vtt => "WebVTT subtitles",
+            SubtitleFormat::Ass => "Advanced SubStation Alpha subtitles",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SubtitleOptions {
+    /// Line width in columns; CJK characters count as two.
+    pub max_line_chars: usize,
+    pub max_lines: usize,
+    /// Longest a cue stays on screen, in seconds.
+    pub max_duration: f64,
+    /// Cues shorter than this are merged into the next one when they fit.
+    pub min_duration: f64,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            max_line_chars: 42,
+            max_lines: 2,
+            max_duration: 7.0,
+            min_duration: 1.0,
+        }
+    }
+}
+
+impl SubtitleOptions {
+    fn validate(&self) -> Result<(), String> {
+        if self.max_line_chars < 10 {
+            return Err("max_line_chars must be at least 10".to_string());
+        }
+        if self.max_lines == 0 {
+            return Err("max_lines must be at least 1".to_string());
+        }
+        if !(self.max_duration.is_finite() && self.max_duration >= 1.0) {
+            return Err("max_duration must be at least 1 second".to_string());
+        }
+        if !(self.min_duration.is_finite() && self.min_duration >= 0.0) {
+            return Err("min_duration can't be negative".to_string());
+        }
+        Ok(())
+    }
+
+    fn max_cue_width(&self) -> usize {
+        self.max_line_chars * self.max_lines
+    }
+}
+
+/// One subtitle on screen, already wrapped.
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub lines: Vec<String>,
+}
+
+// CJK, Hangul and fullwidth forms take two columns
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115f}'
+        | '\u{2e80}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{20000}'..='\u{3fffd}')
+}
+
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if is_wide(c) { 2 } else { 1 }).sum()
+}
+
+/// Join two pieces of text, with a space unless either side is CJK.
+fn join(a: &str, b: &str) -> String {
+    let spaced = !(a.chars().last().is_some_and(is_wide) || b.chars().next().is_some_and(is_wide));
+    if a.is_empty() {
+        b.to_string()
+    } else if spaced {
+        format!("{} {}", a, b)
+    } else {
+        format!("{}{}", a, b)
+    }
+}
+
+/// Greedy word wrap. Lines may break between any two CJK characters, and
+/// words wider than a line are broken wherever they overflow.
+pub fn wrap(text: &str, max_width: usize) -> Vec<String> {
+    // (token, preceded by a space)
+    let mut tokens: Vec<(String, bool)> = Vec::new();
+    let mut word = String::new();
+    let mut word_spaced = false;
+    let mut pending_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), word_spaced));
+            }
+            pending_space = true;
+        } else if is_wide(c) {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), word_spaced));
+            }
+            tokens.push((c.to_string(), pending_space));
+            pending_space = false;
+        } else {
+            if word.is_empty() {
+                word_spaced = pending_space;
+                pending_space = false;
+            }
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push((word, word_spaced));
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for (token, spaced) in tokens {
+        let token_width = width(&token);
+        let gap = usize::from(spaced && !line.is_empty());
+        if !line.is_empty() && line_width + gap + token_width > max_width {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        } else if gap == 1 {
+            line.push(' ');
+            line_width += 1;
+        }
+        for c in token.chars() {
+            let w = if is_wide(c) { 2 } else { 1 };
+            if line_width + w > max_width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += w;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Turn transcript segments into cues: short segments are merged, long
+/// ones are wrapped and split across several cues, and no cue runs past
+/// `max_duration` or into the next one.
+pub fn build_cues(segments: &[Segment], options: &SubtitleOptions) -> Vec<Cue> {
+    let mut merged: Vec<(f64, f64, String)> = Vec::new();
+    for segment in segments {
+        let text = segment
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.is_empty() || !segment.start.is_finite() || !segment.end.is_finite() {
+            continue;
+        }
+        let start = segment.start.max(0.0);
+        let end = segment.end.max(start);
+        if let Some((prev_start, prev_end, prev_text)) = merged.last_mut() {
+            let joined = join(prev_text, &text);
+            let fits = *prev_end - *prev_start < options.min_duration
+                && start - *prev_end <= MERGE_GAP
+                && end - *prev_start <= options.max_duration
+                && width(&joined) <= options.max_cue_width();
+            if fits {
+                *prev_end = end;
+                *prev_text = joined;
+                continue;
+            }
+        }
+        merged.push((start, end, text));
+    }
+
+    let mut cues = Vec::new();
+    for (start, end, text) in merged {
+        let lines = wrap(&text, options.max_line_chars);
+        // A segment longer than max_duration gets at least that many cues,
+        // down to one line each, so its text stays up for the whole segment
+        let needed = ((end - start) / options.max_duration).ceil().max(1.0) as usize;
+        let per_cue = (lines.len() / needed).clamp(1, options.max_lines);
+        // Share the segment's time between its cues by amount of text
+        let total: usize = lines.iter().map(|l| width(l)).sum::<usize>().max(1);
+        let mut at = start;
+        for chunk in lines.chunks(per_cue) {
+            let share: usize = chunk.iter().map(|l| width(l)).sum();
+            let span = (end - start) * share as f64 / total as f64;
+            cues.push(Cue {
+                start: at,
+                end: at + span.min(options.max_duration),
+                lines: chunk.to_vec(),
+            });
+            at += span;
+        }
+    }
+
+    // Whisper segments sometimes overlap slightly
+    for i in 1..cues.len() {
+        let next_start = cues[i].start;
+        let cue = &mut cues[i - 1];
+        if cue.end > next_start && next_start > cue.start {
+            cue.end = next_start;
+        }
+    }
+    cues
+}
+
+fn millis(seconds: f64) -> u64 {
+    (seconds.max(0.0) * 1000.0).round() as u64
+}
+
+fn timestamp(seconds: f64, separator: char) -> String {
+    let ms = millis(seconds);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+// ASS uses one-digit hours and centiseconds
+fn ass_timestamp(seconds: f64) -> String {
+    let cs = (millis(seconds) + 5) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+pub fn to_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let _ = write!(
+            out,
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(cue.start, ','),
+            timestamp(cue.end, ','),
+            cue.lines.join("\n")
+        );
+    }
+    out
+}
+
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let text = cue
+            .lines
+            .iter()
+            .map(|line| {
+                line.replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let _ = write!(
+            out,
+            "{} --> {}\n{}\n\n",
+            timestamp(cue.start, '.'),
+            timestamp(cue.end, '.'),
+            text
+        );
+    }
+    out
+}
+
+pub fn to_ass(cues: &[Cue], title: &str) -> String {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    let mut out = format!(
+        "[Script Info]\n\
+         Title: {}\n\
+         ScriptType: v4.00+\n\
+         WrapStyle: 2\n\
+         ScaledBorderAndShadow: yes\n\
+         PlayResX: 1920\n\
+         PlayResY: 1080\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+         Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, \
+         Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Default,Arial,64,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,3,1,2,60,60,50,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        title
+    );
+    for cue in cues {
+        // Braces open override blocks and backslashes start tags
+        let text = cue
+            .lines
+            .iter()
+            .map(|line| {
+                line.replace('\\', "\u{ff3c}")
+                    .replace('{', "(")
+                    .replace('}', ")")
+            })
+            .collect::<Vec<_>>()
+            .join("\\N");
+        let _ = writeln!(
+            out,
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+            ass_timestamp(cue.start),
+            ass_timestamp(cue.end),
+            text
+        );
+    }
+    out
+}
+
+/// Write a session's transcript as subtitles. Without `destination` the
+/// user picks the file; returns where it was saved, or `None` if they
+/// cancelled.
+#[tauri::command]
+pub async fn export_subtitles(
+    app: AppHandle,
+    session_id: String,
+    format: SubtitleFormat,
+    options: Option<SubtitleOptions>,
+    destination: Option<String>,
+) -> Result<Option<String>, String> {
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    let session = find_session(&app, &session_id)?;
+    let segments = read_segments(&Path::new(&session.project_dir).join(TRANSCRIPT_FILE))
+        .ok_or("This session has no timestamped transcript")?;
+    let cues = build_cues(&segments, &options);
+    if cues.is_empty() {
+        return Err("The transcript is empty".to_string());
+    }
+    let contents = match format {
+        SubtitleFormat::Srt => to_srt(&cues),
+        SubtitleFormat::Vtt => to_vtt(&cues),
+        SubtitleFormat::Ass => to_ass(&cues, &session.title),
+    };
+
+    let file_name = format!("{}.{}", file_stem(&session.title), format.extension());
+    let Some(mut path) = resolve_destination(
+        &app,
+        destination,
+        file_name,
+        format.filter_name(),
+        format.extensions(),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    if path.extension().is_none() {
+        path.set_extension(format.extension());
+    }
+    write_atomic(&path, contents.as_bytes())?;
+    info!("Exported {} cues to {}", cues.len(), path.display());
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64, text: &str) -> Segment {
+        Segment {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn wraps_words_at_the_line_width() {
+        assert_eq!(
+            wrap("the quick brown fox jumps over the lazy dog", 15),
+            ["the quick brown", "fox jumps over", "the lazy dog"]
+        );
+        assert_eq!(wrap("  spaced   out  ", 20), ["spaced out"]);
+        assert!(wrap("", 10).is_empty());
+    }
+
+    #[test]
+    fn breaks_words_wider_than_a_line() {
+        assert_eq!(
+            wrap("a supercalifragilistic b", 10),
+            ["a", "supercalif", "ragilistic", "b"]
+        );
+    }
+
+    #[test]
+    fn wraps_cjk_text_without_spaces() {
+        // Each character is two columns wide
+        let lines = wrap("我们今天来讨论一下机器学习的基本概念", 10);
+        assert_eq!(lines, ["我们今天来", "讨论一下机", "器学习的基", "本概念"]);
+        assert!(lines.iter().all(|l| width(l) <= 10));
+    }
+
+    #[test]
+    fn mixes_cjk_and_latin() {
+        assert_eq!(wrap("使用 Rust 编写", 40), ["使用 Rust 编写"]);
+        assert_eq!(join("使用", "Rust"), "使用Rust");
+        assert_eq!(join("hello", "world"), "hello world");
+        assert_eq!(join("", "world"), "world");
+    }
+
+    #[test]
+    fn merges_short_segments() {
+        let options = SubtitleOptions::default();
+        let cues = build_cues(
+            &[
+                segment(0.0, 0.5, "Hi."),
+                segment(0.6, 2.0, "Welcome back."),
+                segment(5.0, 7.0, "Today: subtitles."),
+            ],
+            &options,
+        );
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, ["Hi. Welcome back."]);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 2.0));
+        assert_eq!(cues[1].start, 5.0);
+    }
+
+    #[test]
+    fn splits_long_text_into_several_cues() {
+        let options = SubtitleOptions {
+            max_line_chars: 10,
+            max_lines: 2,
+            ..SubtitleOptions::default()
+        };
+        let text = "one two three four five six seven eight nine ten";
+        let cues = build_cues(&[segment(0.0, 6.0, text)], &options);
+        assert!(cues.len() > 1);
+        assert!(cues.iter().all(|c| c.lines.len() <= 2));
+        assert!(cues.iter().flat_map(|c| &c.lines).all(|l| width(l) <= 10));
+        let words: Vec<String> = cues.iter().flat_map(|c| c.lines.clone()).collect();
+        assert_eq!(words.join(" "), text);
+        assert_eq!(cues[0].start, 0.0);
+        assert!((cues.last().unwrap().end - 6.0).abs() < 1e-9);
+        for pair in cues.windows(2) {
+            assert!((pair[0].end - pair[1].start).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn spreads_long_segments_over_several_cues() {
+        let options = SubtitleOptions {
+            max_line_chars: 20,
+            max_lines: 2,
+            max_duration: 5.0,
+            ..SubtitleOptions::default()
+        };
+        // Fits in one cue by width, but lasts three cues' worth of time
+        let text = "alpha beta gamma delta epsilon zeta eta theta";
+        let cues = build_cues(&[segment(10.0, 25.0, text)], &options);
+        assert!(cues.len() >= 3, "{:?}", cues);
+        assert!(cues.iter().all(|c| c.end - c.start <= 5.0 + 1e-9));
+        assert_eq!(cues[0].start, 10.0);
+        assert!((cues.last().unwrap().end - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn clips_a_single_line_that_outlasts_max_duration() {
+        let options = SubtitleOptions {
+            max_duration: 5.0,
+            ..SubtitleOptions::default()
+        };
+        let cues = build_cues(&[segment(0.0, 30.0, "[Music]")], &options);
+        assert_eq!(cues.len(), 1);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 5.0));
+    }
+
+    #[test]
+    fn splits_long_cjk_segments() {
+        let options = SubtitleOptions {
+            max_line_chars: 10,
+            max_lines: 1,
+            ..SubtitleOptions::default()
+        };
+        let cues = build_cues(&[segment(0.0, 4.0, "我们今天来讨论一下机器学习")], &options);
+        let lines: Vec<&str> = cues.iter().map(|c| c.lines[0].as_str()).collect();
+        assert_eq!(lines, ["我们今天来", "讨论一下机", "器学习"]);
+    }
+
+    #[test]
+    fn trims_overlapping_segments() {
+        let options = SubtitleOptions::default();
+        let cues = build_cues(
+            &[
+                segment(0.0, 3.5, "First sentence here."),
+                segment(3.0, 6.0, "Second sentence here."),
+            ],
+            &options,
+        );
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].end, 3.0);
+        assert_eq!(cues[1].start, 3.0);
+    }
+
+    #[test]
+    fn skips_empty_and_invalid_segments() {
+        let options = SubtitleOptions::default();
+        let cues = build_cues(
+            &[
+                segment(0.0, 1.0, "   "),
+                segment(f64::NAN, 2.0, "nan"),
+                segment(-1.0, 2.0, "starts before zero"),
+            ],
+            &options,
+        );
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].start, 0.0);
+    }
+}
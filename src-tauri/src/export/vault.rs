@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::Deserialize;
+use tauri::AppHandle;
+
+use super::markdown::{
+    asset_path, encode_link, format_timestamp, link_timestamps, rewrite_images, timestamp_url,
+};
+use super::{
+    file_stem, find_session, pick_folder, read_report, read_segments, session_date, write_atomic,
+    ABSTRACT_FILE, TRANSCRIPT_FILE,
+};
+use crate::history::Session;
+
+const DEFAULT_TAG: &str = "openautonote";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultFlavor {
+    /// `<folder>/<title>.md` with frames in `<folder>/assets/<title>/`.
+    #[default]
+    Obsidian,
+    /// `pages/<title>.md` with frames in the graph's `assets/<title>/`.
+    Logseq,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VaultOptions {
+    pub flavor: VaultFlavor,
+    /// Folder inside an Obsidian vault; empty for the vault root.
+    pub folder: String,
+    /// Added to the front-matter after `openautonote`.
+    pub tags: Vec<String>,
+    pub include_transcript: bool,
+}
+
+impl Default for VaultOptions {
+    fn default() -> Self {
+        Self {
+            flavor: VaultFlavor::default(),
+            folder: "OpenAutoNote".to_string(),
+            tags: Vec::new(),
+            include_transcript: false,
+        }
+    }
+}
+
+// Where the note and its frames go, and how the note links to the frames
+struct Layout {
+    note: PathBuf,
+    assets: PathBuf,
+    assets_link: String,
+}
+
+impl Layout {
+    fn new(vault: &Path, stem: &str, options: &VaultOptions) -> Result<Self, String> {
+        match options.flavor {
+            VaultFlavor::Obsidian => {
+                let folder = options.folder.trim_matches(['/', '\\']);
+                if Path::new(folder)
+                    .components()
+                    .any(|c| !matches!(c, std::path::Component::Normal(_)))
+                {
+                    return Err(format!("Invalid vault folder: {}", options.folder));
+                }
+                let dir = vault.join(folder);
+                Ok(Self {
+                    note: dir.join(format!("{}.md", stem)),
+                    assets: dir.join("assets").join(stem),
+                    assets_link: format!("assets/{}/", stem),
+                })
+            }
+            VaultFlavor::Logseq => Ok(Self {
+                note: vault.join("pages").join(format!("{}.md", stem)),
+                assets: vault.join("assets").join(stem),
+                assets_link: format!("../assets/{}/", stem),
+            }),
+        }
+    }
+}
+
+// The `session` a note's front matter names, if it is one of ours
+fn note_session(note: &Path) -> Option<String> {
+    let text = fs::read_to_string(note).ok()?;
+    let front_matter = text.strip_prefix("---\n")?.split("\n---\n").next()?;
+    front_matter
+        .lines()
+        .find_map(|line| line.strip_prefix("session: "))
+        .and_then(|id| serde_json::from_str(id).ok())
+}
+
+/// Lay the note out under the session's title, unless a note by that name
+/// belongs to another session: then the session id goes in the name too,
+/// so sessions with the same title don't overwrite each other's notes and
+/// frames. Exporting a session again updates its own note.
+fn pick_layout(vault: &Path, session: &Session, options: &VaultOptions) -> Result<Layout, String> {
+    let title = file_stem(&session.title);
+    let short_id: String = session.id.chars().take(8).collect();
+    let stems = [
+        title.clone(),
+        format!("{} ({})", title, short_id),
+        format!("{} ({})", title, session.id),
+    ];
+    for stem in &stems {
+        let layout = Layout::new(vault, stem, options)?;
+        let free = !layout.note.exists()
+            || note_session(&layout.note).as_deref() == Some(session.id.as_str());
+        if free {
+            return Ok(layout);
+        }
+    }
+    Err(format!(
+        "{} already has notes named {}",
+        vault.display(),
+        title
+    ))
+}
+
+// JSON strings are valid YAML double-quoted scalars
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+fn front_matter(session: &Session, options: &VaultOptions) -> String {
+    let mut out = String::from("---\n");
+    let _ = writeln!(out, "title: {}", yaml_string(&session.title));
+    if !session.video_url.is_empty() {
+        let _ = writeln!(out, "source: {}", yaml_string(&session.video_url));
+    }
+    if let Some(date) = session_date(&session.timestamp) {
+        let _ = writeln!(out, "date: {}", date);
+    }
+    if let Some(model) = session
+        .config_snapshot
+        .get("model_name")
+        .and_then(|m| m.as_str())
+        .filter(|m| !m.is_empty())
+    {
+        let _ = writeln!(out, "model: {}", yaml_string(model));
+    }
+    out.push_str("tags:\n");
+    let mut tags = vec![DEFAULT_TAG.to_string()];
+    for tag in &options.tags {
+        // Tags can't contain spaces in either app
+        let tag = tag
+            .trim()
+            .trim_start_matches('#')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    for tag in tags {
+        let _ = writeln!(out, "  - {}", yaml_string(&tag));
+    }
+    let _ = writeln!(out, "session: {}", yaml_string(&session.id));
+    out.push_str("---\n\n");
+    out
+}
+
+/// Copy the frames a note links to and point the links at the copies.
+fn copy_frames(
+    text: &str,
+    project_dir: &Path,
+    layout: &Layout,
+    copied: &mut HashMap<PathBuf, String>,
+) -> String {
+    rewrite_images(text, |_, target| {
+        let source = asset_path(project_dir, target)?;
+        if let Some(link) = copied.get(&source) {
+            return Some(link.clone());
+        }
+        let name = source.file_name()?.to_string_lossy().into_owned();
+        let copy = fs::create_dir_all(&layout.assets)
+            .and_then(|_| fs::copy(&source, layout.assets.join(&name)));
+        if let Err(e) = copy {
+            warn!("Failed to copy {}: {}", source.display(), e);
+            return None;
+        }
+        let link = encode_link(&format!("{}{}", layout.assets_link, name));
+        copied.insert(source, link.clone());
+        Some(link)
+    })
+}
+
+/// Write `session` into `vault` as a Markdown note with its frames.
+/// Returns the note's path.
+pub fn export_session(
+    session: &Session,
+    vault: &Path,
+    options: &VaultOptions,
+) -> Result<PathBuf, String> {
+    if !vault.is_dir() {
+        return Err(format!("{} is not a folder", vault.display()));
+    }
+    let project_dir = Path::new(&session.project_dir);
+    let layout = pick_layout(vault, session, options)?;
+    let mut copied = HashMap::new();
+    let video_url = session.video_url.as_str();
+    let process = |text: &str, copied: &mut HashMap<PathBuf, String>| {
+        let text = copy_frames(text, project_dir, &layout, copied);
+        link_timestamps(&text, |seconds| timestamp_url(video_url, seconds))
+    };
+
+    let mut note = front_matter(session, options);
+    let _ = write!(note, "# {}\n\n", session.title);
+    if let Ok(abstract_md) = fs::read_to_string(project_dir.join(ABSTRACT_FILE)) {
+        if !abstract_md.trim().is_empty() {
+            let _ = write!(
+                note,
+                "## Abstract\n\n{}\n\n",
+                process(abstract_md.trim(), &mut copied)
+            );
+        }
+    }
+    let report = read_report(session);
+    note.push_str(process(report.trim(), &mut copied).trim_end());
+    note.push('\n');
+
+    if options.include_transcript {
+        if let Some(segments) = read_segments(&project_dir.join(TRANSCRIPT_FILE)) {
+            note.push_str("\n## Transcript\n\n");
+            for segment in segments {
+                let seconds = segment.start.max(0.0) as u32;
+                let marker = format!("[{}]", format_timestamp(seconds));
+                let marker = match timestamp_url(video_url, seconds) {
+                    Some(url) => format!("{}({})", marker, url),
+                    None => marker,
+                };
+                let _ = writeln!(note, "- {} {}", marker, segment.text.trim());
+            }
+        }
+    }
+
+    write_atomic(&layout.note, note.as_bytes())?;
+    info!(
+        "Exported {} to {} with {} frames",
+        session.id,
+        layout.note.display(),
+        copied.len()
+    );
+    Ok(layout.note)
+}
+
+/// Copy a session into an Obsidian vault or Logseq graph. Without `vault`
+/// the user picks the folder; returns the note's path, or `None` if they
+/// cancelled.
+#[tauri::command]
+pub async fn export_to_vault(
+    app: AppHandle,
+    session_id: String,
+    vault: Option<String>,
+    options: Option<VaultOptions>,
+) -> Result<Option<String>, String> {
+    let session = find_session(&app, &session_id)?;
+    let options = options.unwrap_or_default();
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = match vault {
+            Some(vault) => PathBuf::from(vault),
+            None => match pick_folder(&handle) {
+                Some(vault) => vault,
+                None => return Ok(None),
+            },
+        };
+        export_session(&session, &vault, &options)
+            .map(|note| Some(note.to_string_lossy().into_owned()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::REPORT_FILE;
+    use crate::test_util::scratch_dir;
+    use serde_json::json;
+
+    fn session(id: &str, title: &str, project_dir: &Path) -> Session {
+        serde_json::from_value(json!({
+            "id": id,
+            "timestamp": "20240501_093000",
+            "title": title,
+            "video_url": "https://example.com/watch?v=1",
+            "project_dir": project_dir,
+            "config_snapshot": {"model_name": "gpt-4o"},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn front_matter_is_valid_yaml_with_normalized_tags() {
+        let talk = session("abc", "A \"quoted\": title", Path::new("/nowhere"));
+        let options = VaultOptions {
+            tags: vec![
+                "#lecture".to_string(),
+                "machine learning".to_string(),
+                "openautonote".to_string(),
+                "  ".to_string(),
+            ],
+            ..VaultOptions::default()
+        };
+        assert_eq!(
+            front_matter(&talk, &options),
+            "---\ntitle: \"A \\\"quoted\\\": title\"\n\
+             source: \"https://example.com/watch?v=1\"\ndate: 2024-05-01\n\
+             model: \"gpt-4o\"\ntags:\n  - \"openautonote\"\n  - \"lecture\"\n\
+             \x20 - \"machine-learning\"\nsession: \"abc\"\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn layout_keeps_notes_inside_the_vault() {
+        let vault = Path::new("/vault");
+        let obsidian = |folder: &str| VaultOptions {
+            folder: folder.to_string(),
+            ..VaultOptions::default()
+        };
+        let layout = Layout::new(vault, "Talk", &obsidian("Notes/Lectures/")).unwrap();
+        assert_eq!(layout.note, vault.join("Notes/Lectures/Talk.md"));
+        assert_eq!(layout.assets, vault.join("Notes/Lectures/assets/Talk"));
+        assert_eq!(layout.assets_link, "assets/Talk/");
+        let root = Layout::new(vault, "Talk", &obsidian("")).unwrap();
+        assert_eq!(root.note, vault.join("Talk.md"));
+        assert!(Layout::new(vault, "Talk", &obsidian("../outside")).is_err());
+        assert!(Layout::new(vault, "Talk", &obsidian("Notes/../../outside")).is_err());
+
+        let logseq = VaultOptions {
+            flavor: VaultFlavor::Logseq,
+            folder: "../ignored".to_string(),
+            ..VaultOptions::default()
+        };
+        let layout = Layout::new(vault, "Talk", &logseq).unwrap();
+        assert_eq!(layout.note, vault.join("pages/Talk.md"));
+        assert_eq!(layout.assets, vault.join("assets/Talk"));
+        assert_eq!(layout.assets_link, "../assets/Talk/");
+    }
+
+    #[test]
+    fn sessions_with_the_same_title_get_their_own_notes() {
+        let dir = scratch_dir("vault", "same-title");
+        let vault = dir.join("vault");
+        fs::create_dir_all(&vault).unwrap();
+        let mut paths = Vec::new();
+        for id in ["11111111-aaaa", "22222222-bbbb"] {
+            let project = dir.join(id);
+            fs::create_dir_all(project.join("assets")).unwrap();
+            fs::write(project.join("assets/1.jpg"), id).unwrap();
+            fs::write(
+                project.join(REPORT_FILE),
+                format!("Notes of {}\n\n![f](/generate/{}/assets/1.jpg)\n", id, id),
+            )
+            .unwrap();
+            let talk = session(id, "Week 1", &project);
+            paths.push(export_session(&talk, &vault, &VaultOptions::default()).unwrap());
+        }
+        let notes = vault.join("OpenAutoNote");
+        assert_eq!(paths[0], notes.join("Week 1.md"));
+        assert_eq!(paths[1], notes.join("Week 1 (22222222).md"));
+        let second = fs::read_to_string(&paths[1]).unwrap();
+        assert!(second.contains("Notes of 22222222-bbbb"));
+        assert!(second.contains("](assets/Week%201%20%2822222222%29/1.jpg)"));
+        let frame = notes.join("assets/Week 1/1.jpg");
+        assert_eq!(fs::read_to_string(frame).unwrap(), "11111111-aaaa");
+
+        // Exporting again replaces the session's own note
+        let again = session("11111111-aaaa", "Week 1", &dir.join("11111111-aaaa"));
+        let path = export_session(&again, &vault, &VaultOptions::default()).unwrap();
+        assert_eq!(path, paths[0]);
+    }
+}
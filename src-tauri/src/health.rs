@@ -0,0 +1,170 @@
+use std::fmt;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+// Endpoint served by the sidecar (see `api_health` in python-core/main.py)
+const HEALTH_PATH: &str = "/api/health";
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Output of `detect_hardware()` on the Python side.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareInfo {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    #[serde(default)]
+    pub valid_modes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendHealth {
+    pub status: String,
+    pub version: String,
+    pub asr_backend: String,
+    pub asr_model: String,
+    /// Whether the speech model is downloaded; it loads on first use.
+    pub model_installed: bool,
+    pub hardware: HardwareInfo,
+    /// The server's own PID; older sidecars don't report it.
+    #[serde(default)]
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum HealthError {
+    /// Nothing is listening yet, or the request timed out.
+    Unreachable(String),
+    /// The server answered with a non-success status.
+    BadStatus(u16),
+    /// The server answered, but not with a health report.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for HealthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthError::Unreachable(e) => write!(f, "backend unreachable: {}", e),
+            HealthError::BadStatus(code) => write!(f, "backend returned HTTP {}", code),
+            HealthError::InvalidResponse(e) => write!(f, "invalid health response: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl HealthConfig {
+    /// Defaults, overridden by `OAN_HEALTH_CONNECT_TIMEOUT_MS` and
+    /// `OAN_HEALTH_REQUEST_TIMEOUT_MS` when set.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(ms) = env_millis("OAN_HEALTH_CONNECT_TIMEOUT_MS") {
+            config.connect_timeout = ms;
+        }
+        if let Some(ms) = env_millis("OAN_HEALTH_REQUEST_TIMEOUT_MS") {
+            config.request_timeout = ms;
+        }
+        config
+    }
+}
+
+fn env_millis(key: &str) -> Option<Duration> {
+    std::env::var(key)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_millis)
+}
+
+/// Queries the sidecar's health endpoint and caches the last good report.
+pub struct HealthMonitor {
+    client: reqwest::Client,
+    url: String,
+    latest: RwLock<Option<BackendHealth>>,
+}
+
+impl HealthMonitor {
+    pub fn new(base_url: &str, bearer: &str, config: &HealthConfig) -> Self {
+        let mut headers = reqwest::header::HeaderMap::new();
+        let mut auth =
+            reqwest::header::HeaderValue::from_str(bearer).expect("bearer token is plain ASCII");
+        auth.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, auth);
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .build()
+            .expect("failed to build health-check HTTP client");
+        Self {
+            client,
+            url: format!("{}{}", base_url, HEALTH_PATH),
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Probe the backend now. The cached report is replaced on success and
+    /// cleared on failure, so `latest()` never reports a dead backend as up.
+    pub async fn check(&self) -> Result<BackendHealth, HealthError> {
+        let result = self.fetch().await;
+        *self.latest.write().unwrap() = result.as_ref().ok().cloned();
+        result
+    }
+
+    /// Last successful report, if the most recent probe succeeded.
+    pub fn latest(&self) -> Option<BackendHealth> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Forget the cached report, e.g. when the sidecar process exits.
+    pub fn invalidate(&self) {
+        *self.latest.write().unwrap() = None;
+    }
+
+    async fn fetch(&self) -> Result<BackendHealth, HealthError> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .map_err(|e| HealthError::Unreachable(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(HealthError::BadStatus(response.status().as_u16()));
+        }
+        response
+            .json::<BackendHealth>()
+            .await
+            .map_err(|e| HealthError::InvalidResponse(e.to_string()))
+    }
+}
+
+/// Returns the cached report unless `refresh` is set or nothing is cached.
+#[tauri::command]
+pub async fn get_backend_health(
+    monitor: State<'_, HealthMonitor>,
+    refresh: Option<bool>,
+) -> Result<BackendHealth, HealthError> {
+    if !refresh.unwrap_or(false) {
+        if let Some(health) = monitor.latest() {
+            return Ok(health);
+        }
+    }
+    monitor.check().await
+}
@@ -0,0 +1,749 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::State;
+
+use crate::export::{read_segments, REPORT_FILE, TRANSCRIPT_FILE};
+use crate::library::Library;
+use crate::timestamp;
+
+const HISTORY_DB_FILE: &str = "history.db";
+const LEGACY_HISTORY_FILE: &str = "user_history.json";
+
+// Bumped whenever the schema below changes; core/storage.py checks it too
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    title TEXT NOT NULL,
+    video_url TEXT NOT NULL DEFAULT '',
+    summary TEXT NOT NULL DEFAULT '',
+    transcript TEXT NOT NULL DEFAULT '',
+    project_dir TEXT NOT NULL DEFAULT '',
+    config_snapshot TEXT NOT NULL DEFAULT '{}',
+    extra TEXT NOT NULL DEFAULT '{}'
+);
+CREATE INDEX IF NOT EXISTS sessions_position ON sessions (position);
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// `status` of a session whose project folder has disappeared.
+pub const MISSING_STATUS: &str = "missing";
+
+// Set once user_history.json has been imported, so it only happens once
+const LEGACY_IMPORT_KEY: &str = "legacy_json_imported";
+
+/// One processed video, as stored by core/storage.py.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub timestamp: String,
+    pub title: String,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub video_url: String,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub summary: String,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub transcript: String,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub project_dir: String,
+    #[serde(default)]
+    pub config_snapshot: Value,
+    /// Fields the UI adds on the fly, such as `status` and `progress`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// The old JSON file has the odd `null` where a string is expected
+fn null_as_empty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl Session {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let config: String = row.get("config_snapshot")?;
+        let extra: String = row.get("extra")?;
+        Ok(Self {
+            id: row.get("id")?,
+            timestamp: row.get("timestamp")?,
+            title: row.get("title")?,
+            video_url: row.get("video_url")?,
+            summary: row.get("summary")?,
+            transcript: row.get("transcript")?,
+            project_dir: row.get("project_dir")?,
+            config_snapshot: serde_json::from_str(&config).unwrap_or_default(),
+            extra: serde_json::from_str(&extra).unwrap_or_default(),
+        })
+    }
+
+    /// A session for a finished project folder that the history doesn't
+    /// know, such as one restored from a backup. `None` without a report.md.
+    pub fn from_folder(dir: &Path) -> Option<Self> {
+        let report = dir.join(REPORT_FILE);
+        let summary = fs::read_to_string(&report).ok()?;
+        let created = fs::metadata(&report)
+            .and_then(|meta| meta.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        let transcript = read_segments(&dir.join(TRANSCRIPT_FILE))
+            .map(|segments| {
+                let texts: Vec<&str> = segments.iter().map(|s| s.text.trim()).collect();
+                texts.join(" ")
+            })
+            .unwrap_or_default();
+        Some(Self {
+            id: new_session_id(),
+            timestamp: timestamp::session_timestamp(created),
+            title: dir.file_name()?.to_string_lossy().into_owned(),
+            video_url: String::new(),
+            summary,
+            transcript,
+            project_dir: dir.to_string_lossy().into_owned(),
+            config_snapshot: Value::Object(Map::new()),
+            extra: Map::new(),
+        })
+    }
+
+    fn project_dir(&self) -> Option<&Path> {
+        Some(Path::new(&self.project_dir)).filter(|_| !self.project_dir.is_empty())
+    }
+
+    /// Point the session at `new_dir`, keeping report links to
+    /// `/generate/<folder>` pointing at the new folder.
+    pub fn move_to(&mut self, new_dir: &Path) {
+        let name = |p: &Path| {
+            p.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        self.summary = self.summary.replace(
+            &format!("/generate/{}", name(Path::new(&self.project_dir))),
+            &format!("/generate/{}", name(new_dir)),
+        );
+        self.project_dir = new_dir.to_string_lossy().into_owned();
+    }
+}
+
+// A random UUID v4, like the sidecar's; validate_and_cleanup_sessions()
+// drops sessions with any other kind of id
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random number generator unavailable");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Session history in an SQLite database shared with the sidecar, which
+/// finds it through `OAN_HISTORY_DB`.
+pub struct HistoryStore {
+    path: Mutex<PathBuf>,
+    conn: Mutex<Connection>,
+}
+
+fn connect(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    // Written by a newer version of the app, whose data this one could damage
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+            Some(format!(
+                "History database schema v{} is newer than supported v{}",
+                version, SCHEMA_VERSION
+            )),
+        ));
+    }
+    // WAL lets the sidecar read while we write, and vice versa
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    Ok(conn)
+}
+
+impl HistoryStore {
+    pub fn open(data_dir: &Path) -> rusqlite::Result<Self> {
+        let _ = fs::create_dir_all(data_dir);
+        let path = data_dir.join(HISTORY_DB_FILE);
+        Ok(Self {
+            conn: Mutex::new(connect(&path)?),
+            path: Mutex::new(path),
+        })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.lock().unwrap().clone()
+    }
+
+    /// Switch to the database in `data_dir`, e.g. after the library moved.
+    pub fn reopen(&self, data_dir: &Path) -> rusqlite::Result<()> {
+        let path = data_dir.join(HISTORY_DB_FILE);
+        let conn = connect(&path)?;
+        *self.conn.lock().unwrap() = conn;
+        *self.path.lock().unwrap() = path;
+        Ok(())
+    }
+
+    /// Write a consistent copy of the database into `data_dir`.
+    pub fn snapshot_to(&self, data_dir: &Path) -> rusqlite::Result<PathBuf> {
+        let path = data_dir.join(HISTORY_DB_FILE);
+        let conn = self.conn.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(path)
+    }
+
+    /// Point sessions whose folder is under `old_root` at the same folder
+    /// under `new_root`. Returns how many sessions changed.
+    pub fn rebase_projects(&self, old_root: &Path, new_root: &Path) -> rusqlite::Result<usize> {
+        let mut rebased = 0;
+        for session in self.list()? {
+            if let Ok(rest) = Path::new(&session.project_dir).strip_prefix(old_root) {
+                let conn = self.conn.lock().unwrap();
+                conn.execute(
+                    "UPDATE sessions SET project_dir = ?2 WHERE id = ?1",
+                    params![session.id, new_root.join(rest).to_string_lossy()],
+                )?;
+                rebased += 1;
+            }
+        }
+        Ok(rebased)
+    }
+
+    /// Newest first, matching the old JSON file's order.
+    pub fn list(&self) -> rusqlite::Result<Vec<Session>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT * FROM sessions ORDER BY position")?;
+        let sessions = stmt
+            .query_map([], Session::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    pub fn get(&self, id: &str) -> rusqlite::Result<Option<Session>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT * FROM sessions WHERE id = ?1",
+            [id],
+            Session::from_row,
+        )
+        .optional()
+    }
+
+    /// Whether a session was made from a video with this
+    /// `generate_video_hash` (stored by the sidecar as `video_hash`).
+    pub fn has_video_hash(&self, hash: &str) -> rusqlite::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sessions WHERE json_extract(extra, '$.video_hash') = ?1)",
+            [hash],
+            |row| row.get(0),
+        )
+    }
+
+    /// Insert `session` at the top of the list, replacing any session with
+    /// the same id.
+    pub fn add(&self, session: &Session) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let top: i64 = conn.query_row(
+            "SELECT COALESCE(MIN(position), 0) FROM sessions",
+            [],
+            |row| row.get(0),
+        )?;
+        insert(&conn, session, top - 1)
+    }
+
+    pub fn delete(&self, id: &str) -> rusqlite::Result<Option<Session>> {
+        let session = self.get(id)?;
+        if session.is_some() {
+            let conn = self.conn.lock().unwrap();
+            conn.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+        }
+        Ok(session)
+    }
+
+    fn update_title(
+        &self,
+        id: &str,
+        title: &str,
+        project_dir: &str,
+        summary: &str,
+    ) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE sessions SET title = ?2, project_dir = ?3, summary = ?4 WHERE id = ?1",
+            params![id, title, project_dir, summary],
+        )?;
+        Ok(())
+    }
+
+    /// Rename a session and its project folder. Returns false if there is
+    /// no such session.
+    pub fn rename(&self, id: &str, new_title: &str) -> Result<bool, String> {
+        let Some(mut session) = self.get(id).map_err(|e| e.to_string())? else {
+            return Ok(false);
+        };
+
+        if let Some(old_dir) = session.project_dir().filter(|d| d.exists()) {
+            let old_dir = old_dir.to_path_buf();
+            let parent = old_dir.parent().unwrap_or(Path::new(""));
+            let base = parent.join(sanitize_filename(new_title));
+            // Never overwrite another session's folder
+            let mut new_dir = base.clone();
+            let mut counter = 1;
+            while new_dir.exists() && new_dir != old_dir {
+                new_dir = PathBuf::from(format!("{}_{}", base.display(), counter));
+                counter += 1;
+            }
+            fs::rename(&old_dir, &new_dir).map_err(|e| format!("Error renaming folder: {}", e))?;
+
+            session.move_to(&new_dir);
+            info!(
+                "Renamed folder: {} -> {}",
+                old_dir.display(),
+                new_dir.display()
+            );
+        }
+
+        self.update_title(id, new_title, &session.project_dir, &session.summary)
+            .map_err(|e| e.to_string())?;
+        info!("Renamed session: {} -> {}", session.title, new_title);
+        Ok(true)
+    }
+
+    /// Save a session's `project_dir` and `summary` after `Session::move_to`.
+    pub fn update_location(&self, session: &Session) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE sessions SET project_dir = ?2, summary = ?3 WHERE id = ?1",
+            params![session.id, session.project_dir, session.summary],
+        )?;
+        Ok(())
+    }
+
+    /// Flag a session whose project folder is gone, or clear the flag. The
+    /// sidebar shows flagged sessions so they can be deleted by hand.
+    pub fn set_missing(&self, id: &str, missing: bool) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        if missing {
+            conn.execute(
+                "UPDATE sessions SET extra = json_set(extra, '$.status', ?2) WHERE id = ?1",
+                params![id, MISSING_STATUS],
+            )?;
+        } else {
+            conn.execute(
+                "UPDATE sessions SET extra = json_remove(extra, '$.status') WHERE id = ?1",
+                [id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Remove every session; returns what was removed.
+    pub fn clear(&self) -> rusqlite::Result<Vec<Session>> {
+        let sessions = self.list()?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM sessions", [])?;
+        Ok(sessions)
+    }
+
+    /// Import the sidecar's old `user_history.json` from `legacy_dir` the
+    /// first time this is called with a file present. The JSON file is
+    /// renamed afterwards, where the folder is writable, so nothing reads it
+    /// again. Returns the number of sessions imported.
+    pub fn import_legacy(&self, legacy_dir: &Path) -> Result<usize, String> {
+        let json_path = legacy_dir.join(LEGACY_HISTORY_FILE);
+        if !json_path.is_file() {
+            return Ok(0);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let done: Option<String> = conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                [LEGACY_IMPORT_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if done.is_some() {
+            return Ok(0);
+        }
+
+        let data: Value = fs::read_to_string(&json_path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", json_path.display(), e))?;
+        // storage.py writes {"sessions": [...]} but older versions wrote a bare list
+        let entries = match data {
+            Value::Object(mut map) => map.remove("sessions").unwrap_or(Value::Null),
+            other => other,
+        };
+        let entries = match entries {
+            Value::Array(entries) => entries,
+            _ => Vec::new(),
+        };
+
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let mut imported = 0;
+        for (position, entry) in entries.into_iter().enumerate() {
+            match serde_json::from_value::<Session>(entry) {
+                Ok(session) => {
+                    insert(&tx, &session, position as i64).map_err(|e| e.to_string())?;
+                    imported += 1;
+                }
+                Err(e) => warn!("Skipping malformed history entry: {}", e),
+            }
+        }
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+            params![LEGACY_IMPORT_KEY, json_path.to_string_lossy()],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        let mut done_path = json_path.clone().into_os_string();
+        done_path.push(".imported");
+        if let Err(e) = fs::rename(&json_path, &done_path) {
+            warn!("Failed to rename {}: {}", json_path.display(), e);
+        }
+        info!(
+            "Imported {} sessions from {}",
+            imported,
+            json_path.display()
+        );
+        Ok(imported)
+    }
+}
+
+fn insert(conn: &Connection, session: &Session, position: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sessions
+            (id, position, timestamp, title, video_url, summary, transcript,
+             project_dir, config_snapshot, extra)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            session.id,
+            position,
+            session.timestamp,
+            session.title,
+            session.video_url,
+            session.summary,
+            session.transcript,
+            session.project_dir,
+            session.config_snapshot.to_string(),
+            Value::Object(session.extra.clone()).to_string(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete a session's project folder, but only if it is a folder inside
+/// `generate_dir`: `project_dir` is whatever the database holds.
+fn remove_project_dir(session: &Session, generate_dir: &Path) {
+    let Some(dir) = session.project_dir().filter(|d| d.exists()) else {
+        return;
+    };
+    let inside = match (dir.canonicalize(), generate_dir.canonicalize()) {
+        (Ok(dir), Ok(generate_dir)) => dir != generate_dir && dir.starts_with(&generate_dir),
+        _ => false,
+    };
+    if !inside {
+        warn!(
+            "Not deleting {}, which is not a project folder in {}",
+            dir.display(),
+            generate_dir.display()
+        );
+        return;
+    }
+    match fs::remove_dir_all(dir) {
+        Ok(()) => info!("Deleted project folder: {}", dir.display()),
+        Err(e) => warn!("Error deleting folder {}: {}", dir.display(), e),
+    }
+}
+
+/// Same rules as `sanitize_filename` in core/utils.py, so renamed folders
+/// match the ones the sidecar creates.
+fn sanitize_filename(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c => c,
+        })
+        .collect();
+    let trimmed = replaced.trim_matches(|c| c == '.' || c == ' ');
+    let mut sanitized = String::with_capacity(trimmed.len());
+    for c in trimmed.chars() {
+        if !(c == '_' && sanitized.ends_with('_')) {
+            sanitized.push(c);
+        }
+    }
+    if sanitized.chars().count() > 200 {
+        sanitized = sanitized.chars().take(200).collect::<String>();
+        sanitized = sanitized.trim_end_matches('_').to_string();
+    }
+    if sanitized.is_empty() {
+        "untitled".to_string()
+    } else {
+        sanitized
+    }
+}
+
+#[tauri::command]
+pub fn list_sessions(store: State<'_, HistoryStore>) -> Result<Vec<Session>, String> {
+    store.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_session(store: State<'_, HistoryStore>, id: String) -> Result<Option<Session>, String> {
+    store.get(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_session(store: State<'_, HistoryStore>, session: Session) -> Result<(), String> {
+    store.add(&session).map_err(|e| e.to_string())
+}
+
+/// Delete a session, and its project folder unless `delete_files` is false.
+#[tauri::command]
+pub fn delete_session(
+    store: State<'_, HistoryStore>,
+    library: State<'_, Library>,
+    id: String,
+    delete_files: Option<bool>,
+) -> Result<bool, String> {
+    let session = store.delete(&id).map_err(|e| e.to_string())?;
+    if let Some(session) = &session {
+        if delete_files.unwrap_or(true) {
+            remove_project_dir(session, &library.generate_dir());
+        }
+    }
+    Ok(session.is_some())
+}
+
+/// Rename a session and its project folder, keeping report links to
+/// `/generate/<folder>` pointing at the new folder.
+#[tauri::command]
+pub fn rename_session(
+    store: State<'_, HistoryStore>,
+    id: String,
+    new_title: String,
+) -> Result<bool, String> {
+    store.rename(&id, &new_title)
+}
+
+/// Remove sessions whose project folder no longer exists. Returns how many
+/// were removed.
+#[tauri::command]
+pub fn sync_orphans(store: State<'_, HistoryStore>) -> Result<usize, String> {
+    let mut removed = 0;
+    for session in store.list().map_err(|e| e.to_string())? {
+        if session.project_dir().is_some_and(|d| d.exists()) {
+            continue;
+        }
+        info!("Removing orphan entry: {} (folder missing)", session.title);
+        store.delete(&session.id).map_err(|e| e.to_string())?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Remove all sessions, and their project folders unless `delete_files` is
+/// false.
+#[tauri::command]
+pub fn clear_history(
+    store: State<'_, HistoryStore>,
+    library: State<'_, Library>,
+    delete_files: Option<bool>,
+) -> Result<(), String> {
+    let sessions = store.clear().map_err(|e| e.to_string())?;
+    if delete_files.unwrap_or(true) {
+        let generate_dir = library.generate_dir();
+        for session in &sessions {
+            remove_project_dir(session, &generate_dir);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use serde_json::json;
+
+    fn session(id: &str, project_dir: &Path) -> Session {
+        serde_json::from_value(json!({
+            "id": id,
+            "timestamp": "20240501_093000",
+            "title": id,
+            "summary": format!(
+                "![frame](/generate/{}/assets/1.jpg)",
+                project_dir.file_name().unwrap().to_string_lossy()
+            ),
+            "project_dir": project_dir,
+        }))
+        .unwrap()
+    }
+
+    fn ids(store: &HistoryStore) -> Vec<String> {
+        store.list().unwrap().into_iter().map(|s| s.id).collect()
+    }
+
+    #[test]
+    fn added_sessions_go_first_and_replace_the_same_id() {
+        let dir = scratch_dir("history", "add");
+        let store = HistoryStore::open(&dir).unwrap();
+        store.add(&session("a", &dir.join("a"))).unwrap();
+        store.add(&session("b", &dir.join("b"))).unwrap();
+        assert_eq!(ids(&store), ["b", "a"]);
+
+        let mut updated = session("a", &dir.join("a"));
+        updated.title = "Updated".into();
+        store.add(&updated).unwrap();
+        assert_eq!(ids(&store), ["a", "b"]);
+        assert_eq!(store.get("a").unwrap().unwrap().title, "Updated");
+
+        assert_eq!(store.delete("b").unwrap().unwrap().id, "b");
+        assert!(store.delete("b").unwrap().is_none());
+        assert!(store.get("b").unwrap().is_none());
+        assert_eq!(ids(&store), ["a"]);
+        drop(store);
+    }
+
+    #[test]
+    fn rename_moves_the_folder_without_overwriting_another() {
+        let dir = scratch_dir("history", "rename");
+        let generate = dir.join("generate");
+        fs::create_dir_all(generate.join("Old")).unwrap();
+        fs::create_dir_all(generate.join("New_ Title")).unwrap();
+        let store = HistoryStore::open(&dir).unwrap();
+        store.add(&session("a", &generate.join("Old"))).unwrap();
+
+        assert!(store.rename("a", "New: Title").unwrap());
+        let renamed = store.get("a").unwrap().unwrap();
+        assert_eq!(renamed.title, "New: Title");
+        assert_eq!(
+            Path::new(&renamed.project_dir),
+            generate.join("New_ Title_1")
+        );
+        assert_eq!(
+            renamed.summary,
+            "![frame](/generate/New_ Title_1/assets/1.jpg)"
+        );
+        assert!(generate.join("New_ Title_1").is_dir());
+        assert!(!generate.join("Old").exists());
+
+        assert!(!store.rename("unknown", "Title").unwrap());
+        drop(store);
+    }
+
+    #[test]
+    fn legacy_json_is_imported_once_in_order() {
+        let dir = scratch_dir("history", "legacy");
+        let legacy = dir.join("install");
+        fs::create_dir_all(&legacy).unwrap();
+        let json_path = legacy.join(LEGACY_HISTORY_FILE);
+        let entries = json!({"sessions": [
+            session("newest", &dir.join("newest")),
+            {"id": "malformed"},
+            session("oldest", &dir.join("oldest")),
+        ]});
+        fs::write(&json_path, entries.to_string()).unwrap();
+        let store = HistoryStore::open(&dir).unwrap();
+
+        assert_eq!(store.import_legacy(&legacy).unwrap(), 2);
+        assert_eq!(ids(&store), ["newest", "oldest"]);
+        assert!(!json_path.exists());
+        assert!(legacy.join("user_history.json.imported").is_file());
+
+        // A file put back later is not imported a second time
+        fs::write(
+            &json_path,
+            json!([session("late", &dir.join("late"))]).to_string(),
+        )
+        .unwrap();
+        assert_eq!(store.import_legacy(&legacy).unwrap(), 0);
+        assert_eq!(ids(&store), ["newest", "oldest"]);
+        drop(store);
+    }
+
+    #[test]
+    fn rebase_only_touches_sessions_under_the_old_root() {
+        let dir = scratch_dir("history", "rebase");
+        let (old_root, new_root) = (dir.join("old/generate"), dir.join("new/generate"));
+        let store = HistoryStore::open(&dir).unwrap();
+        store
+            .add(&session("inside", &old_root.join("Talk")))
+            .unwrap();
+        store
+            .add(&session("outside", &dir.join("elsewhere/Talk")))
+            .unwrap();
+
+        assert_eq!(store.rebase_projects(&old_root, &new_root).unwrap(), 1);
+        let inside = store.get("inside").unwrap().unwrap();
+        assert_eq!(Path::new(&inside.project_dir), new_root.join("Talk"));
+        let outside = store.get("outside").unwrap().unwrap();
+        assert_eq!(Path::new(&outside.project_dir), dir.join("elsewhere/Talk"));
+        drop(store);
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let dir = scratch_dir("history", "schema");
+        Connection::open(dir.join(HISTORY_DB_FILE))
+            .unwrap()
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let err = HistoryStore::open(&dir).err().unwrap();
+        assert!(err.to_string().contains("newer than supported"), "{}", err);
+        let conn = Connection::open(dir.join(HISTORY_DB_FILE)).unwrap();
+        let version: i32 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION + 1);
+        drop(conn);
+    }
+
+    #[test]
+    fn only_project_folders_in_generate_are_deleted() {
+        let dir = scratch_dir("history", "remove");
+        let generate = dir.join("generate");
+        let outside = dir.join("Documents");
+        fs::create_dir_all(generate.join("Talk")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+
+        remove_project_dir(&session("outside", &outside), &generate);
+        assert!(outside.is_dir());
+        remove_project_dir(
+            &session("escape", &generate.join("../Documents")),
+            &generate,
+        );
+        assert!(outside.is_dir());
+        remove_project_dir(&session("root", &generate), &generate);
+        assert!(generate.is_dir());
+        remove_project_dir(&session("inside", &generate.join("Talk")), &generate);
+        assert!(!generate.join("Talk").exists());
+    }
+}
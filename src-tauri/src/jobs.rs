@@ -0,0 +1,681 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::auth::{self, LaunchSecrets};
+use crate::launch_inputs::{self, LaunchInput};
+use crate::supervisor::{self, Supervisor};
+
+const QUEUE_FILE: &str = "jobs.json";
+const JOBS_EVENT: &str = "jobs-changed";
+
+// Endpoints served by the sidecar (see `api_jobs_*` in python-core/main.py)
+const JOBS_PATH: &str = "/api/jobs";
+
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Consecutive failed polls (about a minute) before a running job is failed
+const MAX_POLL_FAILURES: u32 = 60;
+const MAX_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+/// One video to download, transcribe and summarize.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub source: LaunchInput,
+    pub state: JobState,
+    /// Pipeline stage reported by the sidecar while running.
+    #[serde(default)]
+    pub stage: Option<String>,
+    /// 0.0 to 1.0 within the current stage.
+    #[serde(default)]
+    pub progress: f32,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// History session created for this job.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub attempts: u32,
+    /// Unix seconds.
+    pub added_at: u64,
+}
+
+impl Job {
+    fn new(source: LaunchInput) -> Self {
+        Self {
+            id: auth::random_hex(8),
+            source,
+            state: JobState::Queued,
+            stage: None,
+            progress: 0.0,
+            error: None,
+            session_id: None,
+            attempts: 0,
+            added_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = JobState::Queued;
+        self.stage = None;
+        self.progress = 0.0;
+        self.error = None;
+    }
+}
+
+fn default_concurrency() -> usize {
+    1
+}
+
+/// Everything persisted in jobs.json. Queued jobs run in list order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueState {
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+}
+
+impl Default for QueueState {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            concurrency: default_concurrency(),
+            jobs: Vec::new(),
+        }
+    }
+}
+
+/// How a job's run on the sidecar ended.
+enum Outcome {
+    Done(Option<String>),
+    Failed(String),
+    Cancelled,
+    /// The sidecar wasn't reachable or ready; try again later.
+    Requeue,
+}
+
+/// Persistent queue of videos for the sidecar's pipeline, kept in the app
+/// data dir so it survives restarts.
+pub struct JobQueue {
+    path: PathBuf,
+    state: Mutex<QueueState>,
+    wake: Notify,
+}
+
+impl JobQueue {
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(QUEUE_FILE);
+        let mut state: QueueState = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(state) => Some(state),
+                Err(e) => {
+                    warn!("Ignoring invalid {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        // Jobs that were running when the app quit start over
+        for job in state
+            .jobs
+            .iter_mut()
+            .filter(|j| j.state == JobState::Running)
+        {
+            job.reset();
+        }
+        Self {
+            path,
+            state: Mutex::new(state),
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueState {
+        self.state.lock().unwrap().clone()
+    }
+
+    /// Apply `f` to the queue and save it.
+    fn update<T>(&self, f: impl FnOnce(&mut QueueState) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state);
+        if let Err(e) = self.save(&state) {
+            warn!("Failed to save {}: {}", self.path.display(), e);
+        }
+        self.wake.notify_one();
+        result
+    }
+
+    fn save(&self, state: &QueueState) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        // Write then rename so a crash never leaves a half-written queue
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+
+    pub fn add(&self, sources: Vec<LaunchInput>) -> Vec<Job> {
+        let jobs: Vec<Job> = sources.into_iter().map(Job::new).collect();
+        self.update(|state| state.jobs.extend(jobs.iter().cloned()));
+        jobs
+    }
+
+    /// Mark the next queued jobs as running, up to the concurrency limit.
+    fn take_ready(&self) -> Vec<Job> {
+        let mut state = self.state.lock().unwrap();
+        if state.paused {
+            return Vec::new();
+        }
+        let running = state
+            .jobs
+            .iter()
+            .filter(|j| j.state == JobState::Running)
+            .count();
+        let slots = state.concurrency.saturating_sub(running);
+        let mut started = Vec::new();
+        for job in state
+            .jobs
+            .iter_mut()
+            .filter(|j| j.state == JobState::Queued)
+            .take(slots)
+        {
+            job.state = JobState::Running;
+            job.stage = Some("starting".to_string());
+            job.attempts += 1;
+            started.push(job.clone());
+        }
+        if !started.is_empty() {
+            if let Err(e) = self.save(&state) {
+                warn!("Failed to save {}: {}", self.path.display(), e);
+            }
+        }
+        started
+    }
+
+    /// Whether `job` is still running the same attempt, i.e. it wasn't
+    /// cancelled (and perhaps retried) in the meantime.
+    fn is_current(&self, job: &Job) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .jobs
+            .iter()
+            .any(|j| j.id == job.id && j.state == JobState::Running && j.attempts == job.attempts)
+    }
+
+    /// Progress only lives in memory; it is not worth a write per poll.
+    fn set_progress(&self, id: &str, stage: Option<String>, progress: f32) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(job) = state.jobs.iter_mut().find(|j| j.id == id) {
+            job.stage = stage;
+            job.progress = progress;
+        }
+    }
+
+    fn finish(&self, run: &Job, outcome: Outcome) {
+        self.update(|state| {
+            let Some(job) = state.jobs.iter_mut().find(|j| {
+                j.id == run.id && j.state == JobState::Running && j.attempts == run.attempts
+            }) else {
+                // Cancelled or removed while it ran
+                return;
+            };
+            match outcome {
+                Outcome::Done(session_id) => {
+                    job.state = JobState::Done;
+                    job.progress = 1.0;
+                    job.session_id = session_id;
+                }
+                Outcome::Failed(error) => {
+                    job.state = JobState::Failed;
+                    job.error = Some(error);
+                }
+                Outcome::Cancelled => job.state = JobState::Cancelled,
+                Outcome::Requeue => {
+                    job.reset();
+                    job.attempts = job.attempts.saturating_sub(1);
+                }
+            }
+        });
+    }
+
+    /// Returns the state the job was in before it was cancelled.
+    fn cancel(&self, id: &str) -> Option<JobState> {
+        self.update(|state| {
+            let job = state.jobs.iter_mut().find(|j| j.id == id)?;
+            let previous = job.state;
+            if matches!(previous, JobState::Queued | JobState::Running) {
+                job.state = JobState::Cancelled;
+            }
+            Some(previous)
+        })
+    }
+
+    fn move_to(&self, id: &str, index: usize) -> Result<(), String> {
+        self.update(|state| {
+            let from = state
+                .jobs
+                .iter()
+                .position(|j| j.id == id)
+                .ok_or_else(|| format!("Unknown job: {}", id))?;
+            let job = state.jobs.remove(from);
+            let index = index.min(state.jobs.len());
+            state.jobs.insert(index, job);
+            Ok(())
+        })
+    }
+
+    /// Queue failed or cancelled jobs again; all failed ones if `ids` is None.
+    fn retry(&self, ids: Option<&[String]>) -> usize {
+        self.update(|state| {
+            let mut count = 0;
+            for job in state.jobs.iter_mut() {
+                let selected = match ids {
+                    Some(ids) => {
+                        ids.contains(&job.id)
+                            && matches!(job.state, JobState::Failed | JobState::Cancelled)
+                    }
+                    None => job.state == JobState::Failed,
+                };
+                if selected {
+                    job.reset();
+                    count += 1;
+                }
+            }
+            count
+        })
+    }
+}
+
+fn publish(app: &AppHandle) {
+    let _ = app.emit_to("main", JOBS_EVENT, app.state::<JobQueue>().snapshot());
+}
+
+/// Add URLs or files to the queue from Rust (launch arguments, watch
+/// folders and the like).
+pub fn enqueue(app: &AppHandle, sources: Vec<LaunchInput>) -> Vec<Job> {
+    if sources.is_empty() {
+        return Vec::new();
+    }
+    let jobs = app.state::<JobQueue>().add(sources);
+    info!("Queued {} jobs", jobs.len());
+    publish(app);
+    jobs
+}
+
+/// Run the dispatcher: whenever a slot is free and the queue isn't paused,
+/// hand the next queued job to the sidecar.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let queue = app.state::<JobQueue>();
+        loop {
+            let ready = queue.take_ready();
+            if !ready.is_empty() {
+                publish(&app);
+            }
+            for job in ready {
+                tauri::async_runtime::spawn(run_job(app.clone(), job));
+            }
+            tokio::select! {
+                _ = queue.wake.notified() => {}
+                _ = tokio::time::sleep(DISPATCH_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+struct SidecarJob {
+    state: String,
+    stage: Option<String>,
+    #[serde(default)]
+    progress: f32,
+    session_id: Option<String>,
+    error: Option<String>,
+}
+
+/// A request to the sidecar's launcher API, signed with this launch's bearer token.
+pub(crate) fn sidecar_request(
+    app: &AppHandle,
+    method: reqwest::Method,
+    path: &str,
+) -> reqwest::RequestBuilder {
+    let port = app.state::<Supervisor>().port();
+    reqwest::Client::new()
+        .request(method, format!("{}{}", supervisor::backend_url(port), path))
+        .header(
+            reqwest::header::AUTHORIZATION,
+            app.state::<LaunchSecrets>().bearer(),
+        )
+        .timeout(REQUEST_TIMEOUT)
+}
+
+async fn run_job(app: AppHandle, job: Job) {
+    info!("Starting job {}", job.id);
+    let outcome = drive(&app, &job).await;
+    match &outcome {
+        Outcome::Done(_) => info!("Job {} done", job.id),
+        Outcome::Failed(e) => warn!("Job {} failed: {}", job.id, e),
+        Outcome::Cancelled => info!("Job {} cancelled", job.id),
+        // Don't hammer a sidecar that is still starting or restarting
+        Outcome::Requeue => tokio::time::sleep(DISPATCH_INTERVAL).await,
+    }
+    app.state::<JobQueue>().finish(&job, outcome);
+    publish(&app);
+}
+
+// Answers from a sidecar that is starting, restarting or still using an
+// old token, which are worth another try like a refused connection
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    matches!(
+        status,
+        reqwest::StatusCode::UNAUTHORIZED
+            | reqwest::StatusCode::BAD_GATEWAY
+            | reqwest::StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+async fn drive(app: &AppHandle, job: &Job) -> Outcome {
+    let body = match &job.source {
+        LaunchInput::Url(url) => json!({ "job_id": job.id, "url": url }),
+        LaunchInput::File(path) => json!({ "job_id": job.id, "file": path }),
+    };
+    let started = sidecar_request(app, reqwest::Method::POST, JOBS_PATH)
+        .json(&body)
+        .send()
+        .await;
+    match started {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) if is_transient(response.status()) => return Outcome::Requeue,
+        Ok(response) => return Outcome::Failed(format!("HTTP {}", response.status())),
+        Err(_) => return Outcome::Requeue,
+    }
+
+    let queue = app.state::<JobQueue>();
+    let status_path = format!("{}/{}", JOBS_PATH, job.id);
+    let mut failures = 0;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if !queue.is_current(job) {
+            return Outcome::Cancelled;
+        }
+        let response = sidecar_request(app, reqwest::Method::GET, &status_path)
+            .send()
+            .await;
+        let status = match response {
+            Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+                return Outcome::Failed("The engine restarted while this job was running".into());
+            }
+            Ok(response) => match response.error_for_status() {
+                Ok(response) => response.json::<SidecarJob>().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        let status = match status {
+            Ok(status) => {
+                failures = 0;
+                status
+            }
+            Err(e) => {
+                failures += 1;
+                if failures >= MAX_POLL_FAILURES {
+                    return Outcome::Failed(format!("Lost contact with the engine: {}", e));
+                }
+                continue;
+            }
+        };
+        match status.state.as_str() {
+            "done" => return Outcome::Done(status.session_id),
+            "error" => return Outcome::Failed(status.error.unwrap_or_default()),
+            "cancelled" => return Outcome::Cancelled,
+            _ => {
+                queue.set_progress(&job.id, status.stage, status.progress);
+                publish(app);
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_jobs(queue: State<'_, JobQueue>) -> QueueState {
+    queue.snapshot()
+}
+
+/// Queue URLs or file paths. Anything that is neither is rejected.
+#[tauri::command]
+pub fn enqueue_jobs(app: AppHandle, inputs: Vec<String>) -> Result<Vec<Job>, String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut sources = Vec::new();
+    for input in &inputs {
+        match launch_inputs::parse_input(input.trim(), &cwd) {
+            Some(source) => sources.push(source),
+            None => return Err(format!("Not a URL or an existing file: {}", input)),
+        }
+    }
+    Ok(enqueue(&app, sources))
+}
+
+#[tauri::command]
+pub fn pause_jobs(app: AppHandle) {
+    app.state::<JobQueue>().update(|state| state.paused = true);
+    publish(&app);
+}
+
+#[tauri::command]
+pub fn resume_jobs(app: AppHandle) {
+    app.state::<JobQueue>().update(|state| state.paused = false);
+    publish(&app);
+}
+
+/// Cancel a queued job, or stop a running one on the sidecar.
+#[tauri::command]
+pub async fn cancel_job(app: AppHandle, id: String) -> Result<(), String> {
+    let previous = app
+        .state::<JobQueue>()
+        .cancel(&id)
+        .ok_or_else(|| format!("Unknown job: {}", id))?;
+    publish(&app);
+    if previous == JobState::Running {
+        let path = format!("{}/{}/cancel", JOBS_PATH, id);
+        if let Err(e) = sidecar_request(&app, reqwest::Method::POST, &path)
+            .send()
+            .await
+        {
+            warn!("Failed to cancel job {} on the sidecar: {}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Move a job to `index` in the queue.
+#[tauri::command]
+pub fn move_job(app: AppHandle, id: String, index: usize) -> Result<(), String> {
+    app.state::<JobQueue>().move_to(&id, index)?;
+    publish(&app);
+    Ok(())
+}
+
+/// Queue the given failed or cancelled jobs again, or every failed job if
+/// `ids` is omitted. Returns how many were queued.
+#[tauri::command]
+pub fn retry_jobs(app: AppHandle, ids: Option<Vec<String>>) -> usize {
+    let count = app.state::<JobQueue>().retry(ids.as_deref());
+    publish(&app);
+    count
+}
+
+/// Drop finished, failed and cancelled jobs from the list.
+#[tauri::command]
+pub fn clear_finished_jobs(app: AppHandle) {
+    app.state::<JobQueue>().update(|state| {
+        state
+            .jobs
+            .retain(|j| matches!(j.state, JobState::Queued | JobState::Running))
+    });
+    publish(&app);
+}
+
+/// How many jobs the sidecar may run at once (1 to 4).
+#[tauri::command]
+pub fn set_job_concurrency(app: AppHandle, concurrency: usize) -> usize {
+    let concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+    app.state::<JobQueue>()
+        .update(|state| state.concurrency = concurrency);
+    publish(&app);
+    concurrency
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{scratch_dir, ScratchDir};
+
+    fn queue_with(name: &str, count: usize) -> (ScratchDir, JobQueue, Vec<String>) {
+        let dir = scratch_dir("jobs", name);
+        let queue = JobQueue::load(&dir);
+        let sources = (0..count)
+            .map(|i| LaunchInput::Url(format!("https://example.com/{}", i)))
+            .collect();
+        let ids = queue.add(sources).into_iter().map(|j| j.id).collect();
+        (dir, queue, ids)
+    }
+
+    fn states(queue: &JobQueue) -> Vec<JobState> {
+        queue.snapshot().jobs.iter().map(|j| j.state).collect()
+    }
+
+    #[test]
+    fn running_jobs_are_queued_again_on_load() {
+        let (dir, queue, ids) = queue_with("load", 2);
+        queue.take_ready();
+        assert_eq!(states(&queue), [JobState::Running, JobState::Queued]);
+
+        let reloaded = JobQueue::load(&dir);
+        let job = &reloaded.snapshot().jobs[0];
+        assert_eq!(job.id, ids[0]);
+        assert_eq!(job.state, JobState::Queued);
+        assert_eq!(job.stage, None);
+        assert_eq!(job.attempts, 1);
+    }
+
+    #[test]
+    fn take_ready_fills_free_slots_in_order() {
+        let (_dir, queue, ids) = queue_with("take", 4);
+        queue.update(|state| state.concurrency = 2);
+
+        let started: Vec<String> = queue.take_ready().into_iter().map(|j| j.id).collect();
+        assert_eq!(started, ids[..2]);
+        assert!(queue.take_ready().is_empty());
+
+        queue.finish(&queue.snapshot().jobs[0], Outcome::Done(None));
+        let started: Vec<String> = queue.take_ready().into_iter().map(|j| j.id).collect();
+        assert_eq!(started, ids[2..3]);
+
+        queue.update(|state| state.paused = true);
+        queue.finish(&queue.snapshot().jobs[1], Outcome::Done(None));
+        assert!(queue.take_ready().is_empty());
+    }
+
+    #[test]
+    fn attempts_follow_each_run() {
+        let (_dir, queue, _) = queue_with("attempts", 1);
+        let first = queue.take_ready().remove(0);
+        assert_eq!(first.attempts, 1);
+        assert!(queue.is_current(&first));
+
+        // The sidecar wasn't reachable: that run doesn't count
+        queue.finish(&first, Outcome::Requeue);
+        assert_eq!(queue.snapshot().jobs[0].attempts, 0);
+        assert_eq!(states(&queue), [JobState::Queued]);
+
+        let second = queue.take_ready().remove(0);
+        queue.finish(&second, Outcome::Failed("boom".into()));
+        assert_eq!(states(&queue), [JobState::Failed]);
+        assert_eq!(queue.retry(None), 1);
+        let third = queue.take_ready().remove(0);
+        assert_eq!(third.attempts, 2);
+
+        // A stale run finishing late doesn't touch the current one
+        assert!(!queue.is_current(&second));
+        queue.finish(&second, Outcome::Done(Some("session".into())));
+        assert_eq!(states(&queue), [JobState::Running]);
+        queue.finish(&third, Outcome::Done(Some("session".into())));
+        let job = &queue.snapshot().jobs[0];
+        assert_eq!(job.state, JobState::Done);
+        assert_eq!(job.session_id.as_deref(), Some("session"));
+    }
+
+    #[test]
+    fn cancel_retry_and_move() {
+        let (dir, queue, ids) = queue_with("manage", 3);
+        let running = queue.take_ready().remove(0);
+
+        assert_eq!(queue.cancel(&ids[0]), Some(JobState::Running));
+        assert!(!queue.is_current(&running));
+        assert_eq!(queue.cancel(&ids[1]), Some(JobState::Queued));
+        assert_eq!(queue.cancel("unknown"), None);
+        // The run that was cancelled ends without changing the job
+        queue.finish(&running, Outcome::Failed("stopped".into()));
+        assert_eq!(
+            states(&queue),
+            [JobState::Cancelled, JobState::Cancelled, JobState::Queued]
+        );
+
+        // Without ids only failed jobs are retried
+        assert_eq!(queue.retry(None), 0);
+        assert_eq!(queue.retry(Some(&ids[1..2])), 1);
+        assert_eq!(
+            states(&queue),
+            [JobState::Cancelled, JobState::Queued, JobState::Queued]
+        );
+
+        queue.move_to(&ids[2], 0).unwrap();
+        queue.move_to(&ids[0], 99).unwrap();
+        let order: Vec<String> = queue.snapshot().jobs.into_iter().map(|j| j.id).collect();
+        assert_eq!(order, [ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+        assert!(queue.move_to("unknown", 0).is_err());
+
+        // The order is saved
+        let reloaded: Vec<String> = JobQueue::load(&dir)
+            .snapshot()
+            .jobs
+            .into_iter()
+            .map(|j| j.id)
+            .collect();
+        assert_eq!(reloaded, [ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn unavailable_sidecars_are_retried() {
+        assert!(is_transient(reqwest::StatusCode::UNAUTHORIZED));
+        assert!(is_transient(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_transient(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient(reqwest::StatusCode::BAD_REQUEST));
+        assert!(!is_transient(reqwest::StatusCode::INTERNAL_SERVER_ERROR));
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{deep_link, jobs, media};
+
+// Event carrying files that were dropped or opened but aren't usable media
+const REJECTED_EVENT: &str = "media-rejected";
+
+/// Something the user asked us to open on the command line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum LaunchInput {
+    Url(String),
+    File(PathBuf),
+}
+
+/// Turn a launch's argv into inputs. `argv[0]`, flags and deep links are
+/// skipped; relative paths are resolved against the launch's working
+/// directory.
+pub fn parse_args(argv: &[String], cwd: &Path) -> Vec<LaunchInput> {
+    argv.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-') && !deep_link::is_deep_link(arg))
+        .filter_map(|arg| {
+            let input = parse_input(arg, cwd);
+            if input.is_none() {
+                warn!("Ignoring launch argument: {}", arg);
+            }
+            input
+        })
+        .collect()
+}
+
+/// An http(s) URL, or a path to an existing file relative to `cwd`.
+pub fn parse_input(arg: &str, cwd: &Path) -> Option<LaunchInput> {
+    let lower = arg.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return Some(LaunchInput::Url(arg.to_string()));
+    }
+    let path = cwd.join(arg);
+    path.is_file().then_some(LaunchInput::File(path))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Queue files for processing, from a drop on the main window or the OS
+/// "Open with" menu. Anything that isn't audio or video is reported to the
+/// main window instead.
+pub fn open_files(app: &AppHandle, paths: Vec<PathBuf>) {
+    let mut accepted = Vec::new();
+    let mut rejected = Vec::new();
+    for path in paths {
+        match media::validate(&path) {
+            Ok(path) => accepted.push(LaunchInput::File(path)),
+            Err(reason) => {
+                warn!("Not opening {}", reason);
+                rejected.push(RejectedFile { path, reason });
+            }
+        }
+    }
+    if !accepted.is_empty() {
+        info!("Opening {} media files", accepted.len());
+        jobs::enqueue(app, accepted);
+    }
+    if !rejected.is_empty() {
+        let _ = app.emit_to("main", REJECTED_EVENT, &rejected);
+    }
+}
+
+/// Queue launch inputs on the job queue. Files are checked like dropped
+/// ones first.
+pub fn enqueue(app: &AppHandle, inputs: Vec<LaunchInput>) {
+    let mut urls = Vec::new();
+    let mut files = Vec::new();
+    for input in inputs {
+        match input {
+            LaunchInput::Url(_) => urls.push(input),
+            LaunchInput::File(path) => files.push(path),
+        }
+    }
+    if !urls.is_empty() {
+        info!("Opening {} URLs", urls.len());
+        jobs::enqueue(app, urls);
+    }
+    open_files(app, files);
+}
+
+pub fn focus_main_window(app: &AppHandle) {
+    if let Some(main) = app.get_webview_window("main") {
+        let _ = main.unminimize();
+        let _ = main.show();
+        let _ = main.set_focus();
+    }
+}
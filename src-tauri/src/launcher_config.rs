@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::Deserialize;
+
+const LAUNCHER_CONFIG_FILE: &str = "launcher.json";
+
+// Defaults match the old hard-coded probe: 60 polls, 500ms apart
+const DEFAULT_STARTUP_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 500;
+
+/// Launcher settings read from `launcher.json` in the app config dir.
+/// These only affect the Rust side; the sidecar has its own user config.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LauncherConfig {
+    pub readiness: ReadinessPolicy,
+}
+
+/// How long and how often to probe a starting sidecar, and what to do when
+/// it is slow.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReadinessPolicy {
+    pub timeout_secs: u64,
+    pub poll_interval_ms: u64,
+    /// Keep waiting past the timeout for as long as the sidecar process is
+    /// alive, e.g. while a one-file build unpacks or CUDA loads.
+    pub wait_while_alive: bool,
+    /// Times a failed first start is restarted automatically before the
+    /// splash screen asks the user to retry.
+    pub auto_retries: u32,
+}
+
+impl Default for ReadinessPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_secs: DEFAULT_STARTUP_TIMEOUT_SECS,
+            poll_interval_ms: DEFAULT_POLL_INTERVAL_MS,
+            wait_while_alive: false,
+            auto_retries: 0,
+        }
+    }
+}
+
+impl ReadinessPolicy {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        // A zero interval would spin on the health endpoint
+        Duration::from_millis(self.poll_interval_ms.max(50))
+    }
+}
+
+impl LauncherConfig {
+    /// Read `launcher.json` from `config_dir`, falling back to defaults,
+    /// then apply `OAN_STARTUP_*` environment overrides.
+    pub fn load(config_dir: &Path) -> Self {
+        let path = config_dir.join(LAUNCHER_CONFIG_FILE);
+        let mut config = match fs::read_to_string(&path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                warn!("Ignoring invalid {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        config.apply_env();
+        info!("Readiness policy: {:?}", config.readiness);
+        config
+    }
+
+    fn apply_env(&mut self) {
+        let readiness = &mut self.readiness;
+        if let Some(secs) = env_parse("OAN_STARTUP_TIMEOUT_SECS") {
+            readiness.timeout_secs = secs;
+        }
+        if let Some(ms) = env_parse("OAN_STARTUP_POLL_INTERVAL_MS") {
+            readiness.poll_interval_ms = ms;
+        }
+        if let Some(wait) = env_flag("OAN_STARTUP_WAIT_WHILE_ALIVE") {
+            readiness.wait_while_alive = wait;
+        }
+        if let Some(retries) = env_parse("OAN_STARTUP_AUTO_RETRIES") {
+            readiness.auto_retries = retries;
+        }
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    let value = std::env::var(key).ok()?;
+    let parsed = value.trim().parse().ok();
+    if parsed.is_none() {
+        warn!("Ignoring invalid {}={}", key, value);
+    }
+    parsed
+}
+
+fn env_flag(key: &str) -> Option<bool> {
+    let value = std::env::var(key).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => {
+            warn!("Ignoring invalid {}={}", key, value);
+            None
+        }
+    }
+}
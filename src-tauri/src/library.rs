@@ -0,0 +1,477 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+use crate::export;
+use crate::history::HistoryStore;
+use crate::supervisor::{self, Supervisor};
+use crate::watcher;
+
+// Written to the app config dir when the library lives somewhere else
+const LIBRARY_POINTER_FILE: &str = "library.json";
+
+/// Folder with one subfolder per processed video.
+pub const GENERATE_DIR: &str = "generate";
+const CONFIG_FILE: &str = "user_config.json";
+const LEGACY_HISTORY_FILE: &str = "user_history.json";
+
+// Left in the library once data from the install folder has been copied in
+const MIGRATED_MARKER: &str = ".migrated";
+
+// Files the history database may leave next to itself
+const HISTORY_DB_FILES: [&str; 3] = ["history.db", "history.db-wal", "history.db-shm"];
+
+#[derive(Serialize, Deserialize)]
+struct LibraryPointer {
+    path: PathBuf,
+}
+
+/// Per-user folder holding the sidecar's config, the history database and
+/// generate/. The sidecar gets it through `OAN_DATA_DIR`.
+pub struct Library {
+    pointer: PathBuf,
+    dir: RwLock<PathBuf>,
+}
+
+impl Library {
+    /// Use the folder recorded in `library.json` under `config_dir`, or
+    /// `default_dir` if the library was never moved.
+    pub fn resolve(config_dir: &Path, default_dir: &Path) -> Self {
+        let pointer = config_dir.join(LIBRARY_POINTER_FILE);
+        let dir = fs::read_to_string(&pointer)
+            .ok()
+            .and_then(|s| serde_json::from_str::<LibraryPointer>(&s).ok())
+            .map(|p| p.path)
+            .filter(|p| {
+                let usable = p.is_dir();
+                if !usable {
+                    warn!("Library {} is missing, using the default", p.display());
+                }
+                usable
+            })
+            .unwrap_or_else(|| default_dir.to_path_buf());
+        let _ = fs::create_dir_all(&dir);
+        info!("Library: {}", dir.display());
+        Self {
+            pointer,
+            dir: RwLock::new(dir),
+        }
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.dir.read().unwrap().clone()
+    }
+
+    pub fn generate_dir(&self) -> PathBuf {
+        self.dir().join(GENERATE_DIR)
+    }
+
+    fn set_dir(&self, dir: &Path) -> Result<(), String> {
+        if let Some(parent) = self.pointer.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(&LibraryPointer {
+            path: dir.to_path_buf(),
+        })
+        .map_err(|e| e.to_string())?;
+        // Write then rename so a crash never leaves a half-written pointer
+        let tmp = self.pointer.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.pointer).map_err(|e| e.to_string())?;
+        *self.dir.write().unwrap() = dir.to_path_buf();
+        Ok(())
+    }
+}
+
+/// Copy data the sidecar used to keep next to its binary into the library,
+/// once. Nothing is deleted from `install_dir`, which may be read-only.
+pub fn migrate_from_install_dir(library: &Library, store: &HistoryStore, install_dir: &Path) {
+    let dir = library.dir();
+    let marker = dir.join(MIGRATED_MARKER);
+    if marker.exists() || install_dir == dir {
+        return;
+    }
+    let legacy_config = install_dir.join(CONFIG_FILE);
+    let legacy_generate = install_dir.join(GENERATE_DIR);
+    let has_legacy_data = legacy_config.is_file()
+        || legacy_generate.is_dir()
+        || install_dir.join(LEGACY_HISTORY_FILE).is_file();
+
+    if has_legacy_data {
+        info!("Migrating data from {}", install_dir.display());
+        let config = dir.join(CONFIG_FILE);
+        if legacy_config.is_file() && !config.exists() {
+            if let Err(e) = fs::copy(&legacy_config, &config) {
+                warn!("Failed to copy {}: {}", legacy_config.display(), e);
+            }
+        }
+        if legacy_generate.is_dir() {
+            match copy_tree(&legacy_generate, &library.generate_dir()) {
+                Ok(stats) => info!("Copied {} files ({} bytes)", stats.files, stats.bytes),
+                Err(e) => {
+                    // Leave the marker unset so the next launch tries again
+                    warn!("Failed to copy {}: {}", legacy_generate.display(), e);
+                    return;
+                }
+            }
+        }
+        if let Err(e) = store.import_legacy(install_dir) {
+            warn!("Failed to import history: {}", e);
+        }
+        if let Err(e) = store.rebase_projects(&legacy_generate, &library.generate_dir()) {
+            warn!("Failed to update project folders: {}", e);
+        }
+    }
+    let _ = fs::write(&marker, install_dir.to_string_lossy().as_bytes());
+}
+
+#[derive(Debug, Default)]
+struct CopyStats {
+    files: u64,
+    bytes: u64,
+}
+
+/// Recursively copy `src` into `dst`, skipping files that already exist.
+fn copy_tree(src: &Path, dst: &Path) -> io::Result<CopyStats> {
+    let mut stats = CopyStats::default();
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            let sub = copy_tree(&entry.path(), &target)?;
+            stats.files += sub.files;
+            stats.bytes += sub.bytes;
+        } else if !target.exists() {
+            stats.bytes += fs::copy(entry.path(), &target)?;
+            stats.files += 1;
+        }
+    }
+    Ok(stats)
+}
+
+/// Check that every file under `src` exists under `dst` with the same size.
+fn verify_tree(src: &Path, dst: &Path) -> Result<CopyStats, String> {
+    let mut stats = CopyStats::default();
+    let entries = fs::read_dir(src).map_err(|e| format!("{}: {}", src.display(), e))?;
+    for entry in entries.flatten() {
+        let target = dst.join(entry.file_name());
+        let meta = entry.metadata().map_err(|e| e.to_string())?;
+        if meta.is_dir() {
+            let sub = verify_tree(&entry.path(), &target)?;
+            stats.files += sub.files;
+            stats.bytes += sub.bytes;
+            continue;
+        }
+        let copied = fs::metadata(&target)
+            .map_err(|e| format!("{} was not copied: {}", target.display(), e))?;
+        if copied.len() != meta.len() {
+            return Err(format!(
+                "{} is {} bytes but the original is {}",
+                target.display(),
+                copied.len(),
+                meta.len()
+            ));
+        }
+        stats.files += 1;
+        stats.bytes += meta.len();
+    }
+    Ok(stats)
+}
+
+fn is_processing(store: &HistoryStore) -> Result<bool, String> {
+    let sessions = store.list().map_err(|e| e.to_string())?;
+    Ok(sessions
+        .iter()
+        .any(|s| s.extra.get("status").and_then(Value::as_str) == Some("processing")))
+}
+
+/// Refuse destinations the library can't be moved to.
+fn check_destination(
+    store: &HistoryStore,
+    old_dir: &Path,
+    destination: &Path,
+) -> Result<(), String> {
+    if destination.starts_with(old_dir) {
+        return Err("The new location can't be inside the current library".to_string());
+    }
+    if is_processing(store)? {
+        return Err("Wait for the current video to finish before moving the library".to_string());
+    }
+    let occupied = [CONFIG_FILE, GENERATE_DIR, HISTORY_DB_FILES[0]]
+        .iter()
+        .any(|name| destination.join(name).exists());
+    if occupied {
+        return Err(format!(
+            "{} already contains a library",
+            destination.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Copy config, generate/ and a snapshot of the history to `destination`
+/// and check the copy is complete.
+fn copy_library(store: &HistoryStore, old_dir: &Path, destination: &Path) -> Result<(), String> {
+    let old_generate = old_dir.join(GENERATE_DIR);
+    if old_generate.is_dir() {
+        let new_generate = destination.join(GENERATE_DIR);
+        copy_tree(&old_generate, &new_generate).map_err(|e| e.to_string())?;
+        let stats = verify_tree(&old_generate, &new_generate)?;
+        info!("Verified {} files ({} bytes)", stats.files, stats.bytes);
+    }
+    let old_config = old_dir.join(CONFIG_FILE);
+    if old_config.is_file() {
+        fs::copy(&old_config, destination.join(CONFIG_FILE)).map_err(|e| e.to_string())?;
+    }
+    store.snapshot_to(destination).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Point the database and the pointer file at `destination`. On failure the
+/// old database is open again and the library still points at `old_dir`.
+fn switch_library(
+    library: &Library,
+    store: &HistoryStore,
+    old_dir: &Path,
+    destination: &Path,
+) -> Result<(), String> {
+    let sessions_before = store.list().map_err(|e| e.to_string())?.len();
+    store.reopen(destination).map_err(|e| e.to_string())?;
+    let switched = (|| {
+        let sessions_after = store.list().map_err(|e| e.to_string())?.len();
+        if sessions_after != sessions_before {
+            return Err(format!(
+                "The copied history has {} sessions instead of {}",
+                sessions_after, sessions_before
+            ));
+        }
+        store
+            .rebase_projects(&old_dir.join(GENERATE_DIR), &destination.join(GENERATE_DIR))
+            .map_err(|e| e.to_string())?;
+        library.set_dir(destination)
+    })();
+    if switched.is_err() {
+        if let Err(e) = store.reopen(old_dir) {
+            warn!("Failed to reopen {}: {}", old_dir.display(), e);
+        }
+    }
+    switched
+}
+
+/// Remove what `copy_library` put in `destination`, which held none of it
+/// before (see `check_destination`).
+fn remove_copy(destination: &Path, created: bool) {
+    for name in HISTORY_DB_FILES.iter().chain([CONFIG_FILE].iter()) {
+        let _ = fs::remove_file(destination.join(name));
+    }
+    let generate = destination.join(GENERATE_DIR);
+    if generate.exists() {
+        if let Err(e) = fs::remove_dir_all(&generate) {
+            warn!("Failed to remove {}: {}", generate.display(), e);
+        }
+    }
+    if created {
+        let _ = fs::remove_dir(destination);
+    }
+}
+
+/// Copy the library to `destination`, verify the copy, switch everything
+/// over to it and then delete the old files. The sidecar must be stopped.
+/// If anything fails before the switch completes, the old library stays in
+/// use and the copy is removed.
+fn move_library_blocking(app: &AppHandle, destination: &Path) -> Result<PathBuf, String> {
+    let library = app.state::<Library>();
+    let store = app.state::<HistoryStore>();
+    let old_dir = library.dir();
+    let created = !destination.exists();
+    fs::create_dir_all(destination).map_err(|e| e.to_string())?;
+
+    // Copy and verify everything before touching the original
+    let moved = copy_library(&store, &old_dir, destination)
+        .and_then(|()| switch_library(&library, &store, &old_dir, destination));
+    if let Err(e) = moved {
+        remove_copy(destination, created);
+        return Err(e);
+    }
+    if let Err(e) = watcher::start(app, &destination.join(GENERATE_DIR)) {
+        warn!("Failed to watch project folders: {}", e);
+    }
+
+    let _ = fs::copy(
+        old_dir.join(MIGRATED_MARKER),
+        destination.join(MIGRATED_MARKER),
+    );
+
+    // The copy is verified, so failing to clean up is not an error
+    for name in [CONFIG_FILE, MIGRATED_MARKER]
+        .iter()
+        .chain(HISTORY_DB_FILES.iter())
+    {
+        let _ = fs::remove_file(old_dir.join(name));
+    }
+    let old_generate = old_dir.join(GENERATE_DIR);
+    if let Err(e) = fs::remove_dir_all(&old_generate) {
+        warn!("Failed to remove {}: {}", old_generate.display(), e);
+    }
+    info!(
+        "Library moved: {} -> {}",
+        old_dir.display(),
+        destination.display()
+    );
+    Ok(destination.to_path_buf())
+}
+
+#[tauri::command]
+pub fn get_library_dir(library: State<'_, Library>) -> String {
+    library.dir().to_string_lossy().into_owned()
+}
+
+/// Move the library (config, history and generate/) to `destination`, or a
+/// folder picked with the native dialog, and restart the sidecar there.
+/// Returns the new library path, or `None` if the user cancelled. The page
+/// that asked is gone by the time the move ends, so a failed move is also
+/// reported in a native dialog.
+#[tauri::command]
+pub async fn move_library(
+    app: AppHandle,
+    destination: Option<String>,
+) -> Result<Option<String>, String> {
+    let destination = match destination {
+        Some(destination) => PathBuf::from(destination),
+        None => {
+            let handle = app.clone();
+            let picked = tauri::async_runtime::spawn_blocking(move || export::pick_folder(&handle))
+                .await
+                .map_err(|e| e.to_string())?;
+            match picked {
+                Some(picked) => picked,
+                None => return Ok(None),
+            }
+        }
+    };
+    let old_dir = app.state::<Library>().dir();
+    if destination == old_dir {
+        return Ok(Some(old_dir.to_string_lossy().into_owned()));
+    }
+    check_destination(&app.state::<HistoryStore>(), &old_dir, &destination)?;
+
+    // Stop the sidecar cleanly first so nothing it has open is written to
+    // while the files are copied; it starts again once they are in place
+    supervisor::hold(&app).await;
+    let handle = app.clone();
+    let moved =
+        tauri::async_runtime::spawn_blocking(move || move_library_blocking(&handle, &destination))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|moved| moved);
+    app.state::<Supervisor>().release();
+    if let Err(e) = &moved {
+        warn!("Failed to move the library: {}", e);
+        app.dialog()
+            .message(format!("The library was not moved.\n\n{}", e))
+            .title("OpenAutoNote")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
+    }
+    Ok(Some(moved?.to_string_lossy().into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Session;
+    use crate::test_util::scratch_dir;
+    use serde_json::json;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn copy_tree_copies_nested_files_and_keeps_existing_ones() {
+        let dir = scratch_dir("library", "copy");
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        write(&src.join("a/report.md"), "report");
+        write(&src.join("a/assets/1.jpg"), "jpeg");
+        write(&src.join("b/transcript.json"), "[]");
+        write(&dst.join("b/transcript.json"), "kept");
+
+        let stats = copy_tree(&src, &dst).unwrap();
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.bytes, 10);
+        assert_eq!(
+            fs::read_to_string(dst.join("a/assets/1.jpg")).unwrap(),
+            "jpeg"
+        );
+        assert_eq!(
+            fs::read_to_string(dst.join("b/transcript.json")).unwrap(),
+            "kept"
+        );
+    }
+
+    #[test]
+    fn verify_tree_accepts_a_complete_copy() {
+        let dir = scratch_dir("library", "verify-ok");
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        write(&src.join("a/report.md"), "report");
+        write(&src.join("a/assets/1.jpg"), "jpeg");
+        copy_tree(&src, &dst).unwrap();
+
+        let stats = verify_tree(&src, &dst).unwrap();
+        assert_eq!((stats.files, stats.bytes), (2, 10));
+    }
+
+    #[test]
+    fn verify_tree_rejects_missing_and_truncated_files() {
+        let dir = scratch_dir("library", "verify-bad");
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        write(&src.join("a/report.md"), "report");
+        write(&src.join("a/assets/1.jpg"), "jpeg");
+        copy_tree(&src, &dst).unwrap();
+
+        write(&dst.join("a/report.md"), "rep");
+        let err = verify_tree(&src, &dst).unwrap_err();
+        assert!(err.contains("3 bytes but the original is 6"), "{}", err);
+
+        fs::remove_file(dst.join("a/assets/1.jpg")).unwrap();
+        write(&dst.join("a/report.md"), "report");
+        let err = verify_tree(&src, &dst).unwrap_err();
+        assert!(err.contains("was not copied"), "{}", err);
+    }
+
+    #[test]
+    fn check_destination_refuses_unusable_folders() {
+        let dir = scratch_dir("library", "check");
+        let old_dir = dir.join("library");
+        let store = HistoryStore::open(&old_dir).unwrap();
+
+        assert!(check_destination(&store, &old_dir, &dir.join("elsewhere")).is_ok());
+        assert!(check_destination(&store, &old_dir, &old_dir.join("inner")).is_err());
+
+        let occupied = dir.join("occupied");
+        write(&occupied.join(CONFIG_FILE), "{}");
+        let err = check_destination(&store, &old_dir, &occupied).unwrap_err();
+        assert!(err.contains("already contains a library"), "{}", err);
+
+        let session: Session = serde_json::from_value(json!({
+            "id": "busy",
+            "timestamp": "20240501_093000",
+            "title": "busy",
+            "status": "processing",
+        }))
+        .unwrap();
+        store.add(&session).unwrap();
+        let err = check_destination(&store, &old_dir, &dir.join("elsewhere")).unwrap_err();
+        assert!(err.contains("Wait for the current video"), "{}", err);
+        drop(store);
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::PathBuf;
+
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind, TimezoneStrategy};
+use tauri_plugin_opener::OpenerExt;
+
+// Base name of the log file in the platform log dir (<name>.log)
+const LOG_FILE_NAME: &str = "openautonote";
+const MAX_LOG_BYTES: u128 = 5 * 1024 * 1024;
+const KEEP_ROTATED_LOGS: usize = 5;
+const DEFAULT_TAIL_LINES: usize = 200;
+
+/// Log target for lines forwarded from the api-server sidecar.
+pub const SIDECAR_TARGET: &str = "sidecar";
+
+/// Writes Tauri-side and sidecar logs, with timestamps and levels, to stdout
+/// and to size-rotated files in the platform log dir.
+pub fn plugin<R: Runtime>() -> TauriPlugin<R> {
+    tauri_plugin_log::Builder::new()
+        .level(log::LevelFilter::Info)
+        .targets([
+            Target::new(TargetKind::Stdout),
+            Target::new(TargetKind::LogDir {
+                file_name: Some(LOG_FILE_NAME.to_string()),
+            }),
+        ])
+        .max_file_size(MAX_LOG_BYTES)
+        .rotation_strategy(RotationStrategy::KeepSome(KEEP_ROTATED_LOGS))
+        .timezone_strategy(TimezoneStrategy::UseLocal)
+        .build()
+}
+
+pub fn current_log_path<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<PathBuf> {
+    Ok(app
+        .path()
+        .app_log_dir()?
+        .join(format!("{}.log", LOG_FILE_NAME)))
+}
+
+/// The current log file and its rotated predecessors.
+pub fn log_files<R: Runtime>(app: &AppHandle<R>) -> Vec<PathBuf> {
+    let Ok(dir) = app.path().app_log_dir() else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| {
+                    p.is_file()
+                        && p.file_name()
+                            .is_some_and(|n| n.to_string_lossy().starts_with(LOG_FILE_NAME))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Last `lines` lines of the current log file.
+#[tauri::command]
+pub fn tail_log(app: AppHandle, lines: Option<usize>) -> Result<Vec<String>, String> {
+    let path = current_log_path(&app).map_err(|e| e.to_string())?;
+    let content = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let content = String::from_utf8_lossy(&content);
+    let all: Vec<&str> = content.lines().collect();
+    let start = all
+        .len()
+        .saturating_sub(lines.unwrap_or(DEFAULT_TAIL_LINES));
+    Ok(all[start..].iter().map(|l| l.to_string()).collect())
+}
+
+/// Open the current log file in the system's default viewer.
+#[tauri::command]
+pub fn open_log(app: AppHandle) -> Result<(), String> {
+    let path = current_log_path(&app).map_err(|e| e.to_string())?;
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
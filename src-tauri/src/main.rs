@@ -1,53 +1,70 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::io::{Read, Write};
-use std::process::Command;
-use std::thread;
-use std::time::Duration;
+mod auth;
+mod config;
+mod deep_link;
+mod diagnostics;
+mod export;
+mod health;
+mod history;
+mod jobs;
+mod launch_inputs;
+mod launcher_config;
+mod library;
+mod logging;
+mod media;
+mod process_registry;
+mod search;
+mod secrets;
+mod startup;
+mod supervisor;
+#[cfg(test)]
+mod test_util;
+mod timestamp;
+mod watch_folders;
+mod watcher;
 
-use tauri::{Manager, WebviewWindow};
-use tauri_plugin_shell::process::CommandEvent;
-use tauri_plugin_shell::ShellExt;
+use std::path::Path;
 
-// Define the port we expect the Python server to listen on
-const TARGET_PORT: u16 = 8964;
+use log::error;
+use tauri::{AppHandle, Manager, RunEvent, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
 
-fn kill_zombie_sidecars() {
-    #[cfg(target_os = "windows")]
-    {
-        let _ = Command::new("taskkill")
-            .args(["/F", "/IM", "api-server.exe"])
-            .output();
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        let _ = Command::new("pkill").args(["-f", "api-server"]).output();
-    }
+use auth::LaunchSecrets;
+use config::ConfigStore;
+use export::html::PrintPreview;
+use health::{HealthConfig, HealthMonitor};
+use history::HistoryStore;
+use jobs::JobQueue;
+use launcher_config::LauncherConfig;
+use library::Library;
+use process_registry::ProcessRegistry;
+use search::SearchIndex;
+use secrets::SecretStore;
+use startup::StartupReporter;
+use supervisor::Supervisor;
+use watch_folders::WatchFolders;
+use watcher::GenerateWatcher;
 
-    #[cfg(target_os = "linux")]
-    {
-        let _ = Command::new("pkill").args(["-f", "api-server"]).output();
-    }
-}
-
-fn backend_ready() -> bool {
-    if let Ok(mut stream) = std::net::TcpStream::connect(("127.0.0.1", TARGET_PORT)) {
-        let _ = stream.write_all(
-            b"GET / HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\n\r\n",
-        );
-        let mut buf = [0u8; 64];
-        if let Ok(n) = stream.read(&mut buf) {
-            let head = String::from_utf8_lossy(&buf[..n]);
-            return head.starts_with("HTTP/1.1 200") || head.starts_with("HTTP/1.0 200");
-        }
-    }
-    false
+/// The main window is built here rather than in tauri.conf.json because its
+/// URL depends on the port chosen at startup.
+fn open_main_window(app: &AppHandle, port: u16) -> tauri::Result<WebviewWindow> {
+    let url = app
+        .state::<LaunchSecrets>()
+        .login_url(&supervisor::backend_url(port))
+        .parse()
+        .expect("backend URL is always valid");
+    WebviewWindowBuilder::new(app, "main", WebviewUrl::External(url))
+        .title("OpenAutoNote")
+        .inner_size(1200.0, 800.0)
+        .resizable(true)
+        .visible(false)
+        .build()
 }
 
 fn show_main_and_close_splash(main: Option<WebviewWindow>, splash: Option<WebviewWindow>) {
+    // destroy() rather than close(): close() raises CloseRequested, which quits the app
     if let Some(splash) = splash {
-        let _ = splash.close();
+        let _ = splash.destroy();
     }
     if let Some(main) = main {
         let _ = main.show();
@@ -56,66 +73,140 @@ fn show_main_and_close_splash(main: Option<WebviewWindow>, splash: Option<Webvie
 }
 
 fn main() {
-    kill_zombie_sidecars();
-
     tauri::Builder::default()
+        // Must be registered first: a second launch forwards its arguments
+        // here and exits before spawning a sidecar of its own.
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            let inputs = launch_inputs::parse_args(&argv, Path::new(&cwd));
+            launch_inputs::enqueue(app, inputs);
+            launch_inputs::focus_main_window(app);
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(logging::plugin())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
+        .register_uri_scheme_protocol(export::html::PRINT_SCHEME, export::html::serve_print)
         .setup(|app| {
-            let app_handle = app.handle().clone();
-            let splash = app_handle.get_webview_window("splashscreen");
-            let main_window = app_handle.get_webview_window("main");
+            // Only reap sidecars this app recorded, never other processes by name
+            let data_dir = app.path().app_data_dir()?;
+            let registry = ProcessRegistry::new(&data_dir);
+            registry.reap_stale();
+            app.manage(registry);
 
-            // 1. Spawn the Sidecar
-            let sidecar_command = app
-                .shell()
-                .sidecar("api-server")
-                .expect("Failed to create sidecar command");
+            // Config, history and generate/ live in a per-user library rather
+            // than next to the sidecar binary, which may be read-only
+            let library = Library::resolve(&app.path().app_config_dir()?, &data_dir);
+            let store = HistoryStore::open(&library.dir())?;
+            if let Some(install_dir) = std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            {
+                library::migrate_from_install_dir(&library, &store, &install_dir);
+            }
+            if let Err(e) = store.import_legacy(&library.dir()) {
+                error!("Failed to import history: {}", e);
+            }
+            let config_store = ConfigStore::default();
+            let secret_store =
+                SecretStore::open(&app.config().identifier, &app.path().app_config_dir()?);
+            secrets::import_from_config(&secret_store, &config_store, &library.dir());
+            let generate_dir = library.generate_dir();
+            app.manage(store);
+            app.manage(library);
+            app.manage(config_store);
+            app.manage(secret_store);
+            app.manage(SearchIndex::open(&data_dir)?);
+            app.manage(GenerateWatcher::default());
+            app.manage(PrintPreview::default());
+            if let Err(e) = watcher::start(app.handle(), &generate_dir) {
+                error!("Failed to watch project folders: {}", e);
+            }
 
-            let (mut rx, _child) = sidecar_command
-                .args(["--port", &TARGET_PORT.to_string()])
-                .spawn()
-                .expect("Failed to spawn sidecar");
+            // Managed before the argv is handled: every input goes straight to the queue
+            app.manage(JobQueue::load(&data_dir));
+            let argv: Vec<String> = std::env::args().collect();
+            let cwd = std::env::current_dir().unwrap_or_default();
+            launch_inputs::enqueue(app.handle(), launch_inputs::parse_args(&argv, &cwd));
+            deep_link::listen(app);
 
-            // 2. Handle Sidecar Events (logging) in a separate thread
-            tauri::async_runtime::spawn(async move {
-                while let Some(event) = rx.recv().await {
-                    if let CommandEvent::Stdout(line) = event {
-                        let log = String::from_utf8_lossy(&line);
-                        println!("[PY] {}", log);
-                    } else if let CommandEvent::Stderr(line) = event {
-                        let log = String::from_utf8_lossy(&line);
-                        eprintln!("[PY ERR] {}", log);
-                    }
-                }
-            });
+            let launcher_config = LauncherConfig::load(&app.path().app_config_dir()?);
+            app.manage(launcher_config.readiness);
 
-            // 3. Wait for Python Server to be Ready, then swap splash -> main
-            tauri::async_runtime::spawn(async move {
-                let mut attempts = 0;
-                let max_attempts = 60; // ~30s
-                let mut ready = false;
+            let port = supervisor::pick_free_port();
+            supervisor::allow_ui_ipc(app.handle(), port)?;
+            let secrets = LaunchSecrets::generate();
+            app.manage(HealthMonitor::new(
+                &supervisor::backend_url(port),
+                &secrets.bearer(),
+                &HealthConfig::from_env(),
+            ));
+            app.manage(secrets);
+            app.manage(Supervisor::new(port));
+            app.manage(StartupReporter::default());
+            jobs::start(app.handle().clone());
+            app.manage(WatchFolders::load(&data_dir));
+            watch_folders::start(app.handle().clone());
 
-                while attempts < max_attempts {
-                    if backend_ready() {
-                        ready = true;
-                        break;
+            // Spawn and supervise the sidecar, then swap splash -> main once it is ready
+            supervisor::start(app.handle().clone(), move |app_handle| {
+                let splash = app_handle.get_webview_window("splashscreen");
+                let main_window = match open_main_window(app_handle, port) {
+                    Ok(window) => Some(window),
+                    Err(e) => {
+                        error!("Failed to create main window: {}", e);
+                        None
                     }
-                    thread::sleep(Duration::from_millis(500));
-                    attempts += 1;
-                }
-
-                if ready {
-                    show_main_and_close_splash(main_window, splash);
-                } else {
-                    eprintln!("Failed to connect to Python backend after timeout.");
-                    app_handle.exit(1);
-                }
+                };
+                show_main_and_close_splash(main_window, splash);
             });
 
             Ok(())
         })
+        .invoke_handler(tauri::generate_handler![
+            health::get_backend_health,
+            logging::tail_log,
+            logging::open_log,
+            diagnostics::export_diagnostics,
+            history::list_sessions,
+            history::get_session,
+            history::add_session,
+            history::delete_session,
+            history::rename_session,
+            history::sync_orphans,
+            history::clear_history,
+            jobs::list_jobs,
+            jobs::enqueue_jobs,
+            jobs::pause_jobs,
+            jobs::resume_jobs,
+            jobs::cancel_job,
+            jobs::move_job,
+            jobs::retry_jobs,
+            jobs::clear_finished_jobs,
+            jobs::set_job_concurrency,
+            watch_folders::list_watch_folders,
+            watch_folders::add_watch_folder,
+            watch_folders::remove_watch_folder,
+            search::search_notes,
+            export::subtitles::export_subtitles,
+            export::vault::export_to_vault,
+            export::html::export_report,
+            export::epub::export_epub,
+            export::anki::export_anki,
+            library::get_library_dir,
+            library::move_library,
+            config::get_config,
+            config::set_config,
+            secrets::get_secret_status,
+            secrets::set_secret,
+            startup::get_startup_status,
+            startup::retry_startup
+        ])
         .on_window_event(|window, event| match event {
-            tauri::WindowEvent::CloseRequested { .. } => {
+            // Closing the print preview leaves the app running
+            tauri::WindowEvent::CloseRequested { .. }
+                if window.label() != export::html::PRINT_WINDOW =>
+            {
                 #[cfg(not(target_os = "macos"))]
                 {
                     window.app_handle().exit(0);
@@ -125,8 +216,41 @@ fn main() {
                     window.app_handle().exit(0);
                 }
             }
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. })
+                if window.label() == "main" =>
+            {
+                launch_inputs::open_files(window.app_handle(), paths.clone());
+            }
             _ => {}
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            if let RunEvent::ExitRequested { api, code, .. } = &event {
+                // Hold the exit until the sidecar has been stopped; the
+                // second request (from the task below) goes through.
+                if app_handle.state::<Supervisor>().begin_stop() {
+                    api.prevent_exit();
+                    let handle = app_handle.clone();
+                    let code = *code;
+                    tauri::async_runtime::spawn(async move {
+                        supervisor::shutdown(&handle).await;
+                        handle.exit(code.unwrap_or(0));
+                    });
+                }
+            }
+            // macOS delivers "Open with" files as an event rather than argv.
+            // Deep links arrive the same way but go through the deep-link plugin.
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            if let RunEvent::Opened { urls } = &event {
+                let paths: Vec<_> = urls
+                    .iter()
+                    .filter_map(|url| url.to_file_path().ok())
+                    .collect();
+                if !paths.is_empty() {
+                    launch_inputs::open_files(app_handle, paths);
+                    launch_inputs::focus_main_window(app_handle);
+                }
+            }
+        });
 }
@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Audio and video the pipeline can transcribe. Keep in sync with
+/// `fileAssociations` in tauri.conf.json.
+pub const MEDIA_EXTENSIONS: [&str; 12] = [
+    "mp4", "mov", "mkv", "webm", "avi", "m4v", "mp3", "wav", "m4a", "flac", "aac", "ogg",
+];
+
+// Enough for every signature checked in `probe`
+const HEADER_BYTES: usize = 12;
+
+/// Container format recognised from a file's first bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// MP4, MOV, M4A and M4V.
+    IsoMedia,
+    /// Matroska and WebM.
+    Matroska,
+    Avi,
+    Wave,
+    /// MP3 or ADTS AAC.
+    Mpeg,
+    Flac,
+    Ogg,
+}
+
+impl Container {
+    fn matches_extension(self, ext: &str) -> bool {
+        match self {
+            Container::IsoMedia => matches!(ext, "mp4" | "mov" | "m4a" | "m4v"),
+            Container::Matroska => matches!(ext, "mkv" | "webm"),
+            Container::Avi => ext == "avi",
+            Container::Wave => ext == "wav",
+            Container::Mpeg => matches!(ext, "mp3" | "aac"),
+            Container::Flac => ext == "flac",
+            Container::Ogg => ext == "ogg",
+        }
+    }
+}
+
+pub fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+pub fn has_media_extension(path: &Path) -> bool {
+    extension(path).is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Identify the container from its magic bytes.
+pub fn probe(path: &Path) -> Option<Container> {
+    let mut header = [0u8; HEADER_BYTES];
+    let mut file = File::open(path).ok()?;
+    let read = file.read(&mut header).ok()?;
+    let header = &header[..read];
+
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+    if [b"ftyp", b"moov", b"mdat", b"free", b"wide"]
+        .iter()
+        .any(|atom| at(4, *atom))
+    {
+        return Some(Container::IsoMedia);
+    }
+    if at(0, &[0x1a, 0x45, 0xdf, 0xa3]) {
+        return Some(Container::Matroska);
+    }
+    if at(0, b"RIFF") && at(8, b"AVI ") {
+        return Some(Container::Avi);
+    }
+    if at(0, b"RIFF") && at(8, b"WAVE") {
+        return Some(Container::Wave);
+    }
+    if at(0, b"fLaC") {
+        return Some(Container::Flac);
+    }
+    if at(0, b"OggS") {
+        return Some(Container::Ogg);
+    }
+    // ID3 tag, or a bare MPEG audio / ADTS frame sync
+    if at(0, b"ID3") || (header.len() >= 2 && header[0] == 0xff && header[1] & 0xe0 == 0xe0) {
+        return Some(Container::Mpeg);
+    }
+    None
+}
+
+/// Check that `path` is a media file whose contents match its extension.
+/// Returns the absolute path.
+pub fn validate(path: &Path) -> Result<PathBuf, String> {
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    let ext = extension(&path).unwrap_or_default();
+    if !MEDIA_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(format!(
+            "{} is not a supported audio or video file",
+            path.display()
+        ));
+    }
+    match probe(&path) {
+        Some(container) if container.matches_extension(&ext) => Ok(path),
+        Some(container) => Err(format!(
+            "{} looks like {:?}, not .{}",
+            path.display(),
+            container,
+            ext
+        )),
+        None => Err(format!("{} is not a recognised media file", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use std::fs;
+
+    fn file(dir: &Path, name: &str, header: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, header).unwrap();
+        path
+    }
+
+    #[test]
+    fn probe_recognises_magic_bytes() {
+        let dir = scratch_dir("media", "probe");
+        let cases: [(&[u8], Container); 11] = [
+            (b"\0\0\0\x20ftypisom", Container::IsoMedia),
+            (b"\0\0\0\x08moov", Container::IsoMedia),
+            (b"\0\0\0\x08wide\0\0\0\x08mdat", Container::IsoMedia),
+            (&[0x1a, 0x45, 0xdf, 0xa3, 0x9f], Container::Matroska),
+            (b"RIFF\x24\0\0\0AVI LIST", Container::Avi),
+            (b"RIFF\x24\0\0\0WAVEfmt ", Container::Wave),
+            (b"fLaC\0\0\0\x22", Container::Flac),
+            (b"OggS\0\x02", Container::Ogg),
+            (b"ID3\x04\0\0", Container::Mpeg),
+            (&[0xff, 0xfb, 0x90, 0x64], Container::Mpeg),
+            (&[0xff, 0xf1, 0x50, 0x80], Container::Mpeg),
+        ];
+        for (i, (header, container)) in cases.iter().enumerate() {
+            let path = file(&dir, &format!("{}.bin", i), header);
+            assert_eq!(probe(&path), Some(*container), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn probe_rejects_unknown_and_short_files() {
+        let dir = scratch_dir("media", "unknown");
+        assert_eq!(probe(&file(&dir, "empty", b"")), None);
+        assert_eq!(probe(&file(&dir, "text", b"hello, world")), None);
+        assert_eq!(probe(&file(&dir, "riff", b"RIFF\0\0\0\0WEBP")), None);
+        // Too short to reach the atom type at offset 4
+        assert_eq!(probe(&file(&dir, "short", b"\0\0\0ft")), None);
+        assert_eq!(probe(&file(&dir, "sync", &[0xff, 0x00])), None);
+        assert_eq!(probe(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn media_extension_is_case_insensitive() {
+        assert!(has_media_extension(Path::new("talk.mp4")));
+        assert!(has_media_extension(Path::new("/tmp/Lecture.MKV")));
+        assert!(has_media_extension(Path::new("voice.M4a")));
+        assert!(!has_media_extension(Path::new("notes.txt")));
+        assert!(!has_media_extension(Path::new("mp4")));
+        assert!(!has_media_extension(Path::new(".mp4.part")));
+        assert_eq!(extension(Path::new("a.b.WebM")).as_deref(), Some("webm"));
+    }
+
+    #[test]
+    fn every_media_extension_has_a_container() {
+        let containers = [
+            Container::IsoMedia,
+            Container::Matroska,
+            Container::Avi,
+            Container::Wave,
+            Container::Mpeg,
+            Container::Flac,
+            Container::Ogg,
+        ];
+        for ext in MEDIA_EXTENSIONS {
+            assert!(
+                containers.iter().any(|c| c.matches_extension(ext)),
+                "{}",
+                ext
+            );
+        }
+    }
+
+    #[test]
+    fn validate_checks_extension_against_contents() {
+        let dir = scratch_dir("media", "validate");
+        let wav = file(&dir, "clip.WAV", b"RIFF\x24\0\0\0WAVEfmt ");
+        assert_eq!(validate(&wav), Ok(wav.canonicalize().unwrap()));
+        // M4A is an MP4 container under another name
+        assert!(validate(&file(&dir, "voice.m4a", b"\0\0\0\x20ftypM4A ")).is_ok());
+
+        let renamed = file(&dir, "movie.mkv", b"\0\0\0\x20ftypisom");
+        assert!(validate(&renamed)
+            .unwrap_err()
+            .contains("IsoMedia, not .mkv"));
+        let text = file(&dir, "notes.txt", b"RIFF\x24\0\0\0WAVEfmt ");
+        assert!(validate(&text).unwrap_err().contains("not a supported"));
+        let garbage = file(&dir, "broken.mp3", b"not audio");
+        assert!(validate(&garbage).unwrap_err().contains("not a recognised"));
+        let folder = dir.join("folder.mp4");
+        fs::create_dir_all(&folder).unwrap();
+        assert!(validate(&folder).unwrap_err().contains("is not a file"));
+        assert!(validate(&dir.join("missing.mp4")).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+const REGISTRY_FILE: &str = "sidecars.json";
+
+// Name fragment every sidecar executable carries (api-server-<target triple>)
+const SIDECAR_NAME: &str = "api-server";
+
+/// A sidecar process this app started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SidecarRecord {
+    pub pid: u32,
+    /// PID of the server itself, reported once it answers. Under a one-file
+    /// build `pid` is only the bootloader that unpacked it.
+    #[serde(default)]
+    pub server_pid: Option<u32>,
+    /// PID of the launcher that owns the sidecar.
+    pub launcher_pid: u32,
+    pub started_at: u64,
+}
+
+/// PID file in the app data dir listing the sidecars we started, so stale
+/// ones can be cleaned up without touching unrelated processes.
+pub struct ProcessRegistry {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ProcessRegistry {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(REGISTRY_FILE),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, pid: u32) {
+        let _guard = self.lock.lock().unwrap();
+        let mut records = self.load();
+        records.retain(|r| r.pid != pid);
+        records.push(SidecarRecord {
+            pid,
+            server_pid: None,
+            launcher_pid: std::process::id(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+        self.save(&records);
+    }
+
+    pub fn set_server_pid(&self, pid: u32, server_pid: u32) {
+        let _guard = self.lock.lock().unwrap();
+        let mut records = self.load();
+        if let Some(record) = records.iter_mut().find(|r| r.pid == pid) {
+            record.server_pid = Some(server_pid);
+            self.save(&records);
+        }
+    }
+
+    pub fn remove(&self, pid: u32) {
+        let _guard = self.lock.lock().unwrap();
+        let mut records = self.load();
+        records.retain(|r| r.pid != pid);
+        self.save(&records);
+    }
+
+    /// Kill sidecars left behind by launchers that are no longer running.
+    /// Sidecars whose launcher is still alive belong to another instance
+    /// and are left alone.
+    pub fn reap_stale(&self) {
+        let _guard = self.lock.lock().unwrap();
+        let mut kept = Vec::new();
+        for record in self.load() {
+            if record.launcher_pid != std::process::id() && is_alive(record.launcher_pid) {
+                kept.push(record);
+                continue;
+            }
+            if let Some(server_pid) = record.server_pid {
+                kill_server(server_pid);
+            }
+            if is_sidecar(record.pid) {
+                info!("Killing stale sidecar (PID {})", record.pid);
+                force_kill(record.pid);
+            }
+        }
+        self.save(&kept);
+    }
+
+    fn load(&self) -> Vec<SidecarRecord> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, records: &[SidecarRecord]) {
+        if records.is_empty() {
+            let _ = fs::remove_file(&self.path);
+            return;
+        }
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        match serde_json::to_string_pretty(records) {
+            Ok(json) => {
+                if let Err(e) = fs::write(&self.path, json) {
+                    warn!("Failed to write {}: {}", self.path.display(), e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize sidecar records: {}", e),
+        }
+    }
+}
+
+/// Name of the executable running as `pid`, if any.
+fn process_name(pid: u32) -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        let output = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output()
+            .ok()?;
+        // "api-server.exe","1234","Console","1","123,456 K"
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let name = stdout.split(',').next()?.trim().trim_matches('"');
+        if name.is_empty() || name.starts_with("INFO:") {
+            return None;
+        }
+        Some(name.to_string())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let output = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "comm="])
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+}
+
+fn is_alive(pid: u32) -> bool {
+    process_name(pid).is_some()
+}
+
+/// Guards against PID reuse: only treat `pid` as ours if it still runs the
+/// sidecar executable.
+fn is_sidecar(pid: u32) -> bool {
+    process_name(pid).is_some_and(|name| name.contains(SIDECAR_NAME))
+}
+
+/// Kill the sidecar's server process and everything it started. The server
+/// leads its own process group, so ffmpeg and yt-dlp go with it.
+pub fn kill_server(server_pid: u32) {
+    if !is_sidecar(server_pid) {
+        return;
+    }
+    info!("Killing sidecar server (PID {})", server_pid);
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", server_pid)])
+            .output();
+    }
+    // Also on its own, in case it couldn't start a group
+    force_kill(server_pid);
+}
+
+fn force_kill(pid: u32) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .output();
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = Command::new("kill")
+            .args(["-KILL", &pid.to_string()])
+            .output();
+    }
+}
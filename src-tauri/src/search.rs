@@ -0,0 +1,432 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::export::{read_report, read_segments, ABSTRACT_FILE, REPORT_FILE, TRANSCRIPT_FILE};
+use crate::history::{HistoryStore, Session};
+
+const SEARCH_DB_FILE: &str = "search.db";
+const DEFAULT_LIMIT: usize = 50;
+
+// The trigram tokenizer matches substrings, which also works for CJK text
+// that unicode61 would treat as one long token. It needs 3+ characters.
+const MIN_TRIGRAM_CHARS: usize = 3;
+
+// The index is rebuilt from scratch whenever this changes
+const SCHEMA_VERSION: i32 = 2;
+
+const SCHEMA: &str = "
+CREATE VIRTUAL TABLE IF NOT EXISTS notes USING fts5(
+    session_id UNINDEXED,
+    kind UNINDEXED,
+    start UNINDEXED,
+    end UNINDEXED,
+    body,
+    tokenize = 'trigram'
+);
+CREATE TABLE IF NOT EXISTS indexed (
+    session_id TEXT PRIMARY KEY,
+    fingerprint TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT ''
+);
+";
+
+/// Which part of a session a hit came from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HitKind {
+    Title,
+    Report,
+    Abstract,
+    Segment,
+}
+
+impl HitKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            HitKind::Title => "title",
+            HitKind::Report => "report",
+            HitKind::Abstract => "abstract",
+            HitKind::Segment => "segment",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "title" => HitKind::Title,
+            "report" => HitKind::Report,
+            "abstract" => HitKind::Abstract,
+            _ => HitKind::Segment,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub session_id: String,
+    pub kind: HitKind,
+    /// Segment start in seconds, for jumping to that moment.
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    pub snippet: String,
+    /// Lower is better: FTS5 bm25, or minus the number of matches for
+    /// terms too short for the index.
+    pub rank: f64,
+}
+
+/// Full-text index over session titles, reports, abstracts and transcript
+/// segments, kept in sync with the history store on demand.
+pub struct SearchIndex {
+    conn: Mutex<Connection>,
+}
+
+impl SearchIndex {
+    pub fn open(data_dir: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(data_dir.join(SEARCH_DB_FILE))?;
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            conn.execute_batch("DROP TABLE IF EXISTS notes; DROP TABLE IF EXISTS indexed;")?;
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Reindex sessions whose files changed since the last refresh and drop
+    /// sessions that no longer exist.
+    pub fn refresh(&self, store: &HistoryStore) -> Result<(), String> {
+        let sessions = store.list().map_err(|e| e.to_string())?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        let known: Vec<String> = {
+            let mut stmt = tx
+                .prepare("SELECT session_id FROM indexed")
+                .map_err(|e| e.to_string())?;
+            let ids = stmt
+                .query_map([], |row| row.get(0))
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+                .map_err(|e| e.to_string())?;
+            ids
+        };
+        for id in known
+            .iter()
+            .filter(|id| !sessions.iter().any(|s| &s.id == *id))
+        {
+            remove(&tx, id).map_err(|e| e.to_string())?;
+        }
+
+        let mut reindexed = 0;
+        for session in &sessions {
+            let fingerprint = fingerprint(session);
+            let current: Option<String> = tx
+                .query_row(
+                    "SELECT fingerprint FROM indexed WHERE session_id = ?1",
+                    [&session.id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?;
+            if current.as_deref() == Some(fingerprint.as_str()) {
+                continue;
+            }
+            remove(&tx, &session.id).map_err(|e| e.to_string())?;
+            index_session(&tx, session).map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO indexed (session_id, fingerprint, created_at) VALUES (?1, ?2, ?3)",
+                params![session.id, fingerprint, created_key(&session.timestamp)],
+            )
+            .map_err(|e| e.to_string())?;
+            reindexed += 1;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        if reindexed > 0 {
+            info!("Search index: reindexed {} sessions", reindexed);
+        }
+        Ok(())
+    }
+
+    /// Ranked hits for every whitespace-separated term in `query`.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+
+        // Short terms can't use the trigram index, so fall back to LIKE
+        let hits = if terms.iter().all(|t| t.chars().count() >= MIN_TRIGRAM_CHARS) {
+            let fts_query = terms
+                .iter()
+                .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut stmt = conn
+                .prepare(
+                    "SELECT session_id, kind, start, end,
+                            snippet(notes, 4, '[', ']', '…', 16), bm25(notes)
+                     FROM notes WHERE notes MATCH ?1
+                     ORDER BY bm25(notes) LIMIT ?2",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![fts_query, limit as i64], |row| {
+                    Ok(SearchHit {
+                        session_id: row.get(0)?,
+                        kind: HitKind::parse(&row.get::<_, String>(1)?),
+                        start: row.get(2)?,
+                        end: row.get(3)?,
+                        snippet: row.get(4)?,
+                        rank: row.get(5)?,
+                    })
+                })
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+                .map_err(|e| e.to_string())?;
+            rows
+        } else {
+            // Most matches first, then the newest session
+            let clauses = vec!["n.body LIKE ? ESCAPE '\\'"; terms.len()].join(" AND ");
+            let matches = vec![
+                    "(length(lower(n.body)) - length(replace(lower(n.body), ?, ''))) / length(?)";
+                    terms.len()
+                ]
+            .join(" + ");
+            let sql = format!(
+                "SELECT n.session_id, n.kind, n.start, n.end, n.body, {} AS matches
+                 FROM notes n JOIN indexed i ON i.session_id = n.session_id
+                 WHERE {}
+                 ORDER BY matches DESC, i.created_at DESC, n.rowid
+                 LIMIT {}",
+                matches, clauses, limit
+            );
+            // SQLite's lower() and LIKE only fold ASCII
+            let lowered: Vec<String> = terms.iter().map(|t| t.to_ascii_lowercase()).collect();
+            let patterns = terms.iter().map(|t| {
+                let escaped = t
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                format!("%{}%", escaped)
+            });
+            let params: Vec<String> = lowered
+                .iter()
+                .flat_map(|t| [t.clone(), t.clone()])
+                .chain(patterns)
+                .collect();
+            let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                    let body: String = row.get(4)?;
+                    Ok(SearchHit {
+                        session_id: row.get(0)?,
+                        kind: HitKind::parse(&row.get::<_, String>(1)?),
+                        start: row.get(2)?,
+                        end: row.get(3)?,
+                        snippet: excerpt(&body, terms[0]),
+                        rank: -row.get::<_, f64>(5)?,
+                    })
+                })
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+                .map_err(|e| e.to_string())?;
+            rows
+        };
+        Ok(hits)
+    }
+}
+
+fn remove(conn: &Connection, session_id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM notes WHERE session_id = ?1", [session_id])?;
+    conn.execute("DELETE FROM indexed WHERE session_id = ?1", [session_id])?;
+    Ok(())
+}
+
+fn insert(
+    conn: &Connection,
+    session_id: &str,
+    kind: HitKind,
+    span: Option<(f64, f64)>,
+    body: &str,
+) -> rusqlite::Result<()> {
+    if body.trim().is_empty() {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO notes (session_id, kind, start, end, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            session_id,
+            kind.as_str(),
+            span.map(|s| s.0),
+            span.map(|s| s.1),
+            body
+        ],
+    )?;
+    Ok(())
+}
+
+fn index_session(conn: &Connection, session: &Session) -> rusqlite::Result<()> {
+    let dir = Path::new(&session.project_dir);
+    insert(conn, &session.id, HitKind::Title, None, &session.title)?;
+
+    let report = read_report(session);
+    insert(conn, &session.id, HitKind::Report, None, &report)?;
+    if let Ok(abstract_md) = fs::read_to_string(dir.join(ABSTRACT_FILE)) {
+        insert(conn, &session.id, HitKind::Abstract, None, &abstract_md)?;
+    }
+
+    // Sessions from before transcript.json only have the joined text
+    match read_segments(&dir.join(TRANSCRIPT_FILE)) {
+        Some(segments) => {
+            for segment in segments {
+                let span = Some((segment.start, segment.end));
+                insert(conn, &session.id, HitKind::Segment, span, &segment.text)?;
+            }
+        }
+        None => insert(
+            conn,
+            &session.id,
+            HitKind::Segment,
+            None,
+            &session.transcript,
+        )?,
+    }
+    Ok(())
+}
+
+/// Changes whenever the title, folder or any indexed file changes.
+fn fingerprint(session: &Session) -> String {
+    let dir = Path::new(&session.project_dir);
+    let mtimes: Vec<String> = [REPORT_FILE, ABSTRACT_FILE, TRANSCRIPT_FILE]
+        .iter()
+        .map(|name| {
+            fs::metadata(dir.join(name))
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis().to_string())
+                .unwrap_or_default()
+        })
+        .collect();
+    format!(
+        "{}\u{1f}{}\u{1f}{}\u{1f}{}",
+        session.title,
+        session.project_dir,
+        session.summary.len(),
+        mtimes.join(",")
+    )
+}
+
+// Session timestamps are ISO 8601 or %Y%m%d_%H%M%S; their digits sort the same
+fn created_key(timestamp: &str) -> String {
+    timestamp.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// A short window of `body` around the first match of `term`.
+fn excerpt(body: &str, term: &str) -> String {
+    const CONTEXT_CHARS: usize = 40;
+    let lower = body.to_lowercase();
+    let Some(byte_pos) = lower.find(&term.to_lowercase()) else {
+        return body.chars().take(CONTEXT_CHARS * 2).collect();
+    };
+    // to_lowercase can shift byte offsets, so work in characters
+    let char_pos = lower[..byte_pos].chars().count();
+    let start = char_pos.saturating_sub(CONTEXT_CHARS);
+    let chars: Vec<char> = body.chars().collect();
+    let end = (char_pos + term.chars().count() + CONTEXT_CHARS).min(chars.len());
+    let mut snippet: String = chars[start.min(end)..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Search titles, reports, abstracts and transcripts. Segment hits carry
+/// the timestamp to jump to.
+#[tauri::command]
+pub async fn search_notes(
+    app: AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let index = app.state::<SearchIndex>();
+        index.refresh(&app.state::<HistoryStore>())?;
+        index.search(&query, limit.unwrap_or(DEFAULT_LIMIT))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{scratch_dir, ScratchDir};
+
+    fn scratch_index(name: &str) -> (ScratchDir, SearchIndex) {
+        let dir = scratch_dir("search", name);
+        let index = SearchIndex::open(&dir).unwrap();
+        (dir, index)
+    }
+
+    fn add(index: &SearchIndex, session_id: &str, timestamp: &str, body: &str) {
+        let conn = index.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO indexed (session_id, fingerprint, created_at) VALUES (?1, '', ?2)",
+            params![session_id, created_key(timestamp)],
+        )
+        .unwrap();
+        insert(&conn, session_id, HitKind::Report, None, body).unwrap();
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.session_id.as_str()).collect()
+    }
+
+    #[test]
+    fn short_queries_rank_by_match_count() {
+        let (_dir, index) = scratch_index("count");
+        add(&index, "one", "20240101_090000", "AI once");
+        add(&index, "three", "20240101_090000", "ai, AI and more ai");
+        add(&index, "two", "20240101_090000", "ai twice: ai");
+        add(&index, "none", "20240101_090000", "nothing here");
+        let hits = index.search("ai", 10).unwrap();
+        assert_eq!(ids(&hits), ["three", "two", "one"]);
+        assert_eq!(hits[0].rank, -3.0);
+    }
+
+    #[test]
+    fn short_query_ties_prefer_newer_sessions() {
+        let (_dir, index) = scratch_index("recent");
+        add(&index, "old", "20230105_120000", "an ai note");
+        add(&index, "iso", "2024-03-01T08:00:00", "an ai note");
+        add(&index, "new", "20240601_120000", "an ai note");
+        let hits = index.search("ai", 10).unwrap();
+        assert_eq!(ids(&hits), ["new", "iso", "old"]);
+        assert_eq!(ids(&index.search("ai", 2).unwrap()), ["new", "iso"]);
+    }
+
+    #[test]
+    fn short_queries_match_like_wildcards_literally() {
+        let (_dir, index) = scratch_index("escape");
+        add(&index, "pct", "20240101_090000", "50% done");
+        add(&index, "plain", "20240101_090000", "500 done");
+        assert_eq!(ids(&index.search("0%", 10).unwrap()), ["pct"]);
+    }
+
+    #[test]
+    fn created_key_sorts_both_timestamp_formats() {
+        assert_eq!(created_key("20240501_093000"), "20240501093000");
+        assert_eq!(created_key("2024-05-01T09:30:00"), "20240501093000");
+        assert!(created_key("20240502_000000") > created_key("2024-05-01T23:59:59"));
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use log::{info, warn};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::config::ConfigStore;
+use crate::supervisor::Supervisor;
+
+/// Settings kept out of user_config.json.
+pub const SECRET_KEYS: [&str; 3] = ["api_key", "cookies_yt", "cookies_bili"];
+
+/// Prefix of sidecar stdout lines carrying secrets the user changed in the
+/// settings dialog. These lines are consumed, never logged.
+pub const SIDECAR_UPDATE_PREFIX: &str = "@@oan-secrets ";
+
+// Encrypted-file fallback, in the app config dir
+const KEY_FILE: &str = "secrets.key";
+const VAULT_FILE: &str = "secrets.enc";
+const KEY_BYTES: usize = 32;
+const NONCE_BYTES: usize = 12;
+
+enum Backend {
+    /// macOS Keychain, Windows Credential Manager or the Secret Service.
+    Keyring { service: String },
+    /// Linux desktops without a Secret Service.
+    File(EncryptedFile),
+}
+
+/// The API key and site cookies, kept in the platform credential store.
+/// The sidecar gets them over its stdin, so they never touch the disk.
+pub struct SecretStore {
+    backend: Backend,
+    // Read once at startup so the keychain isn't queried on every spawn
+    values: Mutex<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SecretStatus {
+    pub backend: &'static str,
+    /// Which secrets have a value; the values themselves are not exposed.
+    pub stored: Vec<String>,
+}
+
+impl SecretStore {
+    pub fn open(service: &str, config_dir: &Path) -> Self {
+        let backend = if keyring_usable(service) {
+            Backend::Keyring {
+                service: service.to_string(),
+            }
+        } else {
+            match EncryptedFile::open(config_dir) {
+                Ok(file) => Backend::File(file),
+                Err(e) => {
+                    // Nothing to fall back to; writes will fail and say so
+                    warn!("Failed to open the secrets file: {}", e);
+                    Backend::Keyring {
+                        service: service.to_string(),
+                    }
+                }
+            }
+        };
+        let values = match &backend {
+            Backend::Keyring { service } => load_keyring(service),
+            Backend::File(file) => file.load(),
+        };
+        let store = Self {
+            backend,
+            values: Mutex::new(values),
+        };
+        info!("Secrets: {}", store.backend_name());
+        store
+    }
+
+    fn backend_name(&self) -> &'static str {
+        match self.backend {
+            Backend::Keyring { .. } => "keychain",
+            Backend::File(_) => "encrypted file",
+        }
+    }
+
+    pub fn status(&self) -> SecretStatus {
+        SecretStatus {
+            backend: self.backend_name(),
+            stored: self.values.lock().unwrap().keys().cloned().collect(),
+        }
+    }
+
+    /// Store `value` under `key`; an empty value deletes it.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        if !SECRET_KEYS.contains(&key) {
+            return Err(format!("Unknown secret: {}", key));
+        }
+        let mut values = self.values.lock().unwrap();
+        if values.get(key).map(String::as_str).unwrap_or("") == value {
+            return Ok(());
+        }
+        let mut updated = values.clone();
+        if value.is_empty() {
+            updated.remove(key);
+        } else {
+            updated.insert(key.to_string(), value.to_string());
+        }
+        match &self.backend {
+            Backend::Keyring { service } => {
+                let entry = keyring::Entry::new(service, key).map_err(|e| e.to_string())?;
+                let result = if value.is_empty() {
+                    entry.delete_credential()
+                } else {
+                    entry.set_password(value)
+                };
+                match result {
+                    Ok(()) | Err(keyring::Error::NoEntry) => {}
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            Backend::File(file) => file.save(&updated)?,
+        }
+        *values = updated;
+        Ok(())
+    }
+
+    /// The first line written to the sidecar's stdin.
+    pub fn sidecar_message(&self) -> String {
+        let values = self.values.lock().unwrap();
+        let secrets: BTreeMap<&str, &str> = SECRET_KEYS
+            .iter()
+            .map(|key| (*key, values.get(*key).map(String::as_str).unwrap_or("")))
+            .collect();
+        let mut line = serde_json::json!({ "secrets": secrets }).to_string();
+        line.push('\n');
+        line
+    }
+
+    /// Store secrets sent back by the sidecar after the user edited them.
+    pub fn apply_sidecar_update(&self, payload: &str) {
+        let updates: BTreeMap<String, String> = match serde_json::from_str(payload) {
+            Ok(updates) => updates,
+            Err(e) => {
+                warn!("Ignoring malformed secrets update: {}", e);
+                return;
+            }
+        };
+        for (key, value) in updates {
+            match self.set(&key, &value) {
+                Ok(()) => info!("Secret updated: {}", key),
+                Err(e) => warn!("Failed to store {}: {}", key, e),
+            }
+        }
+    }
+}
+
+// Only these platforms have a real store; elsewhere keyring uses a mock
+// that forgets everything on exit
+fn keyring_usable(service: &str) -> bool {
+    if !cfg!(any(
+        target_os = "macos",
+        target_os = "windows",
+        target_os = "linux"
+    )) {
+        return false;
+    }
+    match keyring::Entry::new(service, SECRET_KEYS[0]).and_then(|e| e.get_password()) {
+        Ok(_) | Err(keyring::Error::NoEntry) => true,
+        Err(e) => {
+            info!("No usable keychain ({}), using an encrypted file", e);
+            false
+        }
+    }
+}
+
+fn load_keyring(service: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    for key in SECRET_KEYS {
+        match keyring::Entry::new(service, key).and_then(|e| e.get_password()) {
+            Ok(value) => {
+                values.insert(key.to_string(), value);
+            }
+            Err(keyring::Error::NoEntry) => {}
+            Err(e) => warn!("Failed to read {} from the keychain: {}", key, e),
+        }
+    }
+    values
+}
+
+/// ChaCha20-Poly1305 file keyed by a random per-user key file mixed with
+/// the machine id. Keeps secrets out of backups and copied libraries; it
+/// does not protect against other programs running as the same user.
+struct EncryptedFile {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl EncryptedFile {
+    fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let key_path = dir.join(KEY_FILE);
+        let key_file = match fs::read(&key_path) {
+            Ok(key) if key.len() == KEY_BYTES => key,
+            _ => {
+                let key = random_bytes(KEY_BYTES);
+                write_private(&key_path, &key)?;
+                key
+            }
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(b"openautonote-secrets");
+        hasher.update(&key_file);
+        hasher.update(machine_id());
+        let key = hasher.finalize();
+        Ok(Self {
+            path: dir.join(VAULT_FILE),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        })
+    }
+
+    fn load(&self) -> BTreeMap<String, String> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(_) => return BTreeMap::new(),
+        };
+        if data.len() < NONCE_BYTES {
+            warn!("Ignoring truncated {}", self.path.display());
+            return BTreeMap::new();
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_BYTES);
+        let plain = match self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext) {
+            Ok(plain) => plain,
+            Err(_) => {
+                // Key file replaced or the library copied from another machine
+                warn!("Failed to decrypt {}", self.path.display());
+                return BTreeMap::new();
+            }
+        };
+        serde_json::from_slice(&plain).unwrap_or_default()
+    }
+
+    fn save(&self, values: &BTreeMap<String, String>) -> Result<(), String> {
+        let plain = serde_json::to_vec(values).map_err(|e| e.to_string())?;
+        let nonce = random_bytes(NONCE_BYTES);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plain.as_slice())
+            .map_err(|e| e.to_string())?;
+        let mut data = nonce;
+        data.extend_from_slice(&ciphertext);
+        let tmp = self.path.with_extension("enc.tmp");
+        write_private(&tmp, &data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+}
+
+fn machine_id() -> Vec<u8> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| fs::read(path).ok())
+        .map(|id| String::from_utf8_lossy(&id).trim().as_bytes().to_vec())
+        .unwrap_or_default()
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    getrandom::getrandom(&mut buf).expect("OS random number generator unavailable");
+    buf
+}
+
+/// Write a file only the current user can read.
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)
+}
+
+/// Move secrets that older versions kept in user_config.json into the
+/// store, then drop them from the file.
+pub fn import_from_config(secrets: &SecretStore, config: &ConfigStore, dir: &Path) {
+    let found = match config.raw_values(dir, &SECRET_KEYS) {
+        Ok(found) => found,
+        Err(e) => {
+            warn!("Failed to read secrets from the config: {}", e);
+            return;
+        }
+    };
+    if found.is_empty() {
+        return;
+    }
+    for (key, value) in &found {
+        let value = value.as_str().unwrap_or("");
+        if value.is_empty() {
+            continue;
+        }
+        if let Err(e) = secrets.set(key, value) {
+            // Leave the file alone so nothing is lost
+            warn!("Failed to store {}: {}", key, e);
+            return;
+        }
+    }
+    match config.remove_keys(dir, &SECRET_KEYS) {
+        Ok(()) => info!("Moved secrets out of user_config.json"),
+        Err(e) => warn!("Failed to remove secrets from the config: {}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_secret_status(secrets: State<'_, SecretStore>) -> SecretStatus {
+    secrets.status()
+}
+
+/// Store a secret and hand the new value to the running sidecar.
+#[tauri::command]
+pub fn set_secret(app: AppHandle, key: String, value: String) -> Result<(), String> {
+    let secrets = app.state::<SecretStore>();
+    secrets.set(&key, &value)?;
+    if let Err(e) = app
+        .state::<Supervisor>()
+        .write_stdin(secrets.sidecar_message().as_bytes())
+    {
+        warn!("Failed to send secrets to the sidecar: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+    use serde_json::{json, Value};
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn file_store(dir: &Path) -> SecretStore {
+        SecretStore {
+            backend: Backend::File(EncryptedFile::open(dir).unwrap()),
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    #[test]
+    fn encrypted_file_round_trips() {
+        let dir = scratch_dir("secrets", "round-trip");
+        let secrets = values(&[("api_key", "sk-123"), ("cookies_yt", "SID=abc")]);
+        EncryptedFile::open(&dir).unwrap().save(&secrets).unwrap();
+        assert_eq!(EncryptedFile::open(&dir).unwrap().load(), secrets);
+
+        let data = fs::read(dir.join(VAULT_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("sk-123"));
+        assert!(!dir.join("secrets.enc.tmp").exists());
+    }
+
+    #[test]
+    fn encrypted_file_with_another_key_reads_as_empty() {
+        let dir = scratch_dir("secrets", "wrong-key");
+        let file = EncryptedFile::open(&dir).unwrap();
+        file.save(&values(&[("api_key", "sk-123")])).unwrap();
+        fs::write(dir.join(KEY_FILE), [7u8; KEY_BYTES]).unwrap();
+        assert!(EncryptedFile::open(&dir).unwrap().load().is_empty());
+
+        // A truncated file is ignored too
+        fs::write(dir.join(VAULT_FILE), [0u8; 4]).unwrap();
+        assert!(file.load().is_empty());
+    }
+
+    #[test]
+    fn import_moves_secrets_out_of_the_config() {
+        let dir = scratch_dir("secrets", "import");
+        fs::write(
+            dir.join("user_config.json"),
+            r#"{"api_key": "sk-123", "cookies_yt": "", "model_name": "gpt-4o"}"#,
+        )
+        .unwrap();
+        let secrets = file_store(&dir);
+        let config = ConfigStore::default();
+        import_from_config(&secrets, &config, &dir);
+
+        assert_eq!(secrets.status().stored, ["api_key"]);
+        assert_eq!(
+            EncryptedFile::open(&dir).unwrap().load(),
+            values(&[("api_key", "sk-123")])
+        );
+        let left = config.raw_values(&dir, &["api_key", "cookies_yt", "model_name"]);
+        assert_eq!(
+            Value::Object(left.unwrap()),
+            json!({"model_name": "gpt-4o"})
+        );
+    }
+
+    #[test]
+    fn import_keeps_the_config_when_storing_fails() {
+        let dir = scratch_dir("secrets", "import-fails");
+        let config_file = r#"{"api_key": "sk-123", "model_name": "gpt-4o"}"#;
+        fs::write(dir.join("user_config.json"), config_file).unwrap();
+        let secrets = file_store(&dir);
+        // The temp file `save` writes can't be created
+        fs::create_dir_all(dir.join("secrets.enc.tmp")).unwrap();
+        import_from_config(&secrets, &ConfigStore::default(), &dir);
+
+        assert!(secrets.status().stored.is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.join("user_config.json")).unwrap(),
+            config_file
+        );
+    }
+}
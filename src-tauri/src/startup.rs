@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::supervisor::Supervisor;
+
+// Event the splash window listens for
+const STARTUP_EVENT: &str = "startup";
+const SPLASH_WINDOW: &str = "splashscreen";
+
+/// Milestones of the first sidecar start, in the order they are reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupStage {
+    SidecarSpawned,
+    PortBound,
+    ModelsScanned,
+    Ready,
+}
+
+impl StartupStage {
+    pub fn percent(self) -> u8 {
+        match self {
+            StartupStage::SidecarSpawned => 25,
+            StartupStage::PortBound => 50,
+            StartupStage::ModelsScanned => 80,
+            StartupStage::Ready => 100,
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            StartupStage::SidecarSpawned => StartupStage::PortBound,
+            StartupStage::PortBound => StartupStage::ModelsScanned,
+            StartupStage::ModelsScanned | StartupStage::Ready => StartupStage::Ready,
+        }
+    }
+}
+
+/// What the splash window should show.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StartupStatus {
+    Progress {
+        stage: StartupStage,
+        percent: u8,
+        message: String,
+    },
+    /// `stage` is the first stage that was not reached.
+    Failed {
+        stage: StartupStage,
+        message: String,
+        stderr: Vec<String>,
+    },
+}
+
+/// A status as sent to the splash window. `seq` grows with every update,
+/// so the page can drop a snapshot that arrives after a newer event.
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupUpdate {
+    seq: u64,
+    #[serde(flatten)]
+    status: StartupStatus,
+}
+
+/// Last startup status, kept so a splash page that loads after an event was
+/// emitted can still catch up.
+#[derive(Default)]
+pub struct StartupReporter {
+    reached: Mutex<Option<StartupStage>>,
+    status: Mutex<Option<StartupUpdate>>,
+    seq: AtomicU64,
+}
+
+impl StartupReporter {
+    fn publish(&self, app: &AppHandle, status: StartupStatus) {
+        // Held while emitting, so events go out in `seq` order
+        let mut latest = self.status.lock().unwrap();
+        let update = StartupUpdate {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            status,
+        };
+        let _ = app.emit_to(SPLASH_WINDOW, STARTUP_EVENT, &update);
+        *latest = Some(update);
+    }
+}
+
+/// Report that `stage` has been reached.
+pub fn progress(app: &AppHandle, stage: StartupStage, message: impl Into<String>) {
+    let reporter = app.state::<StartupReporter>();
+    *reporter.reached.lock().unwrap() = Some(stage);
+    reporter.publish(
+        app,
+        StartupStatus::Progress {
+            stage,
+            percent: stage.percent(),
+            message: message.into(),
+        },
+    );
+}
+
+/// Replace the splash message without advancing the stage.
+pub fn still_waiting(app: &AppHandle, message: impl Into<String>) {
+    let reporter = app.state::<StartupReporter>();
+    let stage = reporter
+        .reached
+        .lock()
+        .unwrap()
+        .unwrap_or(StartupStage::SidecarSpawned);
+    reporter.publish(
+        app,
+        StartupStatus::Progress {
+            stage,
+            percent: stage.percent(),
+            message: message.into(),
+        },
+    );
+}
+
+/// Report that startup stalled after the last stage reached.
+pub fn fail(app: &AppHandle, message: impl Into<String>, stderr: Vec<String>) {
+    let reporter = app.state::<StartupReporter>();
+    let stage = reporter
+        .reached
+        .lock()
+        .unwrap()
+        .map_or(StartupStage::SidecarSpawned, StartupStage::next);
+    reporter.publish(
+        app,
+        StartupStatus::Failed {
+            stage,
+            message: message.into(),
+            stderr,
+        },
+    );
+}
+
+#[tauri::command]
+pub fn get_startup_status(reporter: State<'_, StartupReporter>) -> Option<StartupUpdate> {
+    reporter.status.lock().unwrap().clone()
+}
+
+/// Start the sidecar again after a failed startup.
+#[tauri::command]
+pub fn retry_startup(app: AppHandle) -> Result<(), String> {
+    let reporter = app.state::<StartupReporter>();
+    {
+        let mut status = reporter.status.lock().unwrap();
+        let failed = status
+            .as_ref()
+            .is_some_and(|update| matches!(update.status, StartupStatus::Failed { .. }));
+        if !failed {
+            return Err("Startup has not failed".to_string());
+        }
+        *status = None;
+    }
+    *reporter.reached.lock().unwrap() = None;
+    app.state::<Supervisor>().request_retry();
+    Ok(())
+}
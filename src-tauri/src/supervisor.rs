@@ -0,0 +1,584 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use tauri::async_runtime::Receiver;
+use tauri::ipc::CapabilityBuilder;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::{watch, Notify};
+
+use crate::auth::LaunchSecrets;
+use crate::health::{BackendHealth, HealthError, HealthMonitor};
+use crate::history::HistoryStore;
+use crate::launcher_config::ReadinessPolicy;
+use crate::library::Library;
+use crate::logging::SIDECAR_TARGET;
+use crate::process_registry::{self, ProcessRegistry};
+use crate::secrets::{SecretStore, SIDECAR_UPDATE_PREFIX};
+use crate::startup::{self, StartupStage};
+
+// Port used when the OS refuses to hand out an ephemeral one
+const FALLBACK_PORT: u16 = 8964;
+
+// Crash-loop policy: give up after MAX_CRASHES exits within CRASH_WINDOW
+const MAX_CRASHES: usize = 5;
+const CRASH_WINDOW: Duration = Duration::from_secs(300);
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+// How long the sidecar gets to exit after a shutdown request before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+const SHUTDOWN_PATH: &str = "/api/shutdown";
+
+// Stderr lines kept for the splash screen's failure report
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Shared handle to the running api-server sidecar.
+pub struct Supervisor {
+    port: u16,
+    child: Mutex<Option<CommandChild>>,
+    // The server's own PID, as reported by its health endpoint
+    server_pid: Mutex<Option<u32>>,
+    stopping: AtomicBool,
+    running: watch::Sender<bool>,
+    stderr_tail: Mutex<VecDeque<String>>,
+    retry: Notify,
+    restart_requested: AtomicBool,
+    // Set while the sidecar must stay down, e.g. while the library moves
+    held: watch::Sender<bool>,
+}
+
+impl Supervisor {
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            child: Mutex::new(None),
+            server_pid: Mutex::new(None),
+            stopping: AtomicBool::new(false),
+            running: watch::Sender::new(false),
+            stderr_tail: Mutex::new(VecDeque::new()),
+            retry: Notify::new(),
+            restart_requested: AtomicBool::new(false),
+            held: watch::Sender::new(false),
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Mark the sidecar as intentionally stopped so its exit is not treated
+    /// as a crash. Returns `false` if a stop was already under way.
+    pub fn begin_stop(&self) -> bool {
+        !self.stopping.swap(true, Ordering::SeqCst)
+    }
+
+    /// Kill the sidecar if it is still running. Returns the PID it was
+    /// spawned with.
+    pub fn kill(&self) -> Option<u32> {
+        let child = self.child.lock().unwrap().take()?;
+        let pid = child.pid();
+        self.kill_child(child);
+        Some(pid)
+    }
+
+    // Under a one-file build `child` is only the bootloader, and killing it
+    // leaves the server it unpacked running
+    fn kill_child(&self, child: CommandChild) {
+        if let Some(server_pid) = self.server_pid.lock().unwrap().take() {
+            process_registry::kill_server(server_pid);
+        }
+        let _ = child.kill();
+    }
+
+    /// Remember the server PID the sidecar spawned as `pid` reported.
+    fn set_server_pid(&self, pid: u32, server_pid: u32) -> bool {
+        let child = self.child.lock().unwrap();
+        if child.as_ref().is_some_and(|c| c.pid() == pid) {
+            *self.server_pid.lock().unwrap() = Some(server_pid);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Wake a startup that is waiting for the user to retry.
+    pub fn request_retry(&self) {
+        self.retry.notify_waiters();
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    fn set_child(&self, child: CommandChild) {
+        *self.child.lock().unwrap() = Some(child);
+        *self.server_pid.lock().unwrap() = None;
+        self.running.send_replace(true);
+    }
+
+    fn mark_exited(&self) {
+        self.child.lock().unwrap().take();
+        self.running.send_replace(false);
+    }
+
+    fn is_running(&self) -> bool {
+        *self.running.borrow()
+    }
+
+    /// Let a sidecar stopped by `hold` start again.
+    pub fn release(&self) {
+        self.held.send_replace(false);
+    }
+
+    fn is_held(&self) -> bool {
+        *self.held.borrow()
+    }
+
+    /// Kill sidecar `pid` so the supervise loop starts a fresh one right
+    /// away instead of counting the exit as a crash. Does nothing if that
+    /// process has already been replaced.
+    fn restart_now(&self, pid: u32) {
+        let mut child = self.child.lock().unwrap();
+        if child.as_ref().is_some_and(|c| c.pid() == pid) {
+            self.restart_requested.store(true, Ordering::SeqCst);
+            if let Some(child) = child.take() {
+                self.kill_child(child);
+            }
+        }
+    }
+
+    /// Write to the running sidecar's stdin.
+    pub fn write_stdin(&self, data: &[u8]) -> Result<(), String> {
+        match self.child.lock().unwrap().as_mut() {
+            Some(child) => child.write(data).map_err(|e| e.to_string()),
+            None => Err("The sidecar is not running".to_string()),
+        }
+    }
+
+    fn take_restart_request(&self) -> bool {
+        self.restart_requested.swap(false, Ordering::SeqCst)
+    }
+
+    fn push_stderr(&self, line: String) {
+        let mut tail = self.stderr_tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+
+    fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Ask the OS for a free loopback port for the sidecar to bind to.
+pub fn pick_free_port() -> u16 {
+    std::net::TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .unwrap_or(FALLBACK_PORT)
+}
+
+pub fn backend_url(port: u16) -> String {
+    format!("http://127.0.0.1:{}", port)
+}
+
+/// Let the sidecar's pages in the main window use IPC. Only its exact
+/// origin is trusted, not every local server, and it gets only what the UI
+/// uses: events, plus the commands in permissions/sidecar-ui.toml. The
+/// splash screen's startup commands are not among them.
+pub fn allow_ui_ipc(app: &AppHandle, port: u16) -> tauri::Result<()> {
+    app.add_capability(
+        CapabilityBuilder::new("sidecar-ui")
+            .remote(backend_url(port))
+            .local(false)
+            .window("main")
+            .permission("core:event:allow-listen")
+            .permission("core:event:allow-unlisten")
+            .permission("sidecar-ui"),
+    )
+}
+
+/// Poll the health endpoint until the sidecar answers. With `report` set,
+/// each startup stage is sent to the splash screen as it is reached.
+/// Returns `false` on timeout or if the sidecar exits first; in
+/// wait-while-alive mode the timeout only updates the splash message.
+async fn wait_until_ready(app: &AppHandle, report: bool) -> bool {
+    let monitor = app.state::<HealthMonitor>();
+    let policy = app.state::<ReadinessPolicy>();
+    let mut running = app.state::<Supervisor>().running.subscribe();
+    let deadline = Instant::now() + policy.timeout();
+    let mut port_bound = false;
+    let mut overdue = false;
+    loop {
+        let result = monitor.check().await;
+        // Any HTTP answer at all means the server is listening
+        if !port_bound && !matches!(result, Err(HealthError::Unreachable(_))) {
+            port_bound = true;
+            if report {
+                startup::progress(app, StartupStage::PortBound, "Engine is listening");
+            }
+        }
+        if let Ok(health) = result {
+            if report {
+                startup::progress(app, StartupStage::ModelsScanned, models_message(&health));
+            }
+            return true;
+        }
+        if !overdue && Instant::now() >= deadline {
+            if !policy.wait_while_alive {
+                return false;
+            }
+            overdue = true;
+            info!("Sidecar is slow to start, waiting while it is alive");
+            if report {
+                startup::still_waiting(
+                    app,
+                    "Still starting. The first launch can take a few minutes.",
+                );
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(policy.poll_interval()) => {}
+            _ = running.wait_for(|r| !*r) => return false,
+        }
+    }
+}
+
+fn models_message(health: &BackendHealth) -> String {
+    if health.model_installed {
+        format!("Speech model {} is ready", health.asr_model)
+    } else {
+        format!("Speech model {} is not downloaded yet", health.asr_model)
+    }
+}
+
+fn spawn_sidecar(
+    app: &AppHandle,
+    port: u16,
+) -> Result<(Receiver<CommandEvent>, CommandChild), String> {
+    let secrets = app.state::<LaunchSecrets>();
+    let history_db = app.state::<HistoryStore>().path();
+    let data_dir = app.state::<Library>().dir();
+    let (rx, mut child) = app
+        .shell()
+        .sidecar("api-server")
+        .map_err(|e| format!("Failed to create sidecar command: {}", e))?
+        .args(["--port", &port.to_string()])
+        .env("OAN_APP_VERSION", app.package_info().version.to_string())
+        // Line-buffer Python output so the log file keeps up with the sidecar
+        .env("PYTHONUNBUFFERED", "1")
+        .env("OAN_DATA_DIR", data_dir)
+        .env("OAN_HISTORY_DB", history_db)
+        .envs(secrets.sidecar_env())
+        // Tells the sidecar to read the API key and cookies from stdin
+        .env("OAN_SECRETS_STDIN", "1")
+        // `process_registry::kill_server` kills the sidecar's process group
+        .env("OAN_PROCESS_GROUP", "1")
+        .spawn()
+        .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+    let message = app.state::<SecretStore>().sidecar_message();
+    if let Err(e) = child.write(message.as_bytes()) {
+        warn!("Failed to send secrets to the sidecar: {}", e);
+    }
+    Ok((rx, child))
+}
+
+/// Forward sidecar output until the process exits. Returns the exit
+/// description, or `None` if the event channel closed without one.
+async fn forward_events(
+    rx: &mut Receiver<CommandEvent>,
+    supervisor: &Supervisor,
+    secrets: &SecretStore,
+) -> Option<String> {
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => {
+                let line = String::from_utf8_lossy(&line);
+                match line.strip_prefix(SIDECAR_UPDATE_PREFIX) {
+                    Some(payload) => secrets.apply_sidecar_update(payload.trim_end()),
+                    None => info!(target: SIDECAR_TARGET, "{}", line.trim_end()),
+                }
+            }
+            CommandEvent::Stderr(line) => {
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                warn!(target: SIDECAR_TARGET, "{}", line);
+                supervisor.push_stderr(line);
+            }
+            CommandEvent::Error(e) => {
+                error!(target: SIDECAR_TARGET, "{}", e);
+            }
+            CommandEvent::Terminated(payload) => {
+                return Some(format!(
+                    "code {:?}, signal {:?}",
+                    payload.code, payload.signal
+                ));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn backoff_for(crash_count: usize) -> Duration {
+    let exp = crash_count.saturating_sub(1).min(16) as u32;
+    (BACKOFF_BASE * 2u32.pow(exp)).min(BACKOFF_MAX)
+}
+
+fn give_up(app: &AppHandle, reason: &str) {
+    error!("Giving up on sidecar: {}", reason);
+    let handle = app.clone();
+    app.dialog()
+        .message(format!(
+            "The OpenAutoNote engine stopped unexpectedly and could not be restarted.\n\n{}",
+            reason
+        ))
+        .title("OpenAutoNote")
+        .kind(MessageDialogKind::Error)
+        .show(move |_| handle.exit(1));
+}
+
+/// Spawn the sidecar and keep it running, restarting it with exponential
+/// backoff when it exits. `on_first_ready` runs once the first instance
+/// answers the readiness probe; later restarts reload the main window.
+/// Until then, progress goes to the splash screen, and a failed start waits
+/// there for the user to retry.
+pub fn start<F>(app: AppHandle, on_first_ready: F)
+where
+    F: FnOnce(&AppHandle) + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        let supervisor = app.state::<Supervisor>();
+        let port = supervisor.port();
+        let mut crashes: VecDeque<Instant> = VecDeque::new();
+        let auto_retries = app.state::<ReadinessPolicy>().auto_retries;
+        let mut auto_retries_left = auto_retries;
+        // Taken by whichever readiness check succeeds first
+        let on_first_ready = Arc::new(Mutex::new(Some(on_first_ready)));
+
+        loop {
+            let mut held = supervisor.held.subscribe();
+            let _ = held.wait_for(|h| !*h).await;
+            if supervisor.is_stopping() {
+                return;
+            }
+            let first_start = on_first_ready.lock().unwrap().is_some();
+            let auto_retry = first_start && auto_retries_left > 0;
+            supervisor.stderr_tail.lock().unwrap().clear();
+            let (mut rx, child) = match spawn_sidecar(&app, port) {
+                Ok(spawned) => spawned,
+                Err(e) if first_start => {
+                    error!("{}", e);
+                    let retry = supervisor.retry.notified();
+                    startup::fail(&app, e, Vec::new());
+                    retry.await;
+                    continue;
+                }
+                Err(e) => {
+                    give_up(&app, &e);
+                    return;
+                }
+            };
+            let pid = child.pid();
+            app.state::<ProcessRegistry>().record(pid);
+            supervisor.set_child(child);
+            if first_start {
+                startup::progress(
+                    &app,
+                    StartupStage::SidecarSpawned,
+                    format!("Engine process started (PID {})", pid),
+                );
+            }
+
+            // Readiness runs alongside log forwarding so the channel keeps draining
+            let ready_app = app.clone();
+            let first_ready = on_first_ready.clone();
+            tauri::async_runtime::spawn(async move {
+                let ready = wait_until_ready(&ready_app, first_start).await;
+                let supervisor = ready_app.state::<Supervisor>();
+                if ready {
+                    let server_pid = ready_app
+                        .state::<HealthMonitor>()
+                        .latest()
+                        .and_then(|health| health.pid);
+                    if let Some(server_pid) = server_pid {
+                        if supervisor.set_server_pid(pid, server_pid) {
+                            ready_app
+                                .state::<ProcessRegistry>()
+                                .set_server_pid(pid, server_pid);
+                        }
+                    }
+                    let callback = first_ready.lock().unwrap().take();
+                    match callback {
+                        Some(callback) => {
+                            startup::progress(&ready_app, StartupStage::Ready, "Ready");
+                            callback(&ready_app);
+                        }
+                        None => reload_main_window(&ready_app, port),
+                    }
+                } else if !supervisor.is_running() {
+                    // The supervise loop reports exits
+                } else if auto_retry {
+                    warn!("Backend did not become ready in time, restarting it.");
+                    supervisor.restart_now(pid);
+                } else if first_start {
+                    error!("Failed to connect to Python backend after timeout.");
+                    let retry = supervisor.retry.notified();
+                    startup::fail(
+                        &ready_app,
+                        "The engine did not respond in time.",
+                        supervisor.stderr_tail(),
+                    );
+                    retry.await;
+                    supervisor.restart_now(pid);
+                } else {
+                    warn!("Restarted backend did not become ready.");
+                }
+            });
+
+            let exit = forward_events(&mut rx, &supervisor, &app.state::<SecretStore>()).await;
+            supervisor.mark_exited();
+            app.state::<ProcessRegistry>().remove(pid);
+            app.state::<HealthMonitor>().invalidate();
+            if supervisor.is_stopping() {
+                return;
+            }
+            if supervisor.is_held() {
+                info!("Sidecar stopped, holding it until released");
+                crashes.clear();
+                continue;
+            }
+            if supervisor.take_restart_request() {
+                info!("Restarting sidecar on request");
+                if auto_retry {
+                    auto_retries_left -= 1;
+                } else {
+                    auto_retries_left = auto_retries;
+                }
+                crashes.clear();
+                continue;
+            }
+            let exit = exit.unwrap_or_else(|| "event channel closed".to_string());
+            warn!("Sidecar exited ({})", exit);
+
+            // Before the first successful start there is nothing to keep
+            // alive yet, so let the user decide when to try again
+            if on_first_ready.lock().unwrap().is_some() {
+                if auto_retry {
+                    auto_retries_left -= 1;
+                    let delay = backoff_for((auto_retries - auto_retries_left) as usize);
+                    info!("Retrying sidecar start in {:?}", delay);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                let retry = supervisor.retry.notified();
+                startup::fail(
+                    &app,
+                    format!("The engine exited during startup ({}).", exit),
+                    supervisor.stderr_tail(),
+                );
+                retry.await;
+                auto_retries_left = auto_retries;
+                crashes.clear();
+                continue;
+            }
+
+            let now = Instant::now();
+            crashes.push_back(now);
+            while crashes
+                .front()
+                .is_some_and(|t| now.duration_since(*t) > CRASH_WINDOW)
+            {
+                crashes.pop_front();
+            }
+            if crashes.len() >= MAX_CRASHES {
+                give_up(
+                    &app,
+                    &format!(
+                        "The backend crashed {} times within {} minutes (last exit: {}).",
+                        crashes.len(),
+                        CRASH_WINDOW.as_secs() / 60,
+                        exit
+                    ),
+                );
+                return;
+            }
+
+            let delay = backoff_for(crashes.len());
+            info!("Restarting sidecar in {:?}", delay);
+            tokio::time::sleep(delay).await;
+            if supervisor.is_stopping() {
+                return;
+            }
+        }
+    });
+}
+
+fn reload_main_window(app: &AppHandle, port: u16) {
+    if let Some(main) = app.get_webview_window("main") {
+        let url = app.state::<LaunchSecrets>().login_url(&backend_url(port));
+        match url.parse() {
+            Ok(url) => {
+                if let Err(e) = main.navigate(url) {
+                    warn!("Failed to reload main window: {}", e);
+                }
+            }
+            Err(e) => error!("Invalid backend URL: {}", e),
+        }
+    }
+}
+
+/// Ask the sidecar to exit over HTTP, then kill it if it is still running
+/// once `SHUTDOWN_GRACE` has passed.
+pub async fn shutdown(app: &AppHandle) {
+    app.state::<Supervisor>().begin_stop();
+    stop_gracefully(app).await;
+}
+
+/// Stop the sidecar the same way as `shutdown`, but start it again once
+/// `Supervisor::release` is called. Returns after it has exited.
+pub async fn hold(app: &AppHandle) {
+    app.state::<Supervisor>().held.send_replace(true);
+    stop_gracefully(app).await;
+}
+
+async fn stop_gracefully(app: &AppHandle) {
+    let supervisor = app.state::<Supervisor>();
+    let mut running = supervisor.running.subscribe();
+    if !*running.borrow() {
+        return;
+    }
+
+    if let Err(e) = request_graceful_stop(app, supervisor.port()).await {
+        warn!("Graceful stop request failed: {}", e);
+    }
+    let exited = tokio::time::timeout(SHUTDOWN_GRACE, running.wait_for(|r| !*r))
+        .await
+        .is_ok();
+
+    if !exited {
+        if let Some(pid) = supervisor.kill() {
+            warn!("Sidecar did not exit in time, killed PID {}", pid);
+            app.state::<ProcessRegistry>().remove(pid);
+        }
+    }
+}
+
+async fn request_graceful_stop(app: &AppHandle, port: u16) -> Result<(), reqwest::Error> {
+    let bearer = app.state::<LaunchSecrets>().bearer();
+    reqwest::Client::new()
+        .post(format!("{}{}", backend_url(port), SHUTDOWN_PATH))
+        .header(reqwest::header::AUTHORIZATION, bearer)
+        .timeout(Duration::from_secs(2))
+        .send()
+        .await?
+        .error_for_status()
+        .map(|_| ())
+}
@@ -0,0 +1,36 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty directory for one test, removed again when it is dropped, so
+/// failed tests don't leave it behind either.
+pub struct ScratchDir(PathBuf);
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// `oan-<module>-<name>-<pid>` in the temp dir, emptied if a previous run
+/// left it.
+pub fn scratch_dir(module: &str, name: &str) -> ScratchDir {
+    let dir = std::env::temp_dir().join(format!("oan-{}-{}-{}", module, name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    ScratchDir(dir)
+}
@@ -0,0 +1,79 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A point in time broken into UTC calendar fields; std has no time zones
+struct Utc {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+}
+
+impl Utc {
+    fn from(time: SystemTime) -> Self {
+        let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let (days, rem) = (secs / 86_400, secs % 86_400);
+        // Howard Hinnant's civil_from_days
+        let z = days as i64 + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        Self {
+            year: yoe + era * 400 + i64::from(month <= 2),
+            month,
+            day,
+            hour: rem / 3600,
+            minute: rem / 60 % 60,
+            second: rem % 60,
+        }
+    }
+}
+
+/// `2024-05-01T09:30:00Z`, as `dcterms:modified` wants it.
+pub fn iso8601(time: SystemTime) -> String {
+    let t = Utc::from(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+/// `20240501_093000`, the format of a session's `timestamp`. The sidecar
+/// writes local time; this is UTC.
+pub fn session_timestamp(time: SystemTime) -> String {
+    let t = Utc::from(time);
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::session_date;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn formats_utc_calendar_time() {
+        assert_eq!(iso8601(at(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(iso8601(at(1_714_555_800)), "2024-05-01T09:30:00Z");
+        assert_eq!(iso8601(at(951_868_799)), "2000-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn session_timestamps_match_the_sidecar_format() {
+        let timestamp = session_timestamp(at(1_714_555_800));
+        assert_eq!(timestamp, "20240501_093000");
+        assert_eq!(session_date(&timestamp).as_deref(), Some("2024-05-01"));
+    }
+}
@@ -0,0 +1,491 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+
+use crate::export;
+use crate::history::HistoryStore;
+use crate::jobs;
+use crate::launch_inputs::LaunchInput;
+use crate::library::Library;
+use crate::media;
+
+const WATCH_FILE: &str = "watch_folders.json";
+
+// Polled rather than watched: shared and network folders often don't
+// deliver change events
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+// A file counts as finished once its size and mtime hold still this long
+const STABLE_FOR: Duration = Duration::from_secs(10);
+
+// Same sampling as generate_video_hash in python-core/core/downloader.py
+const SAMPLE_SIZE: u64 = 4096;
+
+/// A file seen in a watch folder, by content hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedFile {
+    pub hash: String,
+    pub path: PathBuf,
+    /// `None` for files that were already there when the folder was added.
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchState {
+    #[serde(default)]
+    pub folders: Vec<PathBuf>,
+    #[serde(default)]
+    pub imported: Vec<ImportedFile>,
+}
+
+/// Folders whose new media files are queued for processing automatically.
+pub struct WatchFolders {
+    path: PathBuf,
+    state: Mutex<WatchState>,
+    rescan: Notify,
+}
+
+impl WatchFolders {
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(WATCH_FILE);
+        let state = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(state) => Some(state),
+                Err(e) => {
+                    warn!("Ignoring invalid {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            state: Mutex::new(state),
+            rescan: Notify::new(),
+        }
+    }
+
+    fn folders(&self) -> Vec<PathBuf> {
+        self.state.lock().unwrap().folders.clone()
+    }
+
+    fn is_known(&self, hash: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .imported
+            .iter()
+            .any(|f| f.hash == hash)
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut WatchState) -> T) -> T {
+        let mut state = self.state.lock().unwrap();
+        let result = f(&mut state);
+        if let Err(e) = self.save(&state) {
+            warn!("Failed to save {}: {}", self.path.display(), e);
+        }
+        result
+    }
+
+    fn save(&self, state: &WatchState) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.path).map_err(|e| e.to_string())
+    }
+
+    fn record(&self, files: Vec<ImportedFile>) {
+        if files.is_empty() {
+            return;
+        }
+        self.update(|state| state.imported.extend(files));
+    }
+}
+
+/// sha256 of the size plus 4 KiB from the start, middle and end of the
+/// file. Matches `generate_video_hash` so both sides agree on duplicates.
+pub fn sample_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_string().as_bytes());
+
+    let mut buf = Vec::with_capacity(SAMPLE_SIZE as usize);
+    let mut sample = |file: &mut File, offset: u64| -> io::Result<()> {
+        file.seek(SeekFrom::Start(offset))?;
+        buf.clear();
+        file.by_ref().take(SAMPLE_SIZE).read_to_end(&mut buf)?;
+        hasher.update(&buf);
+        Ok(())
+    };
+    sample(&mut file, 0)?;
+    if size > SAMPLE_SIZE * 3 {
+        sample(&mut file, size / 2)?;
+    }
+    if size > SAMPLE_SIZE {
+        sample(&mut file, size - SAMPLE_SIZE)?;
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn is_media(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.'));
+    !hidden && media::has_media_extension(path)
+}
+
+type Stamp = (u64, Option<SystemTime>);
+
+fn media_files(folder: &Path) -> Vec<(PathBuf, Stamp)> {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read {}: {}", folder.display(), e);
+            return Vec::new();
+        }
+    };
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_media(p))
+        .filter_map(|p| {
+            let meta = fs::metadata(&p).ok().filter(|m| m.is_file())?;
+            Some((p, (meta.len(), meta.modified().ok())))
+        })
+        .collect()
+}
+
+// Already processed some other way, e.g. opened by hand before the folder
+// was watched. Only sessions that recorded a `video_hash` can match; older
+// ones can't be backfilled because the history doesn't keep the path of a
+// local source file, so their videos are imported again.
+fn is_in_history(app: &AppHandle, hash: &str) -> bool {
+    app.state::<HistoryStore>()
+        .has_video_hash(hash)
+        .unwrap_or_else(|e| {
+            warn!("Failed to check history for duplicates: {}", e);
+            false
+        })
+}
+
+/// Tracks files between scans until they stop changing.
+#[derive(Default)]
+struct Scanner {
+    // Still being written, with when their current size was first seen
+    pending: HashMap<PathBuf, (Stamp, Instant)>,
+    // Already hashed and handled at this size
+    settled: HashMap<PathBuf, Stamp>,
+}
+
+impl Scanner {
+    /// Note the current `files` and return those that have held still for
+    /// `STABLE_FOR` and haven't been handled at this size yet.
+    fn poll(&mut self, files: Vec<(PathBuf, Stamp)>, now: Instant) -> Vec<(PathBuf, Stamp)> {
+        let mut present = HashSet::new();
+        let mut ready = Vec::new();
+        for (path, stamp) in files {
+            present.insert(path.clone());
+            if self.settled.get(&path) == Some(&stamp) {
+                continue;
+            }
+            match self.pending.get(&path) {
+                Some((seen, since)) if *seen == stamp => {
+                    if stamp.0 > 0 && now.duration_since(*since) >= STABLE_FOR {
+                        ready.push((path, stamp));
+                    }
+                }
+                _ => {
+                    self.pending.insert(path, (stamp, now));
+                }
+            }
+        }
+        self.pending.retain(|path, _| present.contains(path));
+        self.settled.retain(|path, _| present.contains(path));
+        ready
+    }
+
+    // Handled at this size; not returned again until it changes
+    fn settle(&mut self, path: &Path, stamp: Stamp) {
+        self.pending.remove(path);
+        self.settled.insert(path.to_path_buf(), stamp);
+    }
+
+    fn scan(&mut self, app: &AppHandle) {
+        let watch = app.state::<WatchFolders>();
+        let files = watch
+            .folders()
+            .iter()
+            .flat_map(|folder| media_files(folder))
+            .collect();
+        let ready = self.poll(files, Instant::now());
+
+        let mut imported = Vec::new();
+        for (path, stamp) in ready {
+            let hash = match sample_hash(&path) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    // Probably still locked by whatever is writing it
+                    warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            self.settle(&path, stamp);
+            let duplicate = watch.is_known(&hash)
+                || imported.iter().any(|f: &ImportedFile| f.hash == hash)
+                || is_in_history(app, &hash);
+            if duplicate {
+                info!("Skipping duplicate {}", path.display());
+                continue;
+            }
+            info!("New file in watch folder: {}", path.display());
+            let job_id = jobs::enqueue(app, vec![LaunchInput::File(path.clone())])
+                .first()
+                .map(|job| job.id.clone());
+            imported.push(ImportedFile { hash, path, job_id });
+        }
+        watch.record(imported);
+    }
+}
+
+/// Scan the watch folders every few seconds for the life of the app.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let watch = app.state::<WatchFolders>();
+        let mut scanner = Scanner::default();
+        loop {
+            let handle = app.clone();
+            scanner = tauri::async_runtime::spawn_blocking(move || {
+                scanner.scan(&handle);
+                scanner
+            })
+            .await
+            .unwrap_or_default();
+            tokio::select! {
+                _ = watch.rescan.notified() => {}
+                _ = tokio::time::sleep(SCAN_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+fn add_folder(app: &AppHandle, folder: &Path, import_existing: bool) -> Result<PathBuf, String> {
+    let folder = folder
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", folder.display(), e))?;
+    if !folder.is_dir() {
+        return Err(format!("{} is not a folder", folder.display()));
+    }
+    // generate/ holds downloaded videos, which would be imported again
+    let library = app.state::<Library>().dir();
+    if folder.starts_with(&library) || library.starts_with(&folder) {
+        return Err("A watch folder can't contain or be inside the library".to_string());
+    }
+    let watch = app.state::<WatchFolders>();
+    if watch.folders().contains(&folder) {
+        return Ok(folder);
+    }
+
+    // Remember what is already there so only new files get imported
+    if !import_existing {
+        let existing: Vec<ImportedFile> = media_files(&folder)
+            .into_iter()
+            .filter_map(|(path, _)| {
+                let hash = sample_hash(&path).ok()?;
+                Some(ImportedFile {
+                    hash,
+                    path,
+                    job_id: None,
+                })
+            })
+            .collect();
+        watch.record(existing);
+    }
+    watch.update(|state| state.folders.push(folder.clone()));
+    watch.rescan.notify_one();
+    info!("Watching {} for new recordings", folder.display());
+    Ok(folder)
+}
+
+#[tauri::command]
+pub fn list_watch_folders(watch: State<'_, WatchFolders>) -> Vec<PathBuf> {
+    watch.folders()
+}
+
+/// Start watching `path`, or a folder picked with the native dialog. Files
+/// already in it are skipped unless `import_existing` is set. Returns the
+/// folder as stored, or `None` if the user cancelled.
+#[tauri::command]
+pub async fn add_watch_folder(
+    app: AppHandle,
+    path: Option<String>,
+    import_existing: Option<bool>,
+) -> Result<Option<String>, String> {
+    let handle = app.clone();
+    let folder = tauri::async_runtime::spawn_blocking(move || {
+        let Some(path) = path
+            .map(PathBuf::from)
+            .or_else(|| export::pick_folder(&handle))
+        else {
+            return Ok(None);
+        };
+        add_folder(&handle, &path, import_existing.unwrap_or(false)).map(Some)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    Ok(folder.map(|f| f.to_string_lossy().into_owned()))
+}
+
+/// Stop watching `path`. Files already queued from it stay queued.
+#[tauri::command]
+pub fn remove_watch_folder(watch: State<'_, WatchFolders>, path: String) -> Result<(), String> {
+    // Stored canonical; a folder that is gone can only be matched as given
+    let path = PathBuf::from(path);
+    let path = path.canonicalize().unwrap_or(path);
+    let removed = watch.update(|state| {
+        let before = state.folders.len();
+        state.folders.retain(|f| *f != path);
+        state.folders.len() != before
+    });
+    if !removed {
+        return Err(format!("{} is not a watch folder", path.display()));
+    }
+    info!("Stopped watching {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir;
+
+    #[test]
+    fn sample_hash_matches_generate_video_hash() {
+        // generate_video_hash from core/downloader.py on the same files
+        let expected = [
+            (
+                0,
+                "5feceb66ffc86f38d952786c6d696c79c2dbc239dd4e91b46729d73a27fb57e9",
+            ),
+            (
+                100,
+                "c363b99b961be47030f42eea29608c2a78fb5cc5e77a87b25312f1a3fad3f23a",
+            ),
+            (
+                4096,
+                "31bfd0345ebe421721c814f3e620269c58a5154d5255bafb7e5b125a66063505",
+            ),
+            (
+                4097,
+                "427735da39786e21fca6a538431f662bbefb3d0ff68d56dda4b5913024f8435c",
+            ),
+            (
+                12289,
+                "171e6199ceef1583d6cc716edc02da9c47b45e9e3a9a0898e7e3f3fc979d16be",
+            ),
+            (
+                50000,
+                "4976addffb5180f6901feb4072c8cdba85fa97f98949459d24d2ce16c096ac71",
+            ),
+        ];
+        let dir = scratch_dir("watch", "hash");
+        for (size, hash) in expected {
+            let path = dir.join(format!("{}.mp4", size));
+            let bytes: Vec<u8> = (0..size).map(|i: u32| ((i * 7 + 3) % 251) as u8).collect();
+            fs::write(&path, bytes).unwrap();
+            assert_eq!(sample_hash(&path).unwrap(), hash, "{} bytes", size);
+        }
+    }
+
+    fn stamp(size: u64) -> Stamp {
+        (size, Some(SystemTime::UNIX_EPOCH))
+    }
+
+    #[test]
+    fn files_are_ready_once_their_size_holds_still() {
+        let path = PathBuf::from("talk.mp4");
+        let start = Instant::now();
+        let mut scanner = Scanner::default();
+
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(10))], start)
+            .is_empty());
+        // Still growing: the wait starts over
+        let grown = start + STABLE_FOR;
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(20))], grown)
+            .is_empty());
+        let almost = grown + STABLE_FOR - Duration::from_secs(1);
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(20))], almost)
+            .is_empty());
+        let ready = scanner.poll(vec![(path.clone(), stamp(20))], grown + STABLE_FOR);
+        assert_eq!(ready, vec![(path, stamp(20))]);
+    }
+
+    #[test]
+    fn empty_files_are_never_ready() {
+        let path = PathBuf::from("talk.mp4");
+        let start = Instant::now();
+        let mut scanner = Scanner::default();
+        scanner.poll(vec![(path.clone(), stamp(0))], start);
+        let later = start + STABLE_FOR * 3;
+        assert!(scanner.poll(vec![(path, stamp(0))], later).is_empty());
+    }
+
+    #[test]
+    fn settled_files_come_back_only_when_changed() {
+        let path = PathBuf::from("talk.mp4");
+        let start = Instant::now();
+        let mut scanner = Scanner::default();
+        scanner.poll(vec![(path.clone(), stamp(10))], start);
+        let ready = scanner.poll(vec![(path.clone(), stamp(10))], start + STABLE_FOR);
+        assert_eq!(ready.len(), 1);
+        scanner.settle(&path, stamp(10));
+
+        let later = start + STABLE_FOR * 3;
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(10))], later)
+            .is_empty());
+        // Overwritten with a new recording of another size
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(30))], later)
+            .is_empty());
+        let ready = scanner.poll(vec![(path.clone(), stamp(30))], later + STABLE_FOR);
+        assert_eq!(ready, vec![(path, stamp(30))]);
+    }
+
+    #[test]
+    fn files_that_disappear_are_forgotten() {
+        let path = PathBuf::from("talk.mp4");
+        let start = Instant::now();
+        let mut scanner = Scanner::default();
+        scanner.poll(vec![(path.clone(), stamp(10))], start);
+        scanner.settle(&path, stamp(10));
+        scanner.poll(Vec::new(), start + STABLE_FOR);
+        assert!(scanner.pending.is_empty() && scanner.settled.is_empty());
+
+        // Put back, it has to hold still again before it is ready
+        let back = start + STABLE_FOR * 2;
+        assert!(scanner
+            .poll(vec![(path.clone(), stamp(10))], back)
+            .is_empty());
+        assert_eq!(
+            scanner.poll(vec![(path.clone(), stamp(10))], back + STABLE_FOR),
+            vec![(path, stamp(10))]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{info, warn};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::history::{HistoryStore, Session, MISSING_STATUS};
+use crate::search::SearchIndex;
+
+const HISTORY_EVENT: &str = "history-changed";
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What the watcher changed in the history after folders moved on disk.
+#[derive(Debug, Default, Serialize)]
+pub struct HistoryChange {
+    /// Sessions whose folder disappeared. They are flagged as missing
+    /// rather than deleted, so the user decides what to do with them.
+    pub missing: Vec<String>,
+    /// Previously missing sessions whose folder is back.
+    pub found: Vec<String>,
+    /// Sessions whose folder was renamed, with the new folder.
+    pub moved: Vec<MovedSession>,
+    /// Sessions created for finished project folders added to generate/.
+    pub added: Vec<String>,
+    /// Folders in generate/ that no session refers to and that weren't
+    /// added, either because they have no report.md or because the sidecar
+    /// was busy.
+    pub untracked: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MovedSession {
+    pub id: String,
+    pub project_dir: String,
+}
+
+/// Holds the active generate/ watch; replacing it stops the old one.
+#[derive(Default)]
+pub struct GenerateWatcher(Mutex<Option<Debouncer<RecommendedWatcher>>>);
+
+/// Identity of a folder that survives renames, so a renamed project folder
+/// can be matched back to its session.
+#[cfg(unix)]
+fn folder_id(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = fs::metadata(path).ok()?;
+    // Inode numbers are reused once a folder is deleted; the birth time,
+    // where the filesystem has one, tells a new folder from a renamed one
+    let born = meta
+        .created()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    Some(format!("{}:{}:{}", meta.dev(), meta.ino(), born))
+}
+
+// File IDs aren't exposed on stable Windows; NTFS keeps creation time on rename
+#[cfg(not(unix))]
+fn folder_id(path: &Path) -> Option<String> {
+    let created = fs::metadata(path).ok()?.created().ok()?;
+    let nanos = created
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    Some(nanos.to_string())
+}
+
+/// Watch `dir` (the library's generate/) for project folders being added,
+/// removed or renamed outside the app, and bring the history in line.
+/// Replaces any earlier watch.
+pub fn start(app: &AppHandle, dir: &Path) -> Result<(), String> {
+    let dir = dir.to_path_buf();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Session id -> folder identity, as of the last time its folder existed
+    let known: Arc<Mutex<HashMap<String, String>>> = Arc::default();
+
+    let handle = app.clone();
+    let watch_dir = dir.clone();
+    let watch_known = known.clone();
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+        Ok(_) => reconcile(&handle, &watch_dir, &watch_known),
+        Err(e) => warn!("Watch error on {}: {}", watch_dir.display(), e),
+    })
+    .map_err(|e| e.to_string())?;
+    debouncer
+        .watcher()
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| e.to_string())?;
+    *app.state::<GenerateWatcher>().0.lock().unwrap() = Some(debouncer);
+    info!("Watching {}", dir.display());
+
+    // Catch up with anything removed while the app was closed
+    reconcile(app, &dir, &known);
+    Ok(())
+}
+
+fn reconcile(app: &AppHandle, dir: &Path, known: &Mutex<HashMap<String, String>>) {
+    let store = app.state::<HistoryStore>();
+    let change = match sync(&store, dir, known) {
+        Ok(change) => change,
+        Err(e) => {
+            warn!("Not syncing the history with project folders: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = app.state::<SearchIndex>().refresh(&store) {
+        warn!("Failed to refresh search index: {}", e);
+    }
+    let _ = app.emit_to("main", HISTORY_EVENT, &change);
+}
+
+/// Bring the history in line with the project folders in `dir`. `known`
+/// maps session ids to folder identities and is kept up to date here.
+/// Nothing is changed if `dir` can't be listed: on an unplugged disk or an
+/// unmounted share every folder would look deleted.
+fn sync(
+    store: &HistoryStore,
+    dir: &Path,
+    known: &Mutex<HashMap<String, String>>,
+) -> Result<HistoryChange, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let sessions = store.list().map_err(|e| e.to_string())?;
+    let mut known = known.lock().unwrap();
+    let status = |s: &Session| {
+        s.extra
+            .get("status")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+    };
+    let is_processing = |s: &Session| status(s).as_deref() == Some("processing");
+    // The sidecar renames a task folder into place before it updates the
+    // session, so folders showing up meanwhile may be about to be claimed
+    let busy = sessions.iter().any(is_processing);
+
+    let mut unclaimed: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter(|p| !sessions.iter().any(|s| Path::new(&s.project_dir) == p))
+        .collect();
+
+    let mut change = HistoryChange::default();
+    for mut session in sessions {
+        let was_missing = status(&session).as_deref() == Some(MISSING_STATUS);
+        let project_dir = Path::new(&session.project_dir);
+        if project_dir.is_dir() {
+            if let Some(id) = folder_id(project_dir) {
+                known.insert(session.id.clone(), id);
+            }
+            if was_missing {
+                match store.set_missing(&session.id, false) {
+                    Ok(()) => change.found.push(session.id),
+                    Err(e) => warn!("Failed to update session {}: {}", session.id, e),
+                }
+            }
+            continue;
+        }
+        // The sidecar renames the task folder itself when processing finishes
+        if is_processing(&session) {
+            continue;
+        }
+
+        let renamed = known.get(&session.id).and_then(|id| {
+            unclaimed
+                .iter()
+                .position(|p| folder_id(p).as_ref() == Some(id))
+        });
+        match renamed {
+            Some(index) => {
+                let new_dir = unclaimed.remove(index);
+                info!(
+                    "Project folder moved: {} -> {}",
+                    session.project_dir,
+                    new_dir.display()
+                );
+                session.move_to(&new_dir);
+                let updated = store.update_location(&session).and_then(|()| {
+                    if was_missing {
+                        store.set_missing(&session.id, false)?;
+                    }
+                    Ok(())
+                });
+                if let Err(e) = updated {
+                    warn!("Failed to update session {}: {}", session.id, e);
+                    continue;
+                }
+                change.moved.push(MovedSession {
+                    id: session.id,
+                    project_dir: session.project_dir,
+                });
+            }
+            None if was_missing => {}
+            None => {
+                info!(
+                    "Project folder missing: {} ({})",
+                    session.title, session.project_dir
+                );
+                if let Err(e) = store.set_missing(&session.id, true) {
+                    warn!("Failed to update session {}: {}", session.id, e);
+                    continue;
+                }
+                change.missing.push(session.id);
+            }
+        }
+    }
+
+    for dir in unclaimed {
+        let session = match Session::from_folder(&dir).filter(|_| !busy) {
+            Some(session) => session,
+            None => {
+                change.untracked.push(dir);
+                continue;
+            }
+        };
+        info!("Adding project folder: {}", dir.display());
+        if let Err(e) = store.add(&session) {
+            warn!("Failed to add {}: {}", dir.display(), e);
+            change.untracked.push(dir);
+            continue;
+        }
+        if let Some(id) = folder_id(&dir) {
+            known.insert(session.id.clone(), id);
+        }
+        change.added.push(session.id);
+    }
+    Ok(change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, ScratchDir};
+    use serde_json::json;
+
+    fn scratch_dir(name: &str) -> ScratchDir {
+        let dir = test_util::scratch_dir("watcher", name);
+        fs::create_dir_all(dir.join("generate")).unwrap();
+        dir
+    }
+
+    fn session(id: &str, project_dir: &Path, status: Option<&str>) -> Session {
+        let mut session: Session = serde_json::from_value(json!({
+            "id": id,
+            "timestamp": "20240501_093000",
+            "title": id,
+            "summary": format!(
+                "![frame](/generate/{}/assets/1.jpg)",
+                project_dir.file_name().unwrap().to_string_lossy()
+            ),
+            "project_dir": project_dir,
+        }))
+        .unwrap();
+        if let Some(status) = status {
+            session.extra.insert("status".into(), json!(status));
+        }
+        session
+    }
+
+    fn is_missing(store: &HistoryStore, id: &str) -> bool {
+        let session = store.get(id).unwrap().unwrap();
+        session.extra.get("status") == Some(&json!(MISSING_STATUS))
+    }
+
+    fn project(generate: &Path, name: &str, report: bool) -> PathBuf {
+        let dir = generate.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        if report {
+            fs::write(dir.join(crate::export::REPORT_FILE), "# Notes").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn renamed_folder_keeps_its_session() {
+        let root = scratch_dir("rename");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let known = Mutex::default();
+        let old_dir = project(&generate, "Lecture", true);
+        store.add(&session("a", &old_dir, None)).unwrap();
+        sync(&store, &generate, &known).unwrap();
+
+        let new_dir = generate.join("Lecture 1");
+        fs::rename(&old_dir, &new_dir).unwrap();
+        let change = sync(&store, &generate, &known).unwrap();
+
+        assert_eq!(change.moved.len(), 1);
+        assert!(change.missing.is_empty() && change.added.is_empty());
+        let moved = store.get("a").unwrap().unwrap();
+        assert_eq!(Path::new(&moved.project_dir), new_dir);
+        assert_eq!(moved.summary, "![frame](/generate/Lecture 1/assets/1.jpg)");
+    }
+
+    #[test]
+    fn rename_is_matched_by_identity_not_name() {
+        let root = scratch_dir("identity");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let known = Mutex::default();
+        let old_dir = project(&generate, "Talk", true);
+        store.add(&session("a", &old_dir, None)).unwrap();
+        sync(&store, &generate, &known).unwrap();
+
+        // A different folder appears while the original is deleted
+        fs::remove_dir_all(&old_dir).unwrap();
+        let other = project(&generate, "Talk 2", false);
+        let change = sync(&store, &generate, &known).unwrap();
+
+        assert_eq!(change.missing, ["a"]);
+        assert!(change.moved.is_empty());
+        assert_eq!(change.untracked, [other]);
+        assert!(is_missing(&store, "a"));
+    }
+
+    #[test]
+    fn rename_before_first_sync_is_not_matched() {
+        let root = scratch_dir("unknown");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let old_dir = generate.join("Gone");
+        store.add(&session("a", &old_dir, None)).unwrap();
+        project(&generate, "Elsewhere", false);
+
+        let change = sync(&store, &generate, &Mutex::default()).unwrap();
+        assert_eq!(change.missing, ["a"]);
+        assert_eq!(change.untracked.len(), 1);
+    }
+
+    #[test]
+    fn missing_folders_are_flagged_once_and_cleared_when_back() {
+        let root = scratch_dir("missing");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let known = Mutex::default();
+        let dir = project(&generate, "Seminar", true);
+        store.add(&session("a", &dir, None)).unwrap();
+        sync(&store, &generate, &known).unwrap();
+
+        let aside = root.join("Seminar");
+        fs::rename(&dir, &aside).unwrap();
+        assert_eq!(sync(&store, &generate, &known).unwrap().missing, ["a"]);
+        assert!(is_missing(&store, "a"));
+        assert!(sync(&store, &generate, &known).unwrap().missing.is_empty());
+
+        fs::rename(&aside, &dir).unwrap();
+        let change = sync(&store, &generate, &known).unwrap();
+        assert_eq!(change.found, ["a"]);
+        assert!(!is_missing(&store, "a"));
+    }
+
+    #[test]
+    fn unreadable_generate_dir_changes_nothing() {
+        let root = scratch_dir("unplugged");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        store
+            .add(&session("a", &generate.join("Lecture"), None))
+            .unwrap();
+        fs::remove_dir_all(&generate).unwrap();
+
+        assert!(sync(&store, &generate, &Mutex::default()).is_err());
+        assert!(!is_missing(&store, "a"));
+    }
+
+    #[test]
+    fn processing_sessions_are_left_alone() {
+        let root = scratch_dir("processing");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let task_dir = generate.join("20240501_093000_abcd1234");
+        store
+            .add(&session("task", &task_dir, Some("processing")))
+            .unwrap();
+        let finished = project(&generate, "Finished", true);
+
+        let change = sync(&store, &generate, &Mutex::default()).unwrap();
+        assert!(change.missing.is_empty() && change.added.is_empty());
+        assert_eq!(change.untracked, [finished]);
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn finished_folders_are_added() {
+        let root = scratch_dir("added");
+        let generate = root.join("generate");
+        let store = HistoryStore::open(&root).unwrap();
+        let known = Mutex::default();
+        let restored = project(&generate, "Restored", true);
+        let partial = project(&generate, "Partial", false);
+
+        let change = sync(&store, &generate, &known).unwrap();
+        assert_eq!(change.added.len(), 1);
+        assert_eq!(change.untracked, [partial]);
+        let added = store.get(&change.added[0]).unwrap().unwrap();
+        assert_eq!(added.title, "Restored");
+        assert_eq!(added.summary, "# Notes");
+        assert_eq!(Path::new(&added.project_dir), restored);
+        assert_eq!(added.id.len(), 36);
+        assert_eq!(&added.id[14..15], "4");
+
+        // Later renames of the added folder are tracked too
+        let renamed = generate.join("Restored again");
+        fs::rename(&restored, &renamed).unwrap();
+        let change = sync(&store, &generate, &known).unwrap();
+        assert_eq!(change.moved.len(), 1);
+        assert!(change.added.is_empty());
+    }
+}